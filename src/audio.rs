@@ -0,0 +1,525 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use log::debug;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+// Whisper expects 16 kHz mono
+pub const WHISPER_SAMPLE_RATE: u32 = 16000;
+
+// Decodes WAV, FLAC, MP3 or OGG into 16 kHz mono samples ready for Whisper
+pub fn load_audio_file(path: &str) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
+    let (samples, sample_rate, channels) = if is_wav(path)? {
+        read_wav(path)?
+    } else {
+        decode_compressed(path)?
+    };
+
+    let mono = downmix(&samples, channels);
+    Ok(resample(&mono, sample_rate, WHISPER_SAMPLE_RATE))
+}
+
+// Trust the RIFF header over the extension, so misnamed files still take the fast path
+fn is_wav(path: &str) -> io::Result<bool> {
+    let mut header = [0u8; 12];
+    let mut file = File::open(path)?;
+    let read = file.read(&mut header)?;
+    Ok(read == 12 && &header[0..4] == b"RIFF" && &header[8..12] == b"WAVE")
+}
+
+fn read_wav(path: &str) -> Result<(Vec<f32>, u32, usize), Box<dyn std::error::Error>> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+
+    Ok((samples, spec.sample_rate, spec.channels as usize))
+}
+
+fn decode_compressed(path: &str) -> Result<(Vec<f32>, u32, usize), Box<dyn std::error::Error>> {
+    let file = File::open(path)?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = Path::new(path).extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    let probed = symphonia::default::get_probe()
+        .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(|e| format!("Unsupported audio file {}: {}", path, e))?;
+    let mut format = probed.format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| format!("No audio track in {}", path))?;
+    let track_id = track.id;
+    let mut sample_rate = track.codec_params.sample_rate.unwrap_or(WHISPER_SAMPLE_RATE);
+    let mut channels = track.codec_params.channels.map(|c| c.count()).unwrap_or(1);
+    let mut decoder = symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let mut samples = Vec::new();
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A corrupt packet shouldn't throw away the rest of the file
+            Err(SymphoniaError::DecodeError(e)) => {
+                debug!("Skipping undecodable packet: {}", e);
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let spec = *decoded.spec();
+        sample_rate = spec.rate;
+        channels = spec.channels.count();
+
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+        samples.extend_from_slice(buffer.samples());
+    }
+
+    Ok((samples, sample_rate, channels))
+}
+
+// Averages interleaved frames into a single channel
+pub fn downmix(samples: &[f32], channels: usize) -> Vec<f32> {
+    if channels <= 1 {
+        return samples.to_vec();
+    }
+    samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect()
+}
+
+// Peaks below this are treated as silence and never amplified, which would only
+// turn the noise floor into something Whisper tries to transcribe
+const NORMALIZE_SILENCE_PEAK: f32 = 0.01;
+
+// Scales the buffer so its loudest sample reaches `target_peak`; returns the gain used
+pub fn normalize_peak(samples: &mut [f32], target_peak: f32) -> f32 {
+    let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    if peak < NORMALIZE_SILENCE_PEAK {
+        return 1.0;
+    }
+    let gain = target_peak / peak;
+    for sample in samples.iter_mut() {
+        *sample *= gain;
+    }
+    gain
+}
+
+// Length of the windows the loudness is measured over
+const SILENCE_WINDOW_MS: u64 = 20;
+
+// Drops the quiet stretches before the first and after the last window whose RMS reaches
+// `threshold`, keeping `padding` on each side so soft word edges survive. Mono input; a
+// buffer with no window above the threshold is returned whole rather than emptied.
+pub fn trim_silence(samples: &[f32], threshold: f32, sample_rate: u32, padding: std::time::Duration) -> &[f32] {
+    let window_len = (sample_rate as u64 * SILENCE_WINDOW_MS / 1000).max(1) as usize;
+    let loud = |window: &[f32]| window_rms(window) >= threshold;
+    let windows: Vec<bool> = samples.chunks(window_len).map(loud).collect();
+    
+    let (Some(first), Some(last)) = (windows.iter().position(|&l| l), windows.iter().rposition(|&l| l)) else {
+        return samples;
+    };
+    let padding = (padding.as_secs_f64() * sample_rate as f64) as usize;
+    let start = (first * window_len).saturating_sub(padding);
+    let end = ((last + 1) * window_len + padding).min(samples.len());
+    &samples[start..end]
+}
+
+fn window_rms(window: &[f32]) -> f32 {
+    (window.iter().map(|s| s * s).sum::<f32>() / window.len() as f32).sqrt()
+}
+
+// Calibration's headroom over the room noise: twice the RMS, about 6 dB
+const CALIBRATION_HEADROOM: f32 = 2.0;
+// Even a silent room gets a threshold that digital noise stays under
+const MIN_SILENCE_THRESHOLD: f32 = 0.002;
+
+// The level of a recording of the room with nobody talking: the RMS that 95% of the
+// windows stay under, so the odd click or cough doesn't raise it
+pub fn noise_floor(samples: &[f32], sample_rate: u32) -> f32 {
+    let window_len = (sample_rate as u64 * SILENCE_WINDOW_MS / 1000).max(1) as usize;
+    let mut levels: Vec<f32> = samples.chunks(window_len).map(window_rms).collect();
+    if levels.is_empty() {
+        return 0.0;
+    }
+    levels.sort_by(f32::total_cmp);
+    levels[(levels.len() - 1) * 95 / 100]
+}
+
+// Rounded up to four decimals so it reads well in the settings file
+pub fn suggested_threshold(noise_floor: f32) -> f32 {
+    let threshold = (noise_floor * CALIBRATION_HEADROOM).max(MIN_SILENCE_THRESHOLD);
+    (threshold * 10000.0).ceil() / 10000.0
+}
+
+// Decides when a live recording has gone quiet: RMS below `threshold` for `duration`.
+// Silence only counts after some speech, so the pause before the user starts talking
+// never ends the recording.
+pub struct SilenceDetector {
+    threshold: f32,
+    window_len: usize,
+    required_windows: usize,
+    sum_squares: f32,
+    window_filled: usize,
+    heard_speech: bool,
+    quiet_windows: usize,
+    triggered: bool,
+}
+
+impl SilenceDetector {
+    // `samples_per_second` counts every channel of interleaved input
+    pub fn new(threshold: f32, duration: std::time::Duration, samples_per_second: u32) -> Self {
+        let window_len = (samples_per_second as u64 * SILENCE_WINDOW_MS / 1000).max(1) as usize;
+        SilenceDetector {
+            threshold,
+            window_len,
+            required_windows: (duration.as_millis() as u64).div_ceil(SILENCE_WINDOW_MS).max(1) as usize,
+            sum_squares: 0.0,
+            window_filled: 0,
+            heard_speech: false,
+            quiet_windows: 0,
+            triggered: false,
+        }
+    }
+
+    // Returns true once the recording should stop
+    pub fn feed(&mut self, samples: &[f32]) -> bool {
+        for &sample in samples {
+            self.sum_squares += sample * sample;
+            self.window_filled += 1;
+            if self.window_filled < self.window_len {
+                continue;
+            }
+
+            let rms = (self.sum_squares / self.window_len as f32).sqrt();
+            self.sum_squares = 0.0;
+            self.window_filled = 0;
+            if rms >= self.threshold {
+                self.heard_speech = true;
+                self.quiet_windows = 0;
+            } else if self.heard_speech {
+                self.quiet_windows += 1;
+                if self.quiet_windows >= self.required_windows {
+                    self.triggered = true;
+                }
+            }
+        }
+        self.triggered
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.triggered
+    }
+}
+
+// Zero crossings of the sinc kernel on each side; more taps give a sharper cutoff
+const SINC_ZERO_CROSSINGS: f64 = 16.0;
+// Keep the cutoff a little below the output Nyquist frequency so the transition band
+// ends before it, instead of letting energy just above it alias back down
+const CUTOFF_MARGIN: f64 = 0.95;
+
+// Windowed-sinc resampler for any input/output rate pair. It streams: feed it chunks
+// with `process` as they arrive and call `flush` once at the end for the tail.
+pub struct Resampler {
+    from_rate: u64,
+    to_rate: u64,
+    // Fraction of the input Nyquist frequency that passes through
+    cutoff: f64,
+    // Kernel half-width in input samples
+    half_width: f64,
+    buffer: Vec<f32>,
+    // Absolute input index of buffer[0]
+    buffer_start: u64,
+    input_len: u64,
+    output_len: u64,
+}
+
+impl Resampler {
+    pub fn new(from_rate: u32, to_rate: u32) -> Self {
+        let cutoff = (to_rate as f64 / from_rate as f64).min(1.0) * CUTOFF_MARGIN;
+        Resampler {
+            from_rate: from_rate as u64,
+            to_rate: to_rate as u64,
+            cutoff,
+            half_width: SINC_ZERO_CROSSINGS / cutoff,
+            buffer: Vec::new(),
+            buffer_start: 0,
+            input_len: 0,
+            output_len: 0,
+        }
+    }
+
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        if self.from_rate == self.to_rate {
+            self.input_len += input.len() as u64;
+            self.output_len += input.len() as u64;
+            return input.to_vec();
+        }
+
+        self.buffer.extend_from_slice(input);
+        self.input_len += input.len() as u64;
+
+        // Only emit samples whose whole kernel has arrived
+        let available = self.input_len as f64 - self.half_width;
+        let mut output = Vec::new();
+        loop {
+            let position = self.position(self.output_len);
+            if position >= available {
+                break;
+            }
+            output.push(self.interpolate(position));
+            self.output_len += 1;
+        }
+        self.discard_consumed();
+        output
+    }
+
+    // Emits the remaining samples, treating everything after the input as silence
+    pub fn flush(&mut self) -> Vec<f32> {
+        if self.from_rate == self.to_rate {
+            return Vec::new();
+        }
+
+        let expected = self.input_len * self.to_rate / self.from_rate;
+        let mut output = Vec::new();
+        while self.output_len < expected {
+            let sample = self.interpolate(self.position(self.output_len));
+            output.push(sample);
+            self.output_len += 1;
+        }
+        self.buffer.clear();
+        self.buffer_start = self.input_len;
+        output
+    }
+
+    // Input time (in input samples) of an output sample
+    fn position(&self, output_index: u64) -> f64 {
+        (output_index * self.from_rate) as f64 / self.to_rate as f64
+    }
+
+    fn interpolate(&self, position: f64) -> f32 {
+        let first = (position - self.half_width).ceil().max(0.0) as u64;
+        let last = (position + self.half_width).floor() as u64;
+
+        let mut sum = 0.0;
+        let mut weight_sum = 0.0;
+        for n in first.max(self.buffer_start)..=last {
+            let distance = position - n as f64;
+            let weight = self.cutoff * sinc(self.cutoff * distance) * blackman(distance / self.half_width);
+            weight_sum += weight;
+
+            // Samples past the end of the input are silence
+            if let Some(&sample) = self.buffer.get((n - self.buffer_start) as usize) {
+                sum += sample as f64 * weight;
+            }
+        }
+
+        // Normalise so DC passes at unity gain despite the truncated kernel
+        if weight_sum.abs() > f64::EPSILON {
+            (sum / weight_sum) as f32
+        } else {
+            0.0
+        }
+    }
+
+    fn discard_consumed(&mut self) {
+        let needed_from = (self.position(self.output_len) - self.half_width).ceil().max(0.0) as u64;
+        if needed_from > self.buffer_start {
+            let drop = ((needed_from - self.buffer_start) as usize).min(self.buffer.len());
+            self.buffer.drain(..drop);
+            self.buffer_start += drop as u64;
+        }
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    } else {
+        let x = std::f64::consts::PI * x;
+        x.sin() / x
+    }
+}
+
+// Blackman window over [-1, 1]
+fn blackman(x: f64) -> f64 {
+    if x.abs() >= 1.0 {
+        return 0.0;
+    }
+    let phase = std::f64::consts::PI * (x + 1.0);
+    0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos()
+}
+
+// Resamples a complete buffer in one go
+pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    let mut resampler = Resampler::new(from_rate, to_rate);
+    let mut output = resampler.process(samples);
+    output.extend(resampler.flush());
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tone whose frequency may change over time (in seconds)
+    fn tone(frequency: impl Fn(f64) -> f64, rate: u32, seconds: f64) -> Vec<f32> {
+        let mut phase: f64 = 0.0;
+        (0..(rate as f64 * seconds) as usize)
+            .map(|i| {
+                let sample = phase.sin() as f32;
+                phase += 2.0 * std::f64::consts::PI * frequency(i as f64 / rate as f64) / rate as f64;
+                sample
+            })
+            .collect()
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn test_downmix() {
+        assert_eq!(downmix(&[1.0, 0.0, 0.5, 0.5], 2), vec![0.5, 0.5]);
+    }
+
+    #[test]
+    fn test_normalize_peak() {
+        let mut quiet = vec![0.1, -0.05, 0.0, -0.1];
+        assert!((normalize_peak(&mut quiet, 0.9) - 9.0).abs() < 1e-4);
+        assert!((quiet[0] - 0.9).abs() < 1e-6 && (quiet[1] + 0.45).abs() < 1e-6 && (quiet[3] + 0.9).abs() < 1e-6);
+
+        // Near-silence is left alone rather than amplified into noise
+        let mut silent = vec![0.002, -0.004, 0.001];
+        assert_eq!(normalize_peak(&mut silent, 0.9), 1.0);
+        assert_eq!(silent, vec![0.002, -0.004, 0.001]);
+    }
+
+    #[test]
+    fn test_trim_silence_keeps_speech_and_padding() {
+        let rate = 16000;
+        let speech: Vec<f32> = (0..8000).map(|i| 0.3 * (i as f32 * 0.05).sin()).collect();
+        let mut samples = vec![0.001; 8000];
+        samples.extend_from_slice(&speech);
+        samples.extend(vec![0.001; 8000]);
+        
+        let trimmed = trim_silence(&samples, 0.01, rate, std::time::Duration::from_millis(100));
+        // 100 ms of padding on each side of the half second of speech
+        assert_eq!(trimmed.len(), 8000 + 2 * 1600);
+        assert_eq!(&trimmed[1600..9600], &speech[..]);
+        
+        // Nothing loud enough: keep everything
+        let quiet = vec![0.001; 4000];
+        assert_eq!(trim_silence(&quiet, 0.01, rate, std::time::Duration::from_millis(100)).len(), 4000);
+        assert!(trim_silence(&[], 0.01, rate, std::time::Duration::from_millis(100)).is_empty());
+    }
+
+    #[test]
+    fn test_noise_floor_ignores_short_clicks() {
+        let rate = 16000;
+        let mut room: Vec<f32> = (0..48000).map(|i| if i % 2 == 0 { 0.004 } else { -0.004 }).collect();
+        // One 20 ms click among 150 windows
+        room[1000..1320].fill(0.5);
+        let floor = noise_floor(&room, rate);
+        assert!((floor - 0.004).abs() < 1e-6);
+        assert_eq!(suggested_threshold(floor), 0.008);
+        
+        // A silent room or an empty recording still gets a usable threshold
+        assert_eq!(noise_floor(&[], rate), 0.0);
+        assert_eq!(suggested_threshold(0.0), 0.002);
+    }
+
+    #[test]
+    fn test_silence_detector_waits_for_speech() {
+        let mut detector = SilenceDetector::new(0.05, std::time::Duration::from_millis(500), 16000);
+
+        // Leading silence, however long, doesn't stop the recording
+        assert!(!detector.feed(&vec![0.0; 32000]));
+
+        // Speech, then a pause shorter than the limit
+        assert!(!detector.feed(&tone(|_| 300.0, 16000, 0.5)));
+        assert!(!detector.feed(&vec![0.001; 4000]));
+        assert!(!detector.feed(&tone(|_| 300.0, 16000, 0.2)));
+
+        // Sustained silence after speech
+        assert!(detector.feed(&vec![0.001; 9000]));
+        assert!(detector.is_triggered());
+    }
+
+    #[test]
+    fn test_resampler_output_length() {
+        for rate in [44100, 48000, 96000] {
+            assert_eq!(resample(&vec![0.0; rate as usize], rate, 16000).len(), 16000);
+        }
+
+        // Streaming in odd-sized chunks gives the same number of samples
+        let input = vec![0.25; 48000];
+        let mut resampler = Resampler::new(48000, 16000);
+        let mut output: Vec<f32> = input.chunks(1234).flat_map(|chunk| resampler.process(chunk)).collect();
+        output.extend(resampler.flush());
+        assert_eq!(output.len(), 16000);
+        assert!(output[100..15900].iter().all(|s| (s - 0.25).abs() < 1e-3));
+    }
+
+    #[test]
+    fn test_resampler_removes_frequencies_above_nyquist() {
+        // A tone well inside the 8 kHz output band survives
+        let low = tone(|_| 1000.0, 48000, 0.5);
+        let passed = resample(&low, 48000, 16000);
+        assert!((rms(&passed[500..7500]) - rms(&low)).abs() < 0.02);
+
+        // A 9-20 kHz sweep would alias into the band if it weren't filtered out
+        let sweep = tone(|t| 9000.0 + 11000.0 * t, 48000, 1.0);
+        let filtered = resample(&sweep, 48000, 16000);
+        assert!(rms(&filtered[500..15500]) < 0.01 * rms(&sweep), "aliasing rms {}", rms(&filtered));
+    }
+
+    #[test]
+    fn test_decode_flac_fixture() {
+        // 0.2 s of stereo audio at 22.05 kHz
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tone_22k_stereo.flac");
+        let samples = load_audio_file(path).unwrap();
+        assert!((3150..=3250).contains(&samples.len()), "got {} samples", samples.len());
+        assert!(samples.iter().any(|s| s.abs() > 0.1));
+    }
+
+    #[test]
+    fn test_decode_mp3_fixture() {
+        // 19 silent 1152-sample frames of stereo audio at 44.1 kHz, about 0.5 s
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/silence_44k_stereo.mp3");
+        let samples = load_audio_file(path).unwrap();
+        assert!((7500..=8400).contains(&samples.len()), "got {} samples", samples.len());
+    }
+}
@@ -0,0 +1,125 @@
+// Stops a feedback loop: when the microphone picks up the assistant's own "Failed to
+// execute" announcement (or Whisper keeps hallucinating "Thank you."), every recording
+// fails the same way and produces another announcement. After `threshold` identical
+// failures in a row the breaker opens and capture stops until the cooldown is over.
+
+use std::time::{Duration, Instant};
+
+pub struct CircuitBreaker {
+    threshold: usize,
+    window: Duration,
+    // None keeps it open until restart
+    cooldown: Option<Duration>,
+    last_failure: Option<String>,
+    streak: usize,
+    streak_started: Option<Instant>,
+    opened_at: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: usize, window: Duration, cooldown: Option<Duration>) -> Self {
+        CircuitBreaker {
+            threshold: threshold.max(1),
+            window,
+            cooldown,
+            last_failure: None,
+            streak: 0,
+            streak_started: None,
+            opened_at: None,
+        }
+    }
+
+    // Returns true when this failure is the one that opens the breaker
+    pub fn record_failure(&mut self, key: &str, now: Instant) -> bool {
+        if self.opened_at.is_some() {
+            return false;
+        }
+
+        let continues = self.last_failure.as_deref() == Some(key)
+            && self.streak_started.is_some_and(|started| now.duration_since(started) <= self.window);
+        if continues {
+            self.streak += 1;
+        } else {
+            self.last_failure = Some(key.to_string());
+            self.streak = 1;
+            self.streak_started = Some(now);
+        }
+
+        if self.streak >= self.threshold {
+            self.opened_at = Some(now);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn record_success(&mut self) {
+        self.last_failure = None;
+        self.streak = 0;
+        self.streak_started = None;
+    }
+
+    // Closes again, with a clean streak, once the cooldown has passed
+    pub fn is_open(&mut self, now: Instant) -> bool {
+        let Some(opened_at) = self.opened_at else {
+            return false;
+        };
+        match self.cooldown {
+            Some(cooldown) if now.duration_since(opened_at) >= cooldown => {
+                self.opened_at = None;
+                self.record_success();
+                false
+            }
+            _ => true,
+        }
+    }
+
+    pub fn remaining(&self, now: Instant) -> Option<Duration> {
+        let opened_at = self.opened_at?;
+        self.cooldown.map(|cooldown| cooldown.saturating_sub(now.duration_since(opened_at)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breaker_opens_on_repeated_failure() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut breaker = CircuitBreaker::new(3, Duration::from_secs(30), Some(Duration::from_secs(60)));
+
+        // A different failure or a success restarts the count
+        assert!(!breaker.record_failure("thank you", at(0)));
+        assert!(!breaker.record_failure("thank you", at(1)));
+        assert!(!breaker.record_failure("failed to execute", at(2)));
+        breaker.record_success();
+        assert!(!breaker.record_failure("thank you", at(3)));
+        assert!(!breaker.record_failure("thank you", at(4)));
+        assert!(!breaker.is_open(at(4)));
+
+        assert!(breaker.record_failure("thank you", at(5)));
+        assert!(breaker.is_open(at(6)));
+        assert_eq!(breaker.remaining(at(35)), Some(Duration::from_secs(30)));
+
+        // Cooldown over
+        assert!(!breaker.is_open(at(65)));
+        assert!(!breaker.record_failure("thank you", at(66)));
+    }
+
+    #[test]
+    fn test_breaker_ignores_failures_outside_window() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut breaker = CircuitBreaker::new(2, Duration::from_secs(10), None);
+
+        assert!(!breaker.record_failure("thank you", at(0)));
+        assert!(!breaker.record_failure("thank you", at(20)));
+        assert!(breaker.record_failure("thank you", at(25)));
+
+        // Without a cooldown it stays open
+        assert!(breaker.is_open(at(10_000)));
+        assert_eq!(breaker.remaining(at(10_000)), None);
+    }
+}
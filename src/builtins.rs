@@ -0,0 +1,295 @@
+// Voice commands handled by the assistant itself rather than a configured shortcut
+
+use crate::history::{CommandEntry, CommandHistory, FAILED_TRANSCRIPTION};
+use crate::suggestions::SmartSuggestions;
+
+const AFFIRMATIVE: &[&str] = &["yes", "y", "yeah", "yep", "sure", "confirm", "do it", "okay", "ok"];
+const NEGATIVE: &[&str] = &["no", "n", "nope", "cancel", "stop", "don't", "never mind"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuiltinCommand {
+    ClearHistory,
+    // Speaks the last transcription back; unlike "repeat that" it never runs anything
+    ReadBackLast,
+    SetShortcutEnabled { phrase: String, enabled: bool },
+    OpenSettings,
+    OpenHistory,
+    SetMuted(bool),
+    SetPaused(bool),
+}
+
+impl BuiltinCommand {
+    pub fn needs_confirmation(&self) -> bool {
+        match self {
+            BuiltinCommand::ClearHistory => true,
+            BuiltinCommand::ReadBackLast => false,
+            BuiltinCommand::SetShortcutEnabled { .. } => false,
+            BuiltinCommand::OpenSettings | BuiltinCommand::OpenHistory => false,
+            BuiltinCommand::SetMuted(_) | BuiltinCommand::SetPaused(_) => false,
+        }
+    }
+
+    pub fn confirmation_prompt(&self) -> &'static str {
+        match self {
+            BuiltinCommand::ClearHistory => "Clear all command history? Say yes to confirm",
+            BuiltinCommand::ReadBackLast => "",
+            BuiltinCommand::SetShortcutEnabled { .. } => "",
+            BuiltinCommand::OpenSettings | BuiltinCommand::OpenHistory => "",
+            BuiltinCommand::SetMuted(_) | BuiltinCommand::SetPaused(_) => "",
+        }
+    }
+}
+
+// Lowercases and strips the punctuation Whisper adds ("Clear history." -> "clear history")
+pub fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '\'' { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn parse_builtin(text: &str) -> Option<BuiltinCommand> {
+    let text = normalize(text);
+    match text.as_str() {
+        "clear history" | "clear my history" | "clear command history" | "reset statistics" => {
+            return Some(BuiltinCommand::ClearHistory);
+        }
+        "what did i just say" | "what did i say" | "read that back" => {
+            return Some(BuiltinCommand::ReadBackLast);
+        }
+        "open settings" | "open the settings" | "open settings file" | "edit settings" => {
+            return Some(BuiltinCommand::OpenSettings);
+        }
+        "open history" | "open the history" | "open command history" | "open history file" => {
+            return Some(BuiltinCommand::OpenHistory);
+        }
+        "mute" | "mute feedback" | "be quiet" => return Some(BuiltinCommand::SetMuted(true)),
+        "unmute" | "unmute feedback" => return Some(BuiltinCommand::SetMuted(false)),
+        "pause listening" | "stop listening" => return Some(BuiltinCommand::SetPaused(true)),
+        "resume listening" | "start listening" => return Some(BuiltinCommand::SetPaused(false)),
+        _ => {}
+    }
+
+    // "disable open terminal" / "enable open terminal"
+    for (prefix, enabled) in [("disable ", false), ("enable ", true)] {
+        if let Some(phrase) = text.strip_prefix(prefix) {
+            return Some(BuiltinCommand::SetShortcutEnabled { phrase: phrase.to_string(), enabled });
+        }
+    }
+    None
+}
+
+const GREETINGS: &[&str] = &["hey", "hi", "hello", "ok", "okay"];
+const POLITE_OPENERS: &[&str] = &["could you", "can you", "would you", "please"];
+const POLITE_CLOSERS: &[&str] = &["for me", "please", "thanks", "thank you"];
+
+// Removes the assistant's name from the start ("Hey computer, could you open terminal")
+// or end ("open terminal, computer") along with polite filler. None if it wasn't addressed.
+// Works on the original words so captured arguments keep their case and punctuation.
+pub fn strip_assistant_name(text: &str, name: &str) -> Option<String> {
+    let name = normalize(name);
+    let words: Vec<&str> = text.split_whitespace().collect();
+
+    let mut rest = &words[..];
+    let mut leading = rest;
+    for greeting in GREETINGS {
+        if strip_phrase(&mut leading, greeting, false) {
+            break;
+        }
+    }
+    if strip_phrase(&mut leading, &name, false) {
+        rest = leading;
+    } else if !strip_phrase(&mut rest, &name, true) {
+        return None;
+    }
+
+    for opener in POLITE_OPENERS {
+        strip_phrase(&mut rest, opener, false);
+    }
+    for closer in POLITE_CLOSERS {
+        strip_phrase(&mut rest, closer, true);
+    }
+    Some(rest.join(" ").trim_matches(|c: char| c == ',' || c.is_whitespace()).to_string())
+}
+
+fn strip_phrase(words: &mut &[&str], phrase: &str, from_end: bool) -> bool {
+    let n = phrase.split_whitespace().count();
+    if n == 0 || words.len() < n {
+        return false;
+    }
+    let (candidate, remaining) = if from_end {
+        (&words[words.len() - n..], &words[..words.len() - n])
+    } else {
+        (&words[..n], &words[n..])
+    };
+    if normalize(&candidate.join(" ")) != phrase {
+        return false;
+    }
+    *words = remaining;
+    true
+}
+
+pub fn readback_message(history: &CommandHistory) -> String {
+    match history.get_recent_entries(1).first() {
+        None => "I haven't heard anything yet".to_string(),
+        Some(entry) if entry.transcription == FAILED_TRANSCRIPTION => {
+            "I couldn't understand the last recording".to_string()
+        }
+        Some(entry) => format!("You said: {}", entry.transcription.trim()),
+    }
+}
+
+// An empty phrase turns "repeat that" off
+pub fn is_repeat_request(text: &str, phrase: &str) -> bool {
+    let phrase = normalize(phrase);
+    !phrase.is_empty() && normalize(text) == phrase
+}
+
+// The newest entry that ran a command; dictation never matches a phrase, so it's skipped
+pub fn last_executed(history: &CommandHistory) -> Option<&CommandEntry> {
+    history
+        .get_recent_entries(usize::MAX)
+        .into_iter()
+        .find(|entry| entry.success && entry.command_matched.is_some())
+}
+
+// What "Repeating ..." names: the matched phrase, or what was said when a fallback ran it
+pub fn repeat_label(entry: &CommandEntry) -> &str {
+    entry
+        .command_matched
+        .as_deref()
+        .map(str::trim)
+        .filter(|phrase| !phrase.is_empty())
+        .unwrap_or_else(|| entry.transcription.trim())
+}
+
+// Some(true) for yes, Some(false) for no, None if the response was neither. With `fuzzy`,
+// a first word that is close enough to one of the words ("yeh", "yup") also counts.
+pub fn classify_response(text: &str, fuzzy: Option<(&SmartSuggestions, f32)>) -> Option<bool> {
+    let response = normalize(text);
+    if response.is_empty() {
+        return None;
+    }
+
+    if NEGATIVE.iter().any(|word| response == *word || response.starts_with(&format!("{} ", word))) {
+        return Some(false);
+    } else if AFFIRMATIVE.iter().any(|word| response == *word || response.starts_with(&format!("{} ", word))) {
+        return Some(true);
+    }
+
+    let (suggestions, threshold) = fuzzy?;
+    let first = response.split_whitespace().next()?;
+    // One- and two-letter words ("y", "no") are contained in too many unrelated words
+    let best = |words: &[&str]| {
+        words
+            .iter()
+            .filter(|word| word.len() > 2 && !word.contains(' '))
+            .map(|word| suggestions.fuzzy_match(first, word))
+            .fold(0.0, f32::max)
+    };
+    let (yes, no) = (best(AFFIRMATIVE), best(NEGATIVE));
+    if yes.max(no) < threshold || yes == no {
+        None
+    } else {
+        Some(yes > no)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Local;
+
+    fn entry(transcription: &str, command_matched: Option<&str>, success: bool) -> CommandEntry {
+        CommandEntry {
+            timestamp: Local::now(),
+            transcription: transcription.to_string(),
+            command_matched: command_matched.map(String::from),
+            command_executed: None,
+            success,
+            duration_ms: 0,
+            audio_duration_ms: 0,
+            outcome: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_builtin() {
+        assert_eq!(parse_builtin("Clear history."), Some(BuiltinCommand::ClearHistory));
+        assert_eq!(parse_builtin("open terminal"), None);
+        assert_eq!(parse_builtin("Open settings."), Some(BuiltinCommand::OpenSettings));
+        assert_eq!(parse_builtin("Pause listening."), Some(BuiltinCommand::SetPaused(true)));
+        assert_eq!(
+            parse_builtin("Disable open terminal."),
+            Some(BuiltinCommand::SetShortcutEnabled { phrase: "open terminal".to_string(), enabled: false })
+        );
+    }
+
+    #[test]
+    fn test_strip_assistant_name() {
+        assert_eq!(strip_assistant_name("Computer, open terminal.", "computer"), Some("open terminal.".to_string()));
+        assert_eq!(
+            strip_assistant_name("Hey Jarvis, could you search for Rust for me", "jarvis"),
+            Some("search for Rust".to_string())
+        );
+        assert_eq!(strip_assistant_name("open terminal please, computer.", "Computer"), Some("open terminal".to_string()));
+        // The name in the middle of a sentence isn't an address
+        assert_eq!(strip_assistant_name("open computer settings", "computer"), None);
+        assert_eq!(strip_assistant_name("open terminal", "computer"), None);
+    }
+
+    #[test]
+    fn test_readback_message() {
+        let mut history = CommandHistory::new();
+        assert_eq!(readback_message(&history), "I haven't heard anything yet");
+
+        for transcription in ["open terminal", " open browser"] {
+            history.add_entry(entry(transcription, None, false));
+        }
+        assert_eq!(parse_builtin("What did I just say?"), Some(BuiltinCommand::ReadBackLast));
+        assert_eq!(readback_message(&history), "You said: open browser");
+    }
+
+    #[test]
+    fn test_repeat_last_executed() {
+        assert!(is_repeat_request("Repeat that.", "repeat that"));
+        assert!(!is_repeat_request("repeat that please", "repeat that"));
+        assert!(!is_repeat_request("", ""));
+
+        let mut history = CommandHistory::new();
+        assert!(last_executed(&history).is_none());
+        history.add_entry(entry("search for rust", Some("search for {query}"), true));
+        history.add_entry(entry("open termnal", Some("open terminal"), false));
+        // Dictation succeeds without matching a phrase
+        history.add_entry(entry("dear bob", None, true));
+        assert_eq!(last_executed(&history).unwrap().transcription, "search for rust");
+        assert_eq!(repeat_label(last_executed(&history).unwrap()), "search for {query}");
+        assert_eq!(repeat_label(&entry(" Spotify. ", Some(""), true)), "Spotify.");
+    }
+
+    #[test]
+    fn test_classify_response() {
+        assert_eq!(classify_response("Yes.", None), Some(true));
+        assert_eq!(classify_response("yeah do it", None), Some(true));
+        assert_eq!(classify_response("No, cancel", None), Some(false));
+        assert_eq!(classify_response("open terminal", None), None);
+        assert_eq!(classify_response("Yeh.", None), None);
+    }
+
+    #[test]
+    fn test_classify_response_fuzzy() {
+        let suggestions = SmartSuggestions::default();
+        let fuzzy = Some((&suggestions, 0.6));
+        assert_eq!(classify_response("Yeh.", fuzzy), Some(true));
+        assert_eq!(classify_response("yup", fuzzy), Some(true));
+        assert_eq!(classify_response("noppe", fuzzy), Some(false));
+
+        // Unrelated words stay unclassified
+        assert_eq!(classify_response("open terminal", fuzzy), None);
+        assert_eq!(classify_response("know", fuzzy), None);
+        assert_eq!(classify_response("banana", fuzzy), None);
+    }
+}
@@ -0,0 +1,120 @@
+// Live captions: every transcription is written as one line to `caption_fifo`, which
+// an overlay (OBS text source, `tail -f`, ...) can read. A missing path is created as
+// a named pipe and removed again on exit; an existing regular file is appended to.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+pub struct CaptionSink {
+    path: PathBuf,
+    created_fifo: bool,
+    // Kept open between lines so readers don't see end-of-file after each caption
+    writer: Mutex<Option<File>>,
+}
+
+impl CaptionSink {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let path = PathBuf::from(path);
+        let created_fifo = !path.exists();
+        if created_fifo {
+            make_fifo(&path)?;
+        }
+        Ok(CaptionSink { path, created_fifo, writer: Mutex::new(None) })
+    }
+
+    // Never blocks: with no reader on the pipe, or a reader that has fallen behind,
+    // the line is dropped rather than holding up the next command
+    pub fn write_line(&self, text: &str) {
+        let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if line.is_empty() {
+            return;
+        }
+
+        let mut writer = self.writer.lock().unwrap();
+        if writer.is_none() {
+            *writer = open_writer(&self.path).ok();
+        }
+        if let Some(file) = writer.as_mut() {
+            if writeln!(file, "{}", line).is_err() {
+                // The reader went away; reopen once a new one shows up
+                *writer = None;
+            }
+        }
+    }
+}
+
+impl Drop for CaptionSink {
+    fn drop(&mut self) {
+        if self.created_fifo {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn make_fifo(path: &std::path::Path) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    if unsafe { libc::mkfifo(c_path.as_ptr(), 0o644) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+// Without named pipes, captions go to a plain file instead
+#[cfg(not(unix))]
+fn make_fifo(path: &std::path::Path) -> io::Result<()> {
+    File::create(path).map(|_| ())
+}
+
+fn open_writer(path: &std::path::Path) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.custom_flags(libc::O_NONBLOCK);
+    }
+    options.open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_caption_lines_written_in_order() {
+        let path = std::env::temp_dir().join(format!("stt_whisper_captions_{}.txt", std::process::id()));
+        File::create(&path).unwrap();
+
+        let sink = CaptionSink::open(path.to_str().unwrap()).unwrap();
+        sink.write_line(" Open terminal. ");
+        sink.write_line("");
+        sink.write_line("take a\nscreenshot");
+        drop(sink);
+
+        // An existing file is left in place
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Open terminal.\ntake a screenshot\n");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_created_fifo_is_removed() {
+        use std::os::unix::fs::FileTypeExt;
+
+        let path = std::env::temp_dir().join(format!("stt_whisper_captions_{}.fifo", std::process::id()));
+        let sink = CaptionSink::open(path.to_str().unwrap()).unwrap();
+        assert!(fs::metadata(&path).unwrap().file_type().is_fifo());
+
+        // No reader yet, so the caption is dropped instead of blocking
+        sink.write_line("nobody is listening");
+        drop(sink);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,126 @@
+use chrono::NaiveDate;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct CliArgs {
+    pub clear_history: bool,
+    pub assume_yes: bool,
+    pub show_help: bool,
+    pub transcribe_file: Option<String>,
+    pub print_default_config: bool,
+    pub print_schema: bool,
+    pub once: bool,
+    pub list_devices: bool,
+    pub list_voices: bool,
+    pub timestamps: bool,
+    pub no_match: bool,
+    pub stats: bool,
+    pub dry_run: bool,
+    pub search: Option<String>,
+    pub since: Option<NaiveDate>,
+    pub failures: bool,
+    pub calibrate: bool,
+}
+
+pub const USAGE: &str = "Usage: stt-whisper [OPTIONS]
+
+Options:
+  --once             Record one command (until the keybind is pressed again), run it and exit
+  --clear-history    Delete all command history and exit
+  --transcribe-file <PATH>
+                     Handle a WAV, FLAC, MP3 or OGG file like a live recording: print the
+                     transcription, match it against your shortcuts and run it, then exit
+                     (add --dry-run to only log)
+  --no-match         With --transcribe-file, only print the transcription
+  --timestamps       With --transcribe-file, only print each segment with its times and confidence
+  --print-default-config
+                     Print the default settings file and exit
+  --print-schema     Print a JSON Schema for the settings file and exit
+  --list-devices     Print the names of the available input devices and exit
+  --list-voices      Print the voices the text-to-speech engine offers and exit
+  --calibrate        Measure the room's background noise, suggest a silence_threshold and exit
+  --stats            Print command statistics from the history file and exit
+  --search <QUERY>   Print history entries whose transcription or command contains QUERY and exit
+  --since <DATE>     With --search, only entries from DATE (YYYY-MM-DD) onwards
+  --failures         With --search, only entries that failed
+  --dry-run          Match commands and log what would run without running anything
+  -y, --yes          Don't ask for confirmation
+  -h, --help         Show this help";
+
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<CliArgs, String> {
+    let mut cli = CliArgs::default();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--clear-history" => cli.clear_history = true,
+            "-y" | "--yes" => cli.assume_yes = true,
+            "-h" | "--help" => cli.show_help = true,
+            "--print-default-config" => cli.print_default_config = true,
+            "--print-schema" => cli.print_schema = true,
+            "--once" => cli.once = true,
+            "--list-devices" => cli.list_devices = true,
+            "--list-voices" => cli.list_voices = true,
+            "--calibrate" => cli.calibrate = true,
+            "--timestamps" => cli.timestamps = true,
+            "--no-match" => cli.no_match = true,
+            "--stats" => cli.stats = true,
+            "--dry-run" => cli.dry_run = true,
+            "--failures" => cli.failures = true,
+            "--search" => {
+                let query = args.next().ok_or_else(|| format!("--search needs a query\n\n{}", USAGE))?;
+                cli.search = Some(query);
+            }
+            "--since" => {
+                let date = args.next().ok_or_else(|| format!("--since needs a date\n\n{}", USAGE))?;
+                let date = NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                    .map_err(|_| format!("--since expects a date like 2024-05-31, not '{}'", date))?;
+                cli.since = Some(date);
+            }
+            "--transcribe-file" => {
+                let path = args.next().ok_or_else(|| format!("--transcribe-file needs a path\n\n{}", USAGE))?;
+                cli.transcribe_file = Some(path);
+            }
+            other => return Err(format!("Unknown option: {}\n\n{}", other, USAGE)),
+        }
+    }
+
+    Ok(cli)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse_args(args(&[])).unwrap(), CliArgs::default());
+
+        let cli = parse_args(args(&["--clear-history", "--yes"])).unwrap();
+        assert!(cli.clear_history && cli.assume_yes);
+
+        let cli = parse_args(args(&["--transcribe-file", "talk.mp3"])).unwrap();
+        assert_eq!(cli.transcribe_file.as_deref(), Some("talk.mp3"));
+        assert!(!cli.timestamps);
+        assert!(parse_args(args(&["--transcribe-file", "talk.mp3", "--timestamps"])).unwrap().timestamps);
+        assert!(parse_args(args(&["--no-match", "--transcribe-file", "meeting.mp3"])).unwrap().no_match);
+
+        assert!(parse_args(args(&["--list-devices"])).unwrap().list_devices);
+        assert!(parse_args(args(&["--list-voices"])).unwrap().list_voices);
+        assert!(parse_args(args(&["--calibrate", "-y"])).unwrap().calibrate);
+        assert!(parse_args(args(&["--stats"])).unwrap().stats);
+        assert!(parse_args(args(&["--once", "--dry-run"])).unwrap().dry_run);
+
+        let cli = parse_args(args(&["--search", "open", "--since", "2024-05-31", "--failures"])).unwrap();
+        assert_eq!(cli.search.as_deref(), Some("open"));
+        assert_eq!(cli.since, NaiveDate::from_ymd_opt(2024, 5, 31));
+        assert!(cli.failures);
+        assert!(parse_args(args(&["--search", "open", "--since", "last week"])).is_err());
+
+        assert!(parse_args(args(&["--bogus"])).is_err());
+        assert!(parse_args(args(&["--transcribe-file"])).is_err());
+    }
+}
@@ -0,0 +1,9 @@
+// Puts transcriptions on the system clipboard so they can be pasted by hand
+
+use arboard::Clipboard;
+
+pub fn set_clipboard(text: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut clipboard = Clipboard::new()?;
+    clipboard.set_text(text)?;
+    Ok(())
+}
@@ -0,0 +1,45 @@
+// Keeps a command from firing twice in a row: a keybind held a moment too long, or the
+// same phrase heard in two recordings, would otherwise take two screenshots. Each
+// shortcut remembers when it last ran and is skipped until its cooldown has passed.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+pub struct Cooldowns {
+    last_run: HashMap<String, Instant>,
+}
+
+impl Cooldowns {
+    pub fn record(&mut self, phrase: &str, now: Instant) {
+        self.last_run.insert(phrase.to_string(), now);
+    }
+
+    // How much longer `phrase` has to wait, or None if it may run now
+    pub fn remaining(&self, phrase: &str, cooldown: Duration, now: Instant) -> Option<Duration> {
+        let last_run = self.last_run.get(phrase)?;
+        let remaining = cooldown.saturating_sub(now.duration_since(*last_run));
+        (!remaining.is_zero()).then_some(remaining)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cooldown_expires() {
+        let mut cooldowns = Cooldowns::default();
+        let start = Instant::now();
+        let cooldown = Duration::from_millis(1000);
+        assert_eq!(cooldowns.remaining("take screenshot", cooldown, start), None);
+
+        cooldowns.record("take screenshot", start);
+        let later = start + Duration::from_millis(400);
+        assert_eq!(cooldowns.remaining("take screenshot", cooldown, later), Some(Duration::from_millis(600)));
+        assert_eq!(cooldowns.remaining("open terminal", cooldown, later), None);
+        assert_eq!(cooldowns.remaining("take screenshot", cooldown, start + cooldown), None);
+        // No cooldown configured
+        assert_eq!(cooldowns.remaining("take screenshot", Duration::ZERO, later), None);
+    }
+}
@@ -0,0 +1,117 @@
+// Short tones for recording start, stop and errors. They are quicker than spoken
+// announcements, don't depend on a TTS engine and mean the same in every language.
+// Each cue opens the default output device on its own thread, so the record loop never waits.
+
+use std::f32::consts::PI;
+use std::thread;
+use std::time::Duration;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SizedSample, StreamConfig};
+use log::warn;
+
+const VOLUME: f32 = 0.25;
+// Ramping each note in and out avoids a click at either end
+const FADE_MS: u64 = 5;
+// Lets the output buffer drain before the stream is dropped
+const DRAIN_MARGIN: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cue {
+    Start,
+    Stop,
+    Error,
+}
+
+impl Cue {
+    // Frequency in Hz and length in ms of each note: rising for start, falling for stop
+    fn notes(self) -> &'static [(f32, u64)] {
+        match self {
+            Cue::Start => &[(660.0, 70), (880.0, 70)],
+            Cue::Stop => &[(880.0, 70), (660.0, 70)],
+            Cue::Error => &[(220.0, 250)],
+        }
+    }
+}
+
+// Mono samples for the cue at `sample_rate`
+pub fn tone(cue: Cue, sample_rate: u32) -> Vec<f32> {
+    let mut samples = Vec::new();
+    for &(frequency, ms) in cue.notes() {
+        let len = (sample_rate as u64 * ms / 1000) as usize;
+        let fade = ((sample_rate as u64 * FADE_MS / 1000) as usize).clamp(1, len.max(2) / 2);
+        samples.extend((0..len).map(|i| {
+            let envelope = (i.min(len - 1 - i) as f32 / fade as f32).min(1.0);
+            (2.0 * PI * frequency * i as f32 / sample_rate as f32).sin() * VOLUME * envelope
+        }));
+    }
+    samples
+}
+
+pub fn play(cue: Cue) {
+    thread::spawn(move || {
+        if let Err(e) = play_blocking(cue) {
+            warn!("Failed to play audio cue: {}", e);
+        }
+    });
+}
+
+fn play_blocking(cue: Cue) -> Result<(), Box<dyn std::error::Error>> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or("no output device")?;
+    let config = device.default_output_config()?;
+    let sample_rate = config.sample_rate().0;
+    let samples = tone(cue, sample_rate);
+    let length = Duration::from_secs_f64(samples.len() as f64 / sample_rate as f64);
+
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config.into(), samples)?,
+        cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config.into(), samples)?,
+        cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config.into(), samples)?,
+        _ => return Err("Unsupported sample format".into()),
+    };
+    stream.play()?;
+    thread::sleep(length + DRAIN_MARGIN);
+    Ok(())
+}
+
+// Every channel gets the same sample, then silence once the cue is over
+fn build_stream<T: SizedSample + FromSample<f32>>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    samples: Vec<f32>,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    let channels = config.channels as usize;
+    let mut samples = samples.into_iter();
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &_| {
+            for frame in data.chunks_mut(channels) {
+                frame.fill(T::from_sample_(samples.next().unwrap_or(0.0)));
+            }
+        },
+        |err| warn!("Audio cue stream error: {}", err),
+        None,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tone_shape() {
+        let start = tone(Cue::Start, 16000);
+        // Two 70 ms notes
+        assert_eq!(start.len(), 2 * 1120);
+        assert!(start.iter().all(|s| s.abs() <= VOLUME));
+        // Faded in and out rather than starting or ending at full volume
+        assert_eq!(start[0], 0.0);
+        assert!(start[start.len() - 1].abs() < 0.01);
+        assert!(start.iter().any(|s| s.abs() > VOLUME * 0.9));
+
+        assert_eq!(tone(Cue::Error, 48000).len(), 12000);
+        assert_ne!(tone(Cue::Start, 16000), tone(Cue::Stop, 16000));
+    }
+}
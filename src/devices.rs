@@ -0,0 +1,89 @@
+// Picks the microphone and catches the easy mistake of recording from a loopback or
+// monitor device, which captures the assistant's own speech instead of the user's.
+
+use cpal::traits::{DeviceTrait, HostTrait};
+use log::{info, warn};
+
+// Lowercase fragments of names that loopback/monitor devices use across platforms
+const LOOPBACK_PATTERNS: &[&str] = &[
+    "monitor of",
+    ".monitor",
+    "loopback",
+    "stereo mix",
+    "what u hear",
+    "wave out mix",
+    "blackhole",
+    "soundflower",
+    "cable output",
+    "vb-audio",
+];
+
+pub fn is_loopback_device(name: &str) -> bool {
+    let name = name.to_lowercase();
+    LOOPBACK_PATTERNS.iter().any(|pattern| name.contains(pattern))
+}
+
+// The first input whose name contains `name` (case-insensitive). Falls back to the
+// system default, so an unplugged headset doesn't stop the assistant from listening.
+pub fn select_input_device(name: Option<&str>) -> Result<cpal::Device, Box<dyn std::error::Error>> {
+    let host = cpal::default_host();
+    if let Some(name) = name {
+        let wanted = name.to_lowercase();
+        let found = host.input_devices()?.find(|device| device.name().is_ok_and(|n| n.to_lowercase().contains(&wanted)));
+        match found {
+            Some(device) => return Ok(device),
+            None => warn!("No input device matching '{}', using the default. Run with --list-devices to see them.", name),
+        }
+    }
+    Ok(host.default_input_device().ok_or("No input device available")?)
+}
+
+pub fn input_device_names() -> Vec<String> {
+    cpal::default_host()
+        .input_devices()
+        .map(|devices| devices.filter_map(|device| device.name().ok()).collect())
+        .unwrap_or_default()
+}
+
+// Warns at startup; recording from the monitor still works, it just hears the wrong thing
+pub fn check_input_device(name: Option<&str>) {
+    let device = match select_input_device(name) {
+        Ok(device) => device,
+        Err(e) => {
+            warn!("{}", e);
+            return;
+        }
+    };
+    let Ok(device_name) = device.name() else {
+        return;
+    };
+    info!("Input device: {}", device_name);
+
+    if is_loopback_device(&device_name) {
+        warn!("'{}' looks like a loopback/monitor device, so the assistant would", device_name);
+        warn!("   record its own voice and system audio instead of your microphone.");
+        let microphones: Vec<String> = input_device_names().into_iter().filter(|n| !is_loopback_device(n)).collect();
+        if let Some(first) = microphones.first() {
+            warn!("   Microphones found: {}", microphones.join(", "));
+            warn!("   To use one, set \"input_device\": \"{}\" in the settings file.", first);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loopback_device_names() {
+        assert!(is_loopback_device("Monitor of Built-in Audio Analog Stereo"));
+        assert!(is_loopback_device("alsa_output.pci-0000_00_1f.3.analog-stereo.monitor"));
+        assert!(is_loopback_device("Stereo Mix (Realtek High Definition Audio)"));
+        assert!(is_loopback_device("BlackHole 2ch"));
+        assert!(is_loopback_device("CABLE Output (VB-Audio Virtual Cable)"));
+
+        assert!(!is_loopback_device("Built-in Microphone"));
+        assert!(!is_loopback_device("Microphone (Blue Yeti)"));
+        assert!(!is_loopback_device("alsa_input.usb-046d_HD_Webcam_C615-02.analog-mono"));
+    }
+}
@@ -0,0 +1,86 @@
+// Voice typing: instead of matching shortcuts, the transcription is typed into
+// whichever window has focus.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use enigo::{Direction, Enigo, Key, Keyboard};
+
+#[derive(Debug, PartialEq)]
+pub enum Typed<'a> {
+    Text(&'a str),
+    Enter,
+}
+
+// Lines are typed as text with Enter between them, so newlines work in every app
+pub fn keystrokes(text: &str) -> Vec<Typed<'_>> {
+    let mut typed = Vec::new();
+    for (i, line) in text.trim().lines().enumerate() {
+        if i > 0 {
+            typed.push(Typed::Enter);
+        }
+        let line = line.trim_end_matches('\r');
+        if !line.is_empty() {
+            typed.push(Typed::Text(line));
+        }
+    }
+    typed
+}
+
+// Typing while the keybind is still down would turn e.g. Ctrl+F8 plus the text into
+// a string of Ctrl shortcuts, so this waits for the release first
+pub fn type_text<F: FnMut() -> bool>(text: &str, keybind_held: F, max_wait: Duration) -> Result<(), Box<dyn std::error::Error>> {
+    wait_for_release(keybind_held, max_wait)?;
+
+    let typed = keystrokes(text);
+    if typed.is_empty() {
+        return Ok(());
+    }
+    let mut enigo = Enigo::new(&enigo::Settings::default())?;
+    for typed in typed {
+        match typed {
+            Typed::Text(line) => enigo.text(line)?,
+            Typed::Enter => enigo.key(Key::Return, Direction::Click)?,
+        }
+    }
+    Ok(())
+}
+
+fn wait_for_release<F: FnMut() -> bool>(mut keybind_held: F, max_wait: Duration) -> Result<(), String> {
+    let started = Instant::now();
+    while keybind_held() {
+        if started.elapsed() >= max_wait {
+            return Err("the keybind is still held, not typing".to_string());
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keystrokes_keep_unicode_and_newlines() {
+        assert_eq!(keystrokes(" Café déjà vu, naïve 😀 "), vec![Typed::Text("Café déjà vu, naïve 😀")]);
+        assert_eq!(
+            keystrokes("Dear Sam,\r\n\nSee you soon."),
+            vec![Typed::Text("Dear Sam,"), Typed::Enter, Typed::Enter, Typed::Text("See you soon.")]
+        );
+        assert!(keystrokes("  ").is_empty());
+    }
+
+    #[test]
+    fn test_waits_for_keybind_release() {
+        let mut polls = 0;
+        wait_for_release(|| {
+            polls += 1;
+            polls < 3
+        }, Duration::from_secs(1))
+        .unwrap();
+        assert_eq!(polls, 3);
+
+        assert!(wait_for_release(|| true, Duration::from_millis(50)).is_err());
+    }
+}
@@ -0,0 +1,63 @@
+use std::fmt;
+
+use crate::model::MB;
+
+#[derive(Debug)]
+pub enum AssistantError {
+    InsufficientMemory {
+        model_path: String,
+        required_bytes: u64,
+        available_bytes: u64,
+    },
+    InvalidSettings(String),
+    IncompleteModel {
+        model_path: String,
+        reason: String,
+    },
+    // Refused by the command denylist or allowlist
+    CommandBlocked {
+        command: String,
+        reason: String,
+    },
+    // A waited-for shortcut command exited unsuccessfully
+    CommandFailed {
+        command: String,
+        status: Option<i32>,
+        output: String,
+    },
+}
+
+impl fmt::Display for AssistantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssistantError::InsufficientMemory { model_path, required_bytes, available_bytes } => write!(
+                f,
+                "Model '{}' needs about {} MB of memory but only {} MB is available. \
+                 Use a smaller model (e.g. ggml-base.en.bin) or set \"skip_memory_check\": true to load it anyway",
+                model_path,
+                required_bytes / MB,
+                available_bytes / MB
+            ),
+            AssistantError::InvalidSettings(reason) => write!(f, "Invalid settings: {}", reason),
+            AssistantError::IncompleteModel { model_path, reason } => write!(
+                f,
+                "Model '{}' appears incomplete or corrupt ({}). The download may have been interrupted; \
+                 re-download it from https://huggingface.co/ggerganov/whisper.cpp or set \"skip_model_check\": true to load it anyway",
+                model_path, reason
+            ),
+            AssistantError::CommandBlocked { command, reason } => write!(f, "Blocked '{}': {}", command, reason),
+            AssistantError::CommandFailed { command, status, output } => {
+                match status {
+                    Some(code) => write!(f, "'{}' exited with status {}", command, code)?,
+                    None => write!(f, "'{}' was terminated by a signal", command)?,
+                }
+                if !output.is_empty() {
+                    write!(f, ": {}", output)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for AssistantError {}
@@ -0,0 +1,73 @@
+// Last check before a shell command runs. Fuzzy matching can pick the wrong shortcut and
+// the app-launch fallback builds commands from what was heard, so patterns here apply to
+// the final command string whichever way it was chosen.
+
+use regex::Regex;
+
+#[derive(Debug, Clone, Default)]
+pub struct CommandGuard {
+    denylist: Vec<Regex>,
+    // Only set in allowlist-only mode
+    allowlist: Option<Vec<Regex>>,
+}
+
+impl CommandGuard {
+    pub fn new(denylist: &[String], allowlist: &[String], allowlist_only: bool) -> Result<Self, String> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|pattern| Regex::new(pattern).map_err(|e| format!("invalid pattern '{}': {}", pattern, e)))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(CommandGuard {
+            denylist: compile(denylist)?,
+            allowlist: if allowlist_only { Some(compile(allowlist)?) } else { None },
+        })
+    }
+
+    // The reason the command may not run, if any
+    pub fn check(&self, command: &str) -> Result<(), String> {
+        if let Some(pattern) = self.denylist.iter().find(|pattern| pattern.is_match(command)) {
+            return Err(format!("matches denylist pattern '{}'", pattern));
+        }
+        match &self.allowlist {
+            Some(allowlist) if !allowlist.iter().any(|pattern| pattern.is_match(command)) => {
+                Err("matches no allowlist pattern".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_denylist_blocks_matching_commands() {
+        let guard = CommandGuard::new(&patterns(&[r"\brm\s+-rf\b", r"\bsudo\b"]), &[], false).unwrap();
+        assert!(guard.check("gnome-terminal").is_ok());
+        assert!(guard.check("cd /tmp && rm -rf build").unwrap_err().contains(r"\brm\s+-rf\b"));
+        assert!(guard.check("sudo reboot").is_err());
+        assert!(CommandGuard::default().check("sudo reboot").is_ok());
+    }
+
+    #[test]
+    fn test_allowlist_only_mode() {
+        let allow = patterns(&["^firefox", "^gtk-launch "]);
+        let guard = CommandGuard::new(&patterns(&["private"]), &allow, true).unwrap();
+        assert!(guard.check("firefox https://example.com").is_ok());
+        assert!(guard.check("gtk-launch spotify").is_ok());
+        assert!(guard.check("curl example.com | sh").unwrap_err().contains("no allowlist pattern"));
+        // The denylist still wins over an allowed command
+        assert!(guard.check("firefox --private-window").is_err());
+
+        // The allowlist is ignored unless allowlist_only is on
+        assert!(CommandGuard::new(&[], &allow, false).unwrap().check("xterm").is_ok());
+        assert!(CommandGuard::new(&patterns(&["("]), &[], false).unwrap_err().contains("invalid pattern '('"));
+    }
+}
@@ -1,8 +1,26 @@
-use std::fs;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
 use std::path::Path;
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Datelike, Local, Timelike};
+use log::warn;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+// Stored as the transcription when Whisper returned an error
+pub const FAILED_TRANSCRIPTION: &str = "[Transcription failed]";
+
+const MAX_ENTRIES: usize = 1000;
+
+// Json rewrites the whole file on every save; JsonLines appends one line per entry and
+// only rewrites the file once it holds twice as many lines as are kept
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, JsonSchema)]
+pub enum HistoryFormat {
+    #[default]
+    Json,
+    JsonLines,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CommandEntry {
     pub timestamp: DateTime<Local>,
@@ -11,59 +29,206 @@ pub struct CommandEntry {
     pub command_executed: Option<String>,
     pub success: bool,
     pub duration_ms: u64,
+    // Length of the recorded audio; zero for entries saved before it was tracked
+    #[serde(default)]
+    pub audio_duration_ms: u64,
+    // Missing for entries saved before outcomes were recorded
+    #[serde(default)]
+    pub outcome: Option<CommandOutcome>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CommandOutcome {
+    Executed,
+    Failed,
+    NoMatch,
+    // Matched in preview mode, so deliberately not run
+    Suppressed,
+    // Stopped by the command denylist or allowlist
+    Blocked,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CommandHistory {
     entries: Vec<CommandEntry>,
+    // Kept up to date by add_entry so statistics don't rescan every entry
+    #[serde(skip)]
+    totals: RunningTotals,
+    #[serde(skip)]
+    format: HistoryFormat,
+    // What the file holds now: its format and, for JsonLines, its line count
+    #[serde(skip)]
+    on_disk: Option<(HistoryFormat, usize)>,
+    // Entries added since the last save, which are all a JsonLines save has to append
+    #[serde(skip)]
+    unsaved: usize,
+}
+
+#[derive(Debug, Default)]
+struct RunningTotals {
+    total: usize,
+    successful: usize,
+    duration_sum: u64,
+    command_usage: HashMap<String, usize>,
+    command_successes: HashMap<String, usize>,
+}
+
+fn decrement(counts: &mut HashMap<String, usize>, key: &str) {
+    if let Some(count) = counts.get_mut(key) {
+        *count -= 1;
+        if *count == 0 {
+            counts.remove(key);
+        }
+    }
+}
+
+impl RunningTotals {
+    fn add(&mut self, entry: &CommandEntry) {
+        self.total += 1;
+        self.successful += usize::from(entry.success);
+        self.duration_sum += entry.duration_ms;
+        if let Some(cmd) = &entry.command_matched {
+            *self.command_usage.entry(cmd.clone()).or_insert(0) += 1;
+            if entry.success {
+                *self.command_successes.entry(cmd.clone()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    fn remove(&mut self, entry: &CommandEntry) {
+        self.total -= 1;
+        self.successful -= usize::from(entry.success);
+        self.duration_sum -= entry.duration_ms;
+        if let Some(cmd) = &entry.command_matched {
+            decrement(&mut self.command_usage, cmd);
+            if entry.success {
+                decrement(&mut self.command_successes, cmd);
+            }
+        }
+    }
+
+    fn statistics(&self) -> CommandStatistics {
+        CommandStatistics {
+            total_commands: self.total,
+            successful_commands: self.successful,
+            failed_commands: self.total - self.successful,
+            command_usage: self.command_usage.clone(),
+            command_successes: self.command_successes.clone(),
+            avg_duration_ms: if self.total > 0 { self.duration_sum / self.total as u64 } else { 0 },
+        }
+    }
 }
 
 impl CommandHistory {
     pub fn new() -> Self {
         CommandHistory {
             entries: Vec::new(),
+            totals: RunningTotals::default(),
+            format: HistoryFormat::default(),
+            on_disk: None,
+            unsaved: 0,
         }
     }
 
+    // How the next save writes the file; a file in the other format is converted then
+    pub fn with_format(mut self, format: HistoryFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    // Reads either format, so switching history_format keeps the existing history
     pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
         if !Path::new(path).exists() {
             return Ok(Self::new());
         }
         
         let contents = fs::read_to_string(path)?;
-        let history: CommandHistory = serde_json::from_str(&contents)?;
+        let (mut history, on_disk) = match serde_json::from_str::<CommandHistory>(&contents) {
+            Ok(history) => (history, (HistoryFormat::Json, 0)),
+            Err(_) => {
+                let mut history = Self::new();
+                let (entries, complete) = parse_lines(&contents)?;
+                history.entries = entries;
+                let lines = history.entries.len();
+                let excess = lines.saturating_sub(MAX_ENTRIES);
+                history.entries.drain(..excess);
+                // Appending after a cut-off line would join the two, so the file is rewritten
+                let lines = if complete { lines } else { usize::MAX };
+                (history, (HistoryFormat::JsonLines, lines))
+            }
+        };
+        for entry in &history.entries {
+            history.totals.add(entry);
+        }
+        history.on_disk = Some(on_disk);
         Ok(history)
     }
 
-    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let json = serde_json::to_string_pretty(&self)?;
-        fs::write(path, json)?;
+    pub fn save(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        match self.format {
+            HistoryFormat::Json => {
+                let json = serde_json::to_string_pretty(&self)?;
+                fs::write(path, json)?;
+                self.on_disk = Some((HistoryFormat::Json, 0));
+            }
+            HistoryFormat::JsonLines => match self.on_disk {
+                Some((HistoryFormat::JsonLines, lines)) if lines.saturating_add(self.unsaved) <= 2 * MAX_ENTRIES => {
+                    let new = &self.entries[self.entries.len() - self.unsaved..];
+                    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+                    file.write_all(to_lines(new)?.as_bytes())?;
+                    self.on_disk = Some((HistoryFormat::JsonLines, lines + new.len()));
+                }
+                _ => {
+                    // Written aside and renamed over, so an interrupted rewrite loses nothing
+                    let temp_path = format!("{}.tmp", path);
+                    fs::write(&temp_path, to_lines(&self.entries)?)?;
+                    fs::rename(&temp_path, path)?;
+                    self.on_disk = Some((HistoryFormat::JsonLines, self.entries.len()));
+                }
+            },
+        }
+        self.unsaved = 0;
         Ok(())
     }
 
     pub fn add_entry(&mut self, entry: CommandEntry) {
+        self.totals.add(&entry);
         self.entries.push(entry);
         
         // Keep only the last 1000 entries
-        if self.entries.len() > 1000 {
-            self.entries.drain(0..self.entries.len() - 1000);
+        if self.entries.len() > MAX_ENTRIES {
+            let excess = self.entries.len() - MAX_ENTRIES;
+            for old in self.entries.drain(0..excess) {
+                self.totals.remove(&old);
+            }
         }
+        self.unsaved = (self.unsaved + 1).min(self.entries.len());
     }
 
     pub fn get_statistics(&self) -> CommandStatistics {
-        let total_commands = self.entries.len();
-        let successful_commands = self.entries.iter().filter(|e| e.success).count();
+        self.totals.statistics()
+    }
+
+    // Full scan, for statistics over a subset of the history
+    pub fn get_statistics_where<F: Fn(&CommandEntry) -> bool>(&self, filter: F) -> CommandStatistics {
+        let entries: Vec<&CommandEntry> = self.entries.iter().filter(|e| filter(e)).collect();
+        let total_commands = entries.len();
+        let successful_commands = entries.iter().filter(|e| e.success).count();
         let failed_commands = total_commands - successful_commands;
         
-        let mut command_usage = std::collections::HashMap::new();
-        for entry in &self.entries {
+        let mut command_usage = HashMap::new();
+        let mut command_successes = HashMap::new();
+        for entry in &entries {
             if let Some(cmd) = &entry.command_matched {
                 *command_usage.entry(cmd.clone()).or_insert(0) += 1;
+                if entry.success {
+                    *command_successes.entry(cmd.clone()).or_insert(0) += 1;
+                }
             }
         }
         
         let avg_duration = if total_commands > 0 {
-            self.entries.iter().map(|e| e.duration_ms).sum::<u64>() / total_commands as u64
+            entries.iter().map(|e| e.duration_ms).sum::<u64>() / total_commands as u64
         } else {
             0
         };
@@ -73,29 +238,151 @@ impl CommandHistory {
             successful_commands,
             failed_commands,
             command_usage,
+            command_successes,
             avg_duration_ms: avg_duration,
         }
     }
 
+    // Successful commands bucketed by when they ran, the same data suggestions learn from
+    pub fn get_time_distribution(&self) -> TimeDistribution {
+        let mut distribution = TimeDistribution {
+            by_hour: [0; 24],
+            by_day: [0; 7],
+        };
+        for entry in self.entries.iter().filter(|e| e.success && e.command_matched.is_some()) {
+            distribution.by_hour[entry.timestamp.hour() as usize] += 1;
+            distribution.by_day[entry.timestamp.weekday().num_days_from_monday() as usize] += 1;
+        }
+        distribution
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.totals = RunningTotals::default();
+        // Appending nothing would keep the old lines
+        self.on_disk = None;
+        self.unsaved = 0;
+    }
+
+    // Copies the history file aside so a clear can be undone by hand
+    pub fn archive(path: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        if !Path::new(path).exists() {
+            return Ok(None);
+        }
+        
+        let archive_path = format!("{}.{}.bak", path, Local::now().format("%Y%m%d-%H%M%S"));
+        fs::copy(path, &archive_path)?;
+        Ok(Some(archive_path))
+    }
+
     pub fn get_recent_entries(&self, count: usize) -> Vec<&CommandEntry> {
         self.entries.iter().rev().take(count).collect()
     }
 
+    // Oldest first. The query matches, ignoring case, anywhere in the transcription or in
+    // the matched or executed command; an empty query matches everything
+    pub fn search(&self, query: &str, since: Option<DateTime<Local>>, only_failures: bool) -> Vec<&CommandEntry> {
+        let query = query.to_lowercase();
+        self.entries
+            .iter()
+            .filter(|e| since.is_none_or(|since| e.timestamp >= since))
+            .filter(|e| !only_failures || !e.success)
+            .filter(|e| {
+                [Some(&e.transcription), e.command_matched.as_ref(), e.command_executed.as_ref()]
+                    .into_iter()
+                    .flatten()
+                    .any(|text| text.to_lowercase().contains(&query))
+            })
+            .collect()
+    }
+
     pub fn get_all_entries(&self) -> &Vec<CommandEntry> {
         &self.entries
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct CommandStatistics {
     pub total_commands: usize,
     pub successful_commands: usize,
     pub failed_commands: usize,
-    pub command_usage: std::collections::HashMap<String, usize>,
+    pub command_usage: HashMap<String, usize>,
+    // Of the uses above, how many ran successfully
+    pub command_successes: HashMap<String, usize>,
     pub avg_duration_ms: u64,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeGrouping {
+    Hour,
+    Day,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TimeDistribution {
+    pub by_hour: [usize; 24],
+    // Monday first
+    pub by_day: [usize; 7],
+}
+
+const HISTOGRAM_WIDTH: usize = 30;
+const DAY_NAMES: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+impl TimeDistribution {
+    pub fn print_time_distribution(&self, grouping: TimeGrouping) {
+        let (title, labels, counts): (_, Vec<String>, &[usize]) = match grouping {
+            TimeGrouping::Hour => ("🕒 Commands by hour:", (0..24).map(|h| format!("{:02}:00", h)).collect(), &self.by_hour),
+            TimeGrouping::Day => ("📅 Commands by day:", DAY_NAMES.iter().map(|d| d.to_string()).collect(), &self.by_day),
+        };
+
+        println!("\n{}", title);
+        let max = counts.iter().copied().max().unwrap_or(0);
+        if max == 0 {
+            println!("  No successful commands yet.");
+            return;
+        }
+        for (label, &count) in labels.iter().zip(counts) {
+            // Let quiet hours collapse, but keep any nonzero bucket visible
+            if grouping == TimeGrouping::Hour && count == 0 {
+                continue;
+            }
+            let bar = (count * HISTOGRAM_WIDTH).div_ceil(max);
+            println!("  {} {} {}", label, "█".repeat(bar), count);
+        }
+    }
+}
+
+// Success rate, in percent, below which a command is flagged in the summary
+const LOW_SUCCESS_RATE: f64 = 50.0;
+const LOW_SUCCESS_MIN_ATTEMPTS: usize = 3;
+
 impl CommandStatistics {
+    // Matched commands, most used first, with the share of their runs that succeeded
+    pub fn most_used(&self, count: usize) -> Vec<(&str, usize, f64)> {
+        let mut usage: Vec<_> = self.command_usage.iter().collect();
+        usage.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        usage
+            .into_iter()
+            .take(count)
+            .map(|(cmd, &uses)| {
+                let successes = self.command_successes.get(cmd).copied().unwrap_or(0);
+                (cmd.as_str(), uses, successes as f64 / uses as f64 * 100.0)
+            })
+            .collect()
+    }
+
+    // Commands that fail more often than not, worst first. A single unlucky attempt
+    // isn't worth flagging, so only those tried a few times count.
+    pub fn low_performing(&self) -> Vec<(&str, usize, f64)> {
+        let mut flagged: Vec<_> = self
+            .most_used(self.command_usage.len())
+            .into_iter()
+            .filter(|&(_, uses, success_rate)| uses >= LOW_SUCCESS_MIN_ATTEMPTS && success_rate < LOW_SUCCESS_RATE)
+            .collect();
+        flagged.sort_by(|a, b| a.2.total_cmp(&b.2).then_with(|| b.1.cmp(&a.1)));
+        flagged
+    }
+
     pub fn print_summary(&self) {
         println!("\n📊 Command Statistics:");
         println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
@@ -113,13 +400,205 @@ impl CommandStatistics {
         
         if !self.command_usage.is_empty() {
             println!("\n🔥 Most used commands:");
-            let mut usage_vec: Vec<_> = self.command_usage.iter().collect();
-            usage_vec.sort_by(|a, b| b.1.cmp(a.1));
-            
-            for (i, (cmd, count)) in usage_vec.iter().take(5).enumerate() {
-                println!("  {}. {} ({} times)", i + 1, cmd, count);
+            for (i, (cmd, count, success_rate)) in self.most_used(5).iter().enumerate() {
+                println!("  {}. {} ({} times, {:.0}% successful)", i + 1, cmd, count, success_rate);
+            }
+        }
+        
+        let flaky = self.low_performing();
+        if !flaky.is_empty() {
+            println!("\n⚠️  Often failing (consider rewording or fixing the command):");
+            for (cmd, count, success_rate) in flaky {
+                println!("  {} ({:.0}% of {} attempts successful)", cmd, success_rate, count);
             }
         }
         println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
     }
-}
\ No newline at end of file
+}
+
+fn to_lines(entries: &[CommandEntry]) -> Result<String, serde_json::Error> {
+    let mut lines = String::new();
+    for entry in entries {
+        lines.push_str(&serde_json::to_string(entry)?);
+        lines.push('\n');
+    }
+    Ok(lines)
+}
+
+// A crash part way through an append leaves a cut-off last line, which is dropped;
+// false if that happened
+fn parse_lines(contents: &str) -> Result<(Vec<CommandEntry>, bool), serde_json::Error> {
+    let lines: Vec<&str> = contents.lines().filter(|line| !line.trim().is_empty()).collect();
+    let mut entries = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) if i + 1 == lines.len() => {
+                warn!("Ignoring an incomplete last history entry: {}", e);
+                return Ok((entries, false));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok((entries, true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn entry(command: Option<&str>, success: bool) -> CommandEntry {
+        CommandEntry {
+            timestamp: Local::now(),
+            transcription: command.unwrap_or("mumble").to_string(),
+            command_matched: command.map(|c| c.to_string()),
+            command_executed: command.map(|c| c.to_string()),
+            success,
+            duration_ms: 120,
+            audio_duration_ms: 900,
+            outcome: Some(if success { CommandOutcome::Executed } else { CommandOutcome::Failed }),
+        }
+    }
+
+    #[test]
+    fn test_json_lines_appends_and_migrates() {
+        let path = std::env::temp_dir().join(format!("stt_whisper_history_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let lines = || fs::read_to_string(path).unwrap().lines().count();
+
+        // An existing JSON history is converted on the first save
+        let mut history = CommandHistory::new();
+        history.add_entry(entry(Some("open terminal"), true));
+        history.save(path).unwrap();
+        let mut history = CommandHistory::load(path).unwrap().with_format(HistoryFormat::JsonLines);
+        assert_eq!(history.get_all_entries().len(), 1);
+        history.add_entry(entry(Some("lock screen"), true));
+        history.save(path).unwrap();
+        assert_eq!(lines(), 2);
+
+        history.add_entry(entry(None, false));
+        history.save(path).unwrap();
+        assert_eq!(lines(), 3);
+
+        // A save cut off by a crash
+        OpenOptions::new().append(true).open(path).unwrap().write_all(b"{\"timestamp\":").unwrap();
+        let mut history = CommandHistory::load(path).unwrap().with_format(HistoryFormat::JsonLines);
+        assert_eq!(history.get_all_entries().len(), 3);
+        assert_eq!(history.get_statistics().successful_commands, 2);
+        history.add_entry(entry(Some("open terminal"), true));
+        history.save(path).unwrap();
+        assert_eq!(lines(), 4);
+        assert_eq!(CommandHistory::load(path).unwrap().get_all_entries().len(), 4);
+
+        history.clear();
+        history.save(path).unwrap();
+        assert_eq!(lines(), 0);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_search_filters() {
+        let mut history = CommandHistory::new();
+        let mut old = entry(Some("open terminal"), false);
+        old.timestamp = Local.with_ymd_and_hms(2024, 1, 1, 9, 0, 0).unwrap();
+        history.add_entry(old);
+        let mut unmatched = entry(None, false);
+        unmatched.transcription = "Open the pod bay doors".to_string();
+        history.add_entry(unmatched);
+        history.add_entry(entry(Some("open terminal"), true));
+        history.add_entry(entry(Some("lock screen"), true));
+
+        assert_eq!(history.search("OPEN", None, false).len(), 3);
+        assert_eq!(history.search("", None, false).len(), 4);
+
+        let failures = history.search("open", None, true);
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[1].transcription, "Open the pod bay doors");
+
+        let since = Local.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let recent = history.search("terminal", Some(since), false);
+        assert_eq!(recent.len(), 1);
+        assert!(recent[0].success);
+    }
+
+    #[test]
+    fn test_time_distribution_buckets() {
+        let at = |day: u32, hour: u32| Local.with_ymd_and_hms(2024, 1, day, hour, 30, 0).unwrap();
+        let mut history = CommandHistory::new();
+        // 2024-01-01 is a Monday
+        for (day, hour) in [(1, 9), (1, 9), (3, 9), (3, 22)] {
+            let mut e = entry(Some("open terminal"), true);
+            e.timestamp = at(day, hour);
+            history.add_entry(e);
+        }
+        // Failures don't count towards usage patterns
+        let mut failed = entry(None, false);
+        failed.timestamp = at(2, 14);
+        history.add_entry(failed);
+
+        let distribution = history.get_time_distribution();
+        assert_eq!(distribution.by_hour[9], 3);
+        assert_eq!(distribution.by_hour[22], 1);
+        assert_eq!(distribution.by_hour[14], 0);
+        assert_eq!(distribution.by_day, [2, 0, 2, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_incremental_statistics_match_full_scan() {
+        let mut history = CommandHistory::new();
+        // Enough to push the oldest entries out of the retention cap
+        for i in 0..1100 {
+            let command = ["open terminal", "open browser", "take screenshot"][i % 3];
+            let mut e = entry((i % 5 != 0).then_some(command), i % 4 != 0);
+            e.duration_ms = i as u64;
+            history.add_entry(e);
+            if i % 97 == 0 {
+                assert_eq!(history.get_statistics(), history.get_statistics_where(|_| true));
+            }
+        }
+        assert_eq!(history.get_all_entries().len(), 1000);
+        assert_eq!(history.get_statistics(), history.get_statistics_where(|_| true));
+    }
+
+    #[test]
+    fn test_success_rate_per_command() {
+        let mut history = CommandHistory::new();
+        for success in [true, true, false, true] {
+            history.add_entry(entry(Some("open terminal"), success));
+        }
+        history.add_entry(entry(Some("take screenshot"), false));
+        history.add_entry(entry(None, false));
+
+        let stats = history.get_statistics();
+        assert_eq!(stats.most_used(5), vec![("open terminal", 4, 75.0), ("take screenshot", 1, 0.0)]);
+        assert_eq!(stats.most_used(1).len(), 1);
+        // One failed attempt isn't enough to flag a command
+        assert!(stats.low_performing().is_empty());
+
+        for success in [false, false, true] {
+            history.add_entry(entry(Some("take screenshot"), success));
+        }
+        for success in [false, true, true] {
+            history.add_entry(entry(Some("lock screen"), success));
+        }
+        assert_eq!(history.get_statistics().low_performing(), vec![("take screenshot", 4, 25.0)]);
+    }
+
+    #[test]
+    fn test_clear_resets_statistics() {
+        let mut history = CommandHistory::new();
+        history.add_entry(entry(Some("open terminal"), true));
+        history.add_entry(entry(None, false));
+        assert_eq!(history.get_statistics().total_commands, 2);
+
+        history.clear();
+
+        let stats = history.get_statistics();
+        assert!(history.get_all_entries().is_empty());
+        assert_eq!(stats.total_commands, 0);
+        assert_eq!(stats.successful_commands, 0);
+        assert!(stats.command_usage.is_empty());
+        assert_eq!(stats.avg_duration_ms, 0);
+    }
+}
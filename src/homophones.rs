@@ -0,0 +1,56 @@
+// Words Whisper mixes up because they sound the same ("close"/"clothes",
+// "mail"/"male"). Each declared group collapses to its first word on both sides of a
+// comparison, so a command phrase matches whichever spelling was transcribed.
+
+use std::collections::HashMap;
+
+use crate::builtins::normalize;
+
+#[derive(Debug, Clone, Default)]
+pub struct Homophones {
+    canonical: HashMap<String, String>,
+}
+
+impl Homophones {
+    pub fn new(groups: &[Vec<String>]) -> Self {
+        let mut canonical = HashMap::new();
+        for group in groups {
+            let mut words = group.iter().map(|word| normalize(word)).filter(|word| !word.is_empty());
+            if let Some(first) = words.next() {
+                for word in words {
+                    canonical.insert(word, first.clone());
+                }
+            }
+        }
+        Homophones { canonical }
+    }
+
+    // Lowercases the text and swaps every declared homophone for its group's first word
+    pub fn canonicalize(&self, text: &str) -> String {
+        let text = text.to_lowercase();
+        if self.canonical.is_empty() {
+            return text;
+        }
+        text.split_whitespace()
+            .map(|token| self.canonical.get(&normalize(token)).map_or(token, |word| word.as_str()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonicalize_homophones() {
+        let homophones = Homophones::new(&[
+            vec!["close".to_string(), "clothes".to_string()],
+            vec!["Mail".to_string(), "male".to_string()],
+        ]);
+        assert_eq!(homophones.canonicalize("Clothes window."), "close window.");
+        assert_eq!(homophones.canonicalize("check male"), "check mail");
+        assert_eq!(homophones.canonicalize("close the door"), "close the door");
+        assert_eq!(Homophones::default().canonicalize("Clothes Window"), "clothes window");
+    }
+}
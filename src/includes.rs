@@ -0,0 +1,151 @@
+// Shortcuts kept outside the settings file, so a long list can live in its own file and be
+// shared between machines. JSON files hold the same phrase -> shortcut object as `shortcuts`;
+// a `.toml` file holds plain `"phrase" = "command"` lines. Later files win on a clash.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use log::warn;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::shortcuts::Shortcut;
+
+// `"shortcuts_file": "shortcuts.json"` or a list of paths
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum ShortcutFiles {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl ShortcutFiles {
+    pub fn paths(&self) -> &[String] {
+        match self {
+            ShortcutFiles::One(path) => std::slice::from_ref(path),
+            ShortcutFiles::Many(paths) => paths,
+        }
+    }
+}
+
+pub fn load(path: &str) -> Result<HashMap<String, Shortcut>, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("shortcuts_file {}: {}", path, e))?;
+    let is_toml = Path::new(path).extension().is_some_and(|extension| extension.eq_ignore_ascii_case("toml"));
+    let parsed = if is_toml {
+        parse_toml(&contents)
+    } else {
+        serde_json::from_str(&contents).map_err(|e| e.to_string())
+    };
+    parsed.map_err(|e| format!("shortcuts_file {}: {}", path, e))
+}
+
+// Adds `included` over `shortcuts`, warning about each phrase it replaces
+pub fn merge(shortcuts: &mut HashMap<String, Shortcut>, included: HashMap<String, Shortcut>, source: &str) {
+    for (phrase, shortcut) in included {
+        if let Some(previous) = shortcuts.insert(phrase.clone(), shortcut) {
+            warn!("'{}' from {} replaces '{}'", phrase, source, previous.display_command());
+        }
+    }
+}
+
+// Just enough TOML for a flat table of strings: `key = "value"` lines with bare, "basic"
+// or 'literal' keys, and # comments
+fn parse_toml(contents: &str) -> Result<HashMap<String, Shortcut>, String> {
+    let mut shortcuts = HashMap::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fail = |message: &str| format!("line {}: {}", number + 1, message);
+        let (phrase, rest) = if line.starts_with(['"', '\'']) {
+            toml_string(line).ok_or_else(|| fail("unterminated phrase"))?
+        } else {
+            let end = line.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-')).unwrap_or(line.len());
+            (line[..end].to_string(), &line[end..])
+        };
+        let rest = rest.trim_start().strip_prefix('=').ok_or_else(|| fail("expected `\"phrase\" = \"command\"`"))?;
+        let (command, rest) = toml_string(rest.trim_start()).ok_or_else(|| fail("the command must be a quoted string"))?;
+        let rest = rest.trim_start();
+        if !rest.is_empty() && !rest.starts_with('#') {
+            return Err(fail("unexpected text after the command"));
+        }
+        if phrase.is_empty() {
+            return Err(fail("empty phrase"));
+        }
+        if shortcuts.insert(phrase.clone(), Shortcut::from(command.as_str())).is_some() {
+            return Err(fail(&format!("'{}' is defined twice", phrase)));
+        }
+    }
+    Ok(shortcuts)
+}
+
+// A quoted string at the start of `text` and what follows it
+fn toml_string(text: &str) -> Option<(String, &str)> {
+    if let Some(literal) = text.strip_prefix('\'') {
+        let end = literal.find('\'')?;
+        return Some((literal[..end].to_string(), &literal[end + 1..]));
+    }
+    let mut chars = text.strip_prefix('"')?.char_indices();
+    let mut value = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, &text[i + 2..])),
+            '\\' => match chars.next()?.1 {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'r' => value.push('\r'),
+                'u' => {
+                    let digits: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                    value.push(u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32)?);
+                }
+                escaped => value.push(escaped),
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_toml() {
+        let shortcuts = parse_toml(
+            "# Shared shortcuts\n\
+             \"open terminal\" = \"gnome-terminal\"\n\
+             'say \"hi\"' = 'notify-send \"hi\"'  # literal strings keep quotes\n\
+             screenshot = \"flameshot gui\"\n\
+             \n\
+             \"open notes\" = \"code \\\"$HOME/notes\\\"\"\n",
+        )
+        .unwrap();
+        assert_eq!(shortcuts.len(), 4);
+        assert_eq!(shortcuts["open terminal"].command, "gnome-terminal");
+        assert_eq!(shortcuts["say \"hi\""].command, "notify-send \"hi\"");
+        assert_eq!(shortcuts["screenshot"].command, "flameshot gui");
+        assert_eq!(shortcuts["open notes"].command, "code \"$HOME/notes\"");
+
+        assert!(parse_toml("\"open terminal\" = gnome-terminal").unwrap_err().starts_with("line 1"));
+        assert!(parse_toml("\"open terminal\" = \"gnome-terminal").is_err());
+        assert!(parse_toml("[shortcuts]").is_err());
+        assert!(parse_toml("a = \"x\"\na = \"y\"").unwrap_err().contains("twice"));
+    }
+
+    #[test]
+    fn test_later_files_win() {
+        let mut shortcuts = HashMap::from([("open browser".to_string(), Shortcut::from("firefox"))]);
+        merge(&mut shortcuts, HashMap::from([("open browser".to_string(), Shortcut::from("chromium"))]), "work.toml");
+        merge(&mut shortcuts, HashMap::from([("lock screen".to_string(), Shortcut::from("loginctl lock-session"))]), "home.json");
+        assert_eq!(shortcuts["open browser"].command, "chromium");
+        assert_eq!(shortcuts.len(), 2);
+
+        let files: ShortcutFiles = serde_json::from_str("\"shortcuts.json\"").unwrap();
+        assert_eq!(files.paths(), ["shortcuts.json"]);
+        let files: ShortcutFiles = serde_json::from_str("[\"a.json\", \"b.toml\"]").unwrap();
+        assert_eq!(files.paths(), ["a.json", "b.toml"]);
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+// Key-downs are only sampled every poll, so people rarely get every key of a combo
+// into the same sample. A combo counts as pressed when each of its keys was seen
+// down within `window` of the others.
+pub struct ComboDetector<K> {
+    combo: Vec<K>,
+    window: Duration,
+    last_down: HashMap<K, Instant>,
+}
+
+impl<K: Copy + Eq + Hash> ComboDetector<K> {
+    pub fn new(combo: Vec<K>, window: Duration) -> Self {
+        ComboDetector {
+            combo,
+            window,
+            last_down: HashMap::new(),
+        }
+    }
+
+    pub fn update(&mut self, pressed: &[K], now: Instant) -> bool {
+        for key in &self.combo {
+            if pressed.contains(key) {
+                self.last_down.insert(*key, now);
+            }
+        }
+
+        // Old key-downs alone must not fire the combo after everything was released
+        self.is_held(pressed)
+            && self.combo.iter().all(|key| {
+                self.last_down
+                    .get(key)
+                    .is_some_and(|&seen| now.duration_since(seen) <= self.window)
+            })
+    }
+
+    pub fn keys(&self) -> &[K] {
+        &self.combo
+    }
+
+    pub fn is_held(&self, pressed: &[K]) -> bool {
+        self.combo.iter().any(|key| pressed.contains(key))
+    }
+
+    // Forget earlier key-downs once the combo has fired and been released
+    pub fn reset(&mut self) {
+        self.last_down.clear();
+    }
+}
+
+// Several combos polled together. When bindings overlap (F8 and Ctrl+F8) only the most
+// specific satisfied one fires: a bare F8 waits out the combo window in case Ctrl is on its
+// way, and fires once the window passes or the key is released.
+pub struct Hotkeys<K, A> {
+    bindings: Vec<(ComboDetector<K>, A)>,
+    window: Duration,
+    // Binding that is satisfied but could still grow into a larger one, and since when
+    pending: Option<(usize, Instant)>,
+    fired: Option<usize>,
+}
+
+impl<K: Copy + Eq + Hash, A: Clone> Hotkeys<K, A> {
+    pub fn new(window: Duration) -> Self {
+        Hotkeys {
+            bindings: Vec::new(),
+            window,
+            pending: None,
+            fired: None,
+        }
+    }
+
+    pub fn bind(mut self, combo: Vec<K>, action: A) -> Self {
+        self.bindings.push((ComboDetector::new(combo, self.window), action));
+        self
+    }
+
+    pub fn update(&mut self, pressed: &[K], now: Instant) -> Option<A> {
+        let satisfied: Vec<bool> = self
+            .bindings
+            .iter_mut()
+            .map(|(detector, _)| detector.update(pressed, now))
+            .collect();
+
+        // Largest combo wins; among equals, the one bound first
+        let best = (0..self.bindings.len())
+            .filter(|&i| satisfied[i])
+            .max_by_key(|&i| (self.bindings[i].0.keys().len(), std::cmp::Reverse(i)));
+
+        let Some(best) = best else {
+            // Released before the window ran out: nothing bigger came, so fire what we held back
+            return self.pending.take().map(|(i, _)| self.fire(i));
+        };
+
+        let grows_into_larger = self.bindings.iter().any(|(other, _)| {
+            let keys = self.bindings[best].0.keys();
+            other.keys().len() > keys.len() && keys.iter().all(|key| other.keys().contains(key))
+        });
+        if grows_into_larger {
+            let since = match self.pending {
+                Some((i, since)) if i == best => since,
+                _ => now,
+            };
+            if now.duration_since(since) < self.window {
+                self.pending = Some((best, since));
+                return None;
+            }
+        }
+
+        Some(self.fire(best))
+    }
+
+    fn fire(&mut self, index: usize) -> A {
+        self.pending = None;
+        self.fired = Some(index);
+        self.bindings[index].1.clone()
+    }
+
+    // Whether any key of the binding that last fired is still down
+    pub fn is_held(&self, pressed: &[K]) -> bool {
+        self.fired.is_some_and(|i| self.bindings[i].0.is_held(pressed))
+    }
+
+    pub fn reset(&mut self) {
+        for (detector, _) in &mut self.bindings {
+            detector.reset();
+        }
+        self.pending = None;
+        self.fired = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_staggered_key_downs_satisfy_combo() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut detector = ComboDetector::new(vec!["ctrl", "f8"], Duration::from_millis(150));
+
+        // Ctrl is tapped slightly before F8 and released before F8 is seen
+        assert!(!detector.update(&["ctrl"], at(0)));
+        assert!(detector.update(&["f8"], at(100)));
+
+        // Too far apart to count as a combo
+        detector.reset();
+        assert!(!detector.update(&["ctrl"], at(1000)));
+        assert!(!detector.update(&[], at(1100)));
+        assert!(!detector.update(&["f8"], at(1300)));
+    }
+
+    #[test]
+    fn test_most_specific_combo_wins() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut hotkeys = Hotkeys::new(Duration::from_millis(150))
+            .bind(vec!["f8"], "record")
+            .bind(vec!["ctrl", "f8"], "combo");
+
+        // F8 lands a poll before Ctrl: only the combo fires
+        assert_eq!(hotkeys.update(&["f8"], at(0)), None);
+        assert_eq!(hotkeys.update(&["ctrl", "f8"], at(50)), Some("combo"));
+        assert!(hotkeys.is_held(&["ctrl"]));
+        hotkeys.reset();
+
+        // Both at once
+        assert_eq!(hotkeys.update(&["ctrl", "f8"], at(1000)), Some("combo"));
+        hotkeys.reset();
+
+        // Bare F8 fires once the window passes without Ctrl...
+        assert_eq!(hotkeys.update(&["f8"], at(2000)), None);
+        assert_eq!(hotkeys.update(&["f8"], at(2100)), None);
+        assert_eq!(hotkeys.update(&["f8"], at(2200)), Some("record"));
+        hotkeys.reset();
+
+        // ...or when a quick tap is released
+        assert_eq!(hotkeys.update(&["f8"], at(3000)), None);
+        assert_eq!(hotkeys.update(&[], at(3050)), Some("record"));
+    }
+}
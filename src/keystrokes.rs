@@ -0,0 +1,147 @@
+// Shortcuts that press keys in the focused window instead of running a program.
+// A sequence is whitespace-separated chords, each chord being modifiers and one key
+// joined by '+': "ctrl+c", "ctrl+shift+t", "alt+tab enter". Use "plus" for the + key.
+
+use enigo::{Direction, Enigo, Key, Keyboard};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Modifier {
+    Ctrl,
+    Shift,
+    Alt,
+    Meta,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chord {
+    pub modifiers: Vec<Modifier>,
+    pub key: Key,
+}
+
+pub fn parse_key_sequence(sequence: &str) -> Result<Vec<Chord>, String> {
+    let chords: Vec<Chord> = sequence.split_whitespace().map(parse_chord).collect::<Result<_, _>>()?;
+    if chords.is_empty() {
+        return Err("empty key sequence".to_string());
+    }
+    Ok(chords)
+}
+
+fn parse_chord(chord: &str) -> Result<Chord, String> {
+    let parts: Vec<&str> = chord.split('+').collect();
+    let (key, modifiers) = parts.split_last().ok_or_else(|| format!("empty chord in '{}'", chord))?;
+
+    let modifiers = modifiers
+        .iter()
+        .map(|name| parse_modifier(name).ok_or_else(|| format!("unknown modifier '{}' in '{}'", name, chord)))
+        .collect::<Result<_, _>>()?;
+    let key = parse_key(key).ok_or_else(|| format!("unknown key '{}' in '{}'", key, chord))?;
+    Ok(Chord { modifiers, key })
+}
+
+fn parse_modifier(name: &str) -> Option<Modifier> {
+    match name.to_lowercase().as_str() {
+        "ctrl" | "control" => Some(Modifier::Ctrl),
+        "shift" => Some(Modifier::Shift),
+        "alt" | "option" => Some(Modifier::Alt),
+        "super" | "meta" | "cmd" | "win" => Some(Modifier::Meta),
+        _ => None,
+    }
+}
+
+fn parse_key(name: &str) -> Option<Key> {
+    let lower = name.to_lowercase();
+    let key = match lower.as_str() {
+        "enter" | "return" => Key::Return,
+        "tab" => Key::Tab,
+        "esc" | "escape" => Key::Escape,
+        "space" => Key::Space,
+        "backspace" => Key::Backspace,
+        "delete" | "del" => Key::Delete,
+        "up" => Key::UpArrow,
+        "down" => Key::DownArrow,
+        "left" => Key::LeftArrow,
+        "right" => Key::RightArrow,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        "plus" => Key::Unicode('+'),
+        "f1" => Key::F1,
+        "f2" => Key::F2,
+        "f3" => Key::F3,
+        "f4" => Key::F4,
+        "f5" => Key::F5,
+        "f6" => Key::F6,
+        "f7" => Key::F7,
+        "f8" => Key::F8,
+        "f9" => Key::F9,
+        "f10" => Key::F10,
+        "f11" => Key::F11,
+        "f12" => Key::F12,
+        _ => {
+            let mut chars = lower.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Key::Unicode(c),
+                _ => return None,
+            }
+        }
+    };
+    Some(key)
+}
+
+fn modifier_key(modifier: Modifier) -> Key {
+    match modifier {
+        Modifier::Ctrl => Key::Control,
+        Modifier::Shift => Key::Shift,
+        Modifier::Alt => Key::Alt,
+        Modifier::Meta => Key::Meta,
+    }
+}
+
+pub fn send_keys(sequence: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let chords = parse_key_sequence(sequence)?;
+    let mut enigo = Enigo::new(&enigo::Settings::default())?;
+
+    for chord in &chords {
+        for &modifier in &chord.modifiers {
+            enigo.key(modifier_key(modifier), Direction::Press)?;
+        }
+        let clicked = enigo.key(chord.key, Direction::Click);
+        // Release modifiers even if the key failed, so Ctrl isn't left stuck down
+        for &modifier in chord.modifiers.iter().rev() {
+            enigo.key(modifier_key(modifier), Direction::Release)?;
+        }
+        clicked?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key_sequence() {
+        assert_eq!(
+            parse_key_sequence("ctrl+c").unwrap(),
+            vec![Chord { modifiers: vec![Modifier::Ctrl], key: Key::Unicode('c') }]
+        );
+        assert_eq!(
+            parse_key_sequence("Ctrl+Shift+T").unwrap(),
+            vec![Chord { modifiers: vec![Modifier::Ctrl, Modifier::Shift], key: Key::Unicode('t') }]
+        );
+
+        let chords = parse_key_sequence("alt+tab  enter ctrl+plus").unwrap();
+        assert_eq!(chords.len(), 3);
+        assert_eq!(chords[1], Chord { modifiers: vec![], key: Key::Return });
+        assert_eq!(chords[2].key, Key::Unicode('+'));
+    }
+
+    #[test]
+    fn test_parse_key_sequence_errors() {
+        assert!(parse_key_sequence("").is_err());
+        assert!(parse_key_sequence("hyper+c").unwrap_err().contains("unknown modifier 'hyper'"));
+        assert!(parse_key_sequence("ctrl+banana").unwrap_err().contains("unknown key 'banana'"));
+        assert!(parse_key_sequence("ctrl+").is_err());
+    }
+}
@@ -0,0 +1,113 @@
+// Fallback for transcriptions that match no shortcut but look like an app name
+// ("Spotify", "open Slack"). This runs a guessed program, so it is off by default.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::builtins::normalize;
+
+const LAUNCH_VERBS: &[&str] = &["open", "launch", "start", "run"];
+const FILLER_WORDS: &[&str] = &["please", "the", "app", "application"];
+// Longer transcriptions are sentences, not app names
+const MAX_APP_NAME_WORDS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Platform {
+    MacOs,
+    Linux,
+    Windows,
+}
+
+impl Platform {
+    pub fn current() -> Self {
+        if cfg!(target_os = "macos") {
+            Platform::MacOs
+        } else if cfg!(target_os = "windows") {
+            Platform::Windows
+        } else {
+            Platform::Linux
+        }
+    }
+}
+
+pub fn extract_app_name(text: &str) -> Option<String> {
+    let normalized = normalize(text);
+    let mut words: Vec<&str> = normalized.split_whitespace().collect();
+
+    if words.first().is_some_and(|w| LAUNCH_VERBS.contains(w)) {
+        words.remove(0);
+    }
+    words.retain(|w| !FILLER_WORDS.contains(w));
+
+    // Only plain words end up in the shell command
+    let is_plain = |w: &&str| w.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if words.is_empty() || words.len() > MAX_APP_NAME_WORDS || !words.iter().all(is_plain) {
+        return None;
+    }
+    Some(words.join(" "))
+}
+
+pub fn launch_command(app: &str, platform: Platform) -> String {
+    match platform {
+        Platform::MacOs => format!("open -a \"{}\"", app),
+        // Only desktop entries, never arbitrary binaries on PATH ("reboot")
+        Platform::Linux => format!("gtk-launch {}", app.replace(' ', "-")),
+        Platform::Windows => format!("start \"\" \"{}\"", app),
+    }
+}
+
+// Hands an existing file to `open`, which normally wraps the desktop's default handler.
+// Relative paths are resolved first so the editor gets the file this process uses.
+pub fn open_file<F>(path: &str, open: F) -> Result<PathBuf, Box<dyn std::error::Error>>
+where
+    F: FnOnce(&Path) -> io::Result<()>,
+{
+    let resolved = Path::new(path)
+        .canonicalize()
+        .map_err(|e| format!("{} can't be opened: {}", path, e))?;
+    open(&resolved)?;
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_app_name() {
+        assert_eq!(extract_app_name("Spotify."), Some("spotify".to_string()));
+        assert_eq!(extract_app_name("Open the Slack app, please"), Some("slack".to_string()));
+        assert_eq!(extract_app_name("launch visual studio code"), Some("visual studio code".to_string()));
+        assert_eq!(extract_app_name("what is the weather like today"), None);
+        assert_eq!(extract_app_name("open"), None);
+    }
+
+    #[test]
+    fn test_launch_command_per_platform() {
+        assert_eq!(launch_command("spotify", Platform::MacOs), "open -a \"spotify\"");
+        assert_eq!(launch_command("visual studio code", Platform::Linux), "gtk-launch visual-studio-code");
+        assert_eq!(launch_command("spotify", Platform::Windows), "start \"\" \"spotify\"");
+    }
+
+    #[test]
+    fn test_open_file_passes_resolved_path() {
+        let mut opened = None;
+        let resolved = open_file("Cargo.toml", |path| {
+            opened = Some(path.to_path_buf());
+            Ok(())
+        })
+        .unwrap();
+        let expected = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml").canonicalize().unwrap();
+        assert_eq!(resolved, expected);
+        assert_eq!(opened, Some(expected));
+
+        // A missing file never reaches the opener
+        let mut called = false;
+        assert!(open_file("does_not_exist.json", |_| {
+            called = true;
+            Ok(())
+        })
+        .is_err());
+        assert!(!called);
+    }
+}
@@ -1,42 +1,482 @@
 use std::collections::HashMap;
 use std::fs;
+use std::io::{self, BufRead, Write};
 use std::path::Path;
 use std::process::Command;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
 use std::time::{Duration, Instant};
 
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::traits::{DeviceTrait, StreamTrait};
 use device_query::{DeviceQuery, DeviceState, Keycode};
-use hound::{WavSpec, WavWriter};
+use error::AssistantError;
+use state::RuntimeState;
+use log::{debug, error, info, warn};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use whisper_rs::{FullParams, SamplingStrategy, WhisperContext};
-use chrono::Local;
+use chrono::{Local, NaiveTime};
+use whisper_rs::WhisperContext;
 
 mod tts;
-use tts::VoiceFeedback;
+use tts::{truncate_for_speech, Prosody, VoiceFeedback};
 
 mod history;
-use history::{CommandEntry, CommandHistory};
+mod homophones;
+use homophones::Homophones;
+use history::{CommandEntry, CommandHistory, CommandOutcome, HistoryFormat, TimeGrouping, FAILED_TRANSCRIPTION};
 
 mod suggestions;
 use suggestions::SmartSuggestions;
 
-#[derive(Debug, Serialize, Deserialize)]
+mod error;
+mod model;
+
+mod shortcuts;
+use shortcuts::{RegexShortcuts, Shortcut};
+mod includes;
+use includes::ShortcutFiles;
+
+mod worker;
+use worker::TranscriptionQueue;
+
+mod builtins;
+use builtins::BuiltinCommand;
+
+mod cli;
+
+mod keys;
+mod launcher;
+mod notifications;
+mod numbers;
+mod keystrokes;
+mod dictation;
+mod clipboard;
+mod reload;
+use reload::SettingsWatcher;
+mod state;
+use keys::{ComboDetector, Hotkeys};
+
+mod audio;
+mod breaker;
+use breaker::CircuitBreaker;
+mod cooldown;
+use cooldown::Cooldowns;
+mod guard;
+use guard::CommandGuard;
+mod devices;
+mod wake;
+use wake::{RollingBuffer, WakeListener};
+mod captions;
+use captions::CaptionSink;
+mod cues;
+use cues::Cue;
+mod shutdown;
+#[cfg(feature = "http-server")]
+mod server;
+
+mod transcribe;
+use transcribe::{transcribe_audio, transcribe_nbest, transcribe_samples, transcribe_segments, DecodingOverrides, DecodingProfile, Hypothesis, SamplingSettings, Task, TranscribeOptions};
+
+const SETTINGS_PATH: &str = "voice_assistant_settings.json";
+const HISTORY_PATH: &str = "command_history.json";
+const STATE_PATH: &str = "voice_assistant_state.json";
+
+// Recordings waiting for transcription before new ones are dropped
+const RECORDING_QUEUE_CAPACITY: usize = 4;
+
+// Recordings shorter than this are flagged as probably clipped
+const SHORT_RECORDING_MS: u64 = 300;
+// Audio kept around the speech when trim_silence cuts the quiet ends
+const TRIM_PADDING: Duration = Duration::from_millis(200);
+// How often the capture thread checks whether to stop
+const CAPTURE_POLL: Duration = Duration::from_millis(20);
+// How often a paused wake listener checks whether it may listen again
+const WAKE_IDLE_POLL: Duration = Duration::from_millis(100);
+// How often streaming_preview transcribes the audio so far
+const PREVIEW_INTERVAL: Duration = Duration::from_secs(2);
+// Room noise recorded by --calibrate
+const CALIBRATION_LENGTH: Duration = Duration::from_secs(3);
+
+// Longest we hold off recording for voice feedback to finish before cutting it off
+const SELF_AUDIO_MAX_WAIT: Duration = Duration::from_secs(2);
+
+// --once stops recording by itself if the keybind is never pressed
+const ONCE_MAX_RECORDING: Duration = Duration::from_secs(10);
+// How long one-shot runs wait for queued announcements before exiting
+const ONCE_SPEECH_MAX_WAIT: Duration = Duration::from_secs(10);
+// How long "Shutting down" may hold up the exit
+const SHUTDOWN_SPEECH_MAX_WAIT: Duration = Duration::from_secs(3);
+// Peak level normalize_audio brings recordings up to, leaving a little headroom
+const NORMALIZE_TARGET_PEAK: f32 = 0.9;
+// How long to wait after a change to the settings file before reading it
+const RELOAD_SETTLE: Duration = Duration::from_millis(200);
+// Recorded as the executed command for dictated text
+const DICTATION_COMMAND: &str = "type text";
+// How long dictation waits for the keybind to be let go before giving up
+const DICTATION_MAX_WAIT: Duration = Duration::from_secs(3);
+// Whisper's prompt holds about 224 tokens
+const MAX_PROMPT_PHRASES: usize = 40;
+const DEFAULT_KEYBIND: &str = "F8";
+// Longest stretch of command output read aloud
+const MAX_SPOKEN_OUTPUT_CHARS: usize = 150;
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 struct Settings {
-    keybind: String,
+    // Older single binding for command recording, used when `keybinds` is empty
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    keybind: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    keybinds: Vec<Keybind>,
     whisper_model_path: String,
-    shortcuts: HashMap<String, String>,
+    shortcuts: HashMap<String, Shortcut>,
+    // More shortcuts in separate files, merged over `shortcuts` by parse_settings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    shortcuts_file: Option<ShortcutFiles>,
+    // `shortcuts` as written in the settings file, which is what save_settings writes back
+    #[serde(skip)]
+    own_shortcuts: HashMap<String, Shortcut>,
+    // Spoken enable/disable of shortcuts from a shortcuts_file, which can't be saved there,
+    // so they are laid over every reload for the rest of the session
+    #[serde(skip)]
+    included_toggles: HashMap<String, bool>,
+    // The `re:` shortcut keys, compiled by parse_settings
+    #[serde(skip)]
+    regex_shortcuts: RegexShortcuts,
     #[serde(default = "default_voice_feedback")]
     voice_feedback: VoiceFeedbackSettings,
+    #[serde(default)]
+    notifications: NotificationSettings,
+    // Tones on recording start and stop and on errors, separate from voice feedback
+    #[serde(default)]
+    audio_cues: bool,
+    #[serde(default)]
+    http_server: HttpServerSettings,
+    #[serde(default)]
+    skip_memory_check: bool,
+    #[serde(default)]
+    skip_model_check: bool,
+    // Only matters for builds with the `cuda` or `metal` feature; false forces the CPU
+    #[serde(default = "default_use_gpu")]
+    use_gpu: bool,
+    #[serde(default)]
+    startup_accuracy_check: Option<AccuracyCheckSettings>,
+    #[serde(default)]
+    archive_history_on_clear: bool,
+    // JsonLines appends each entry instead of rewriting the file; either format is read
+    #[serde(default)]
+    history_format: HistoryFormat,
+    // Keep voice toggles like "mute" and "pause listening" across restarts
+    #[serde(default)]
+    persist_runtime_state: bool,
+    #[serde(default = "default_language")]
+    language: String,
+    #[serde(default = "default_language")]
+    default_language: String,
+    #[serde(default = "default_min_language_confidence")]
+    min_language_confidence: f32,
+    #[serde(default)]
+    nbest: NBestSettings,
+    // Fuzzy matches at or above this run without an exact phrase match
+    #[serde(default = "default_match_min_confidence", alias = "match_confidence")]
+    match_min_confidence: f32,
+    // Lower bar for "did you mean" suggestions after a miss
+    #[serde(default = "default_suggestion_min_confidence")]
+    suggestion_min_confidence: f32,
+    #[serde(default)]
+    circuit_breaker: CircuitBreakerSettings,
+    // Type every transcription into the focused window instead of running shortcuts
+    #[serde(default)]
+    dictation: bool,
+    // Also copy every transcription to the clipboard
+    #[serde(default)]
+    copy_to_clipboard: bool,
+    // Stop recording by itself once the speaker has been quiet for silence_duration_ms
+    #[serde(default)]
+    auto_stop_on_silence: bool,
+    // RMS level below which input counts as silence
+    #[serde(default = "default_silence_threshold")]
+    silence_threshold: f32,
+    #[serde(default = "default_silence_duration_ms")]
+    silence_duration_ms: u64,
+    // Scale quiet recordings up before transcribing them
+    #[serde(default)]
+    normalize_audio: bool,
+    // Cut the silence before and after the speech, using silence_threshold
+    #[serde(default)]
+    trim_silence: bool,
+    // Recordings stop by themselves after this long; 0 means no limit
+    #[serde(default = "default_max_recording_secs")]
+    max_recording_secs: u64,
+    // Listen all the time and start recording on hearing this ("hey assistant")
+    #[serde(default)]
+    wake_word: Option<String>,
+    // How much recent audio is searched for the wake word, and how often
+    #[serde(default = "default_wake_window_ms")]
+    wake_window_ms: u64,
+    #[serde(default = "default_wake_interval_ms")]
+    wake_interval_ms: u64,
+    // Record from the first input whose name contains this; unset uses the system default
+    #[serde(default)]
+    input_device: Option<String>,
+    // Named pipe (created if missing) that receives each transcription as a line
+    #[serde(default)]
+    caption_fifo: Option<String>,
+    // Where audio uploaded to the HTTP server is written while it's decoded; unset uses the system's
+    #[serde(default)]
+    temp_dir: Option<String>,
+    // Prints an interim transcription while recording; costs a decode every couple of seconds
+    #[serde(default)]
+    streaming_preview: bool,
+    // Whisper parameter preset; Command suits short phrases
+    #[serde(default)]
+    decoding_profile: DecodingProfile,
+    // Individual Whisper parameters that win over the profile
+    #[serde(default)]
+    decoding_overrides: DecodingOverrides,
+    // Names and jargon added to the shortcut phrases in Whisper's prompt
+    #[serde(default)]
+    vocabulary: Vec<String>,
+    // Used as-is instead of the generated prompt, whatever the profile; "" sends none
+    #[serde(default)]
+    initial_prompt: Option<String>,
+    // Greedy or beam search; unset uses the profile's strategy
+    #[serde(default)]
+    sampling: Option<SamplingSettings>,
+    // Groups of words Whisper confuses, e.g. [["close", "clothes"]]; matched as equal
+    #[serde(default)]
+    homophones: Vec<Vec<String>>,
+    // Accept near-misses like "yeh" when confirming; unset means exact words only
+    #[serde(default)]
+    confirmation_fuzzy_threshold: Option<f32>,
+    #[serde(default)]
+    recording_mode: RecordingMode,
+    // Translate gives English text whatever language is spoken
+    #[serde(default)]
+    task: Task,
+    // How far apart the keys of a combo keybind (e.g. "LCTRL+F8") may go down
+    #[serde(default = "default_combo_window_ms")]
+    combo_window_ms: u64,
+    // Name you address the assistant by ("computer"); it's stripped before matching
+    #[serde(default)]
+    assistant_name: Option<String>,
+    // Ignore transcriptions that don't start or end with `assistant_name`
+    #[serde(default)]
+    require_assistant_name: bool,
+    // Longer transcriptions are treated as dictation and never matched to shortcuts
+    #[serde(default)]
+    max_words_for_command_match: Option<usize>,
+    // Run when the whole transcription is a number, e.g. "wmctrl -s {n}" for "three"
+    #[serde(default)]
+    numeric_command_template: Option<String>,
+    // Pause after a command runs before dispatching the next one, e.g. so keys go to a new window
+    #[serde(default)]
+    post_command_settle_ms: u64,
+    // Match and announce commands without running them
+    #[serde(default, alias = "dry_run")]
+    preview_mode: bool,
+    // Set by --dry-run for this session only, so it is never saved into the file
+    #[serde(skip)]
+    dry_run: bool,
+    // Try to open an app named in an unmatched transcription ("Spotify")
+    #[serde(default)]
+    app_launch_fallback: bool,
+    // Split "open terminal and open browser" into separate commands
+    #[serde(default)]
+    allow_command_chaining: bool,
+    #[serde(default = "default_chain_words")]
+    chain_words: Vec<String>,
+    // Chained commands past this many are dropped
+    #[serde(default = "default_max_chained_commands")]
+    max_chained_commands: usize,
+    // Throws away the recording in progress; empty turns it off
+    #[serde(default = "default_cancel_key")]
+    cancel_key: String,
+    // A shortcut that ran this recently is skipped; shortcuts can set their own
+    #[serde(default)]
+    cooldown_ms: u64,
+    // Runs the last successful command again; empty turns it off
+    #[serde(default = "default_repeat_phrase")]
+    repeat_phrase: String,
+    // Filler words stripped from the ends of `{placeholder}` captures
+    #[serde(default = "default_arg_trim_words")]
+    arg_trim_words: Vec<String>,
+    // Regexes; a command matching any of them never runs
+    #[serde(default)]
+    denylist: Vec<String>,
+    // With allowlist_only, only commands matching one of these run
+    #[serde(default)]
+    allowlist: Vec<String>,
+    #[serde(default)]
+    allowlist_only: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 struct VoiceFeedbackSettings {
     enabled: bool,
     announce_recording: bool,
     confirm_commands: bool,
     announce_errors: bool,
+    // Local-time range, e.g. ["22:00", "07:00"], during which nothing is spoken
+    #[serde(default)]
+    quiet_hours: Option<(NaiveTime, NaiveTime)>,
+    // Say "42 percent" rather than letting the TTS engine guess at "42%"
+    #[serde(default)]
+    natural_readback: bool,
+    // Don't start capturing while we're still talking, or we transcribe ourselves
+    #[serde(default)]
+    suppress_self_audio: bool,
+    // Text-to-speech backend, e.g. "speech-dispatcher"; unset uses the platform default
+    #[serde(default)]
+    tts_engine: Option<String>,
+    // Voice name as printed by --list-voices; unset keeps the engine's default
+    #[serde(default)]
+    voice_name: Option<String>,
+    // In the engine's own units, clamped to what it supports
+    #[serde(default = "default_tts_rate")]
+    rate: f32,
+    #[serde(default = "default_tts_pitch")]
+    pitch: f32,
+    #[serde(default = "default_tts_volume")]
+    volume: f32,
+    // Say so when a command is skipped for being on cooldown
+    #[serde(default)]
+    announce_cooldown: bool,
+    // Say "I didn't hear anything" for recordings without speech
+    #[serde(default)]
+    announce_no_speech: bool,
+    // Say "That recording was very short" for likely accidental taps
+    #[serde(default)]
+    announce_short_recordings: bool,
+}
+
+fn default_tts_rate() -> f32 {
+    Prosody::default().rate
+}
+
+fn default_tts_pitch() -> f32 {
+    Prosody::default().pitch
+}
+
+fn default_tts_volume() -> f32 {
+    Prosody::default().volume
+}
+
+impl VoiceFeedbackSettings {
+    fn prosody(&self) -> Prosody {
+        Prosody { rate: self.rate, pitch: self.pitch, volume: self.volume }
+    }
+}
+
+// Local HTTP service; only started by builds with the `http-server` feature
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default)]
+struct HttpServerSettings {
+    enabled: bool,
+    address: String,
+}
+
+impl Default for HttpServerSettings {
+    fn default() -> Self {
+        HttpServerSettings {
+            enabled: false,
+            address: "127.0.0.1:8765".to_string(),
+        }
+    }
+}
+
+// Desktop notifications and WAV cues when a command runs; shortcuts can override icon and cue
+#[derive(Debug, Serialize, Deserialize, Clone, Default, JsonSchema)]
+#[serde(default)]
+struct NotificationSettings {
+    enabled: bool,
+    sound_cues: bool,
+    icon: Option<String>,
+    cue: Option<String>,
+}
+
+// Transcribe a recording of a known phrase at startup to catch a wrong or broken model
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+struct AccuracyCheckSettings {
+    audio_path: String,
+    expected_text: String,
+    #[serde(default = "default_max_word_error_rate")]
+    max_word_error_rate: f32,
+}
+
+fn default_max_word_error_rate() -> f32 {
+    0.3
+}
+
+// Lists the phrases the user is likely to say, so Whisper spells them the same way.
+// The vocabulary goes first so it survives the cut when there are many shortcuts.
+fn shortcut_prompt(shortcuts: &HashMap<String, Shortcut>, vocabulary: &[String]) -> String {
+    let mut phrases: Vec<&str> = shortcuts
+        .iter()
+        .filter(|(phrase, shortcut)| shortcut.enabled && !phrase.contains('{') && !shortcuts::is_regex_phrase(phrase))
+        .map(|(phrase, _)| phrase.as_str())
+        .collect();
+    // Sorted so the same shortcuts always give the same prompt
+    phrases.sort_unstable();
+    let mut words: Vec<&str> = vocabulary.iter().map(|word| word.trim()).filter(|word| !word.is_empty()).collect();
+    words.extend(phrases);
+    words.truncate(MAX_PROMPT_PHRASES);
+    words.join(", ")
+}
+
+// Suspends capture after repeated identical failures, see breaker.rs
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default)]
+struct CircuitBreakerSettings {
+    enabled: bool,
+    // Identical failed transcriptions in a row that open the breaker
+    threshold: usize,
+    // They only count as a row if they all fall within this many seconds
+    window_secs: u64,
+    // 0 keeps capture suspended until restart
+    cooldown_secs: u64,
+}
+
+impl Default for CircuitBreakerSettings {
+    fn default() -> Self {
+        CircuitBreakerSettings {
+            enabled: true,
+            threshold: 3,
+            window_secs: 60,
+            cooldown_secs: 120,
+        }
+    }
+}
+
+// Decode several alternative transcriptions and pick the one that best matches a shortcut
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default)]
+struct NBestSettings {
+    enabled: bool,
+    count: usize,
+    // Exponent on the decode confidence: 0 ignores it, higher values trust Whisper more
+    confidence_weight: f32,
+    selection: NBestSelection,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, JsonSchema)]
+enum NBestSelection {
+    // Best decode confidence and match score together, weighted by `confidence_weight`
+    #[default]
+    Weighted,
+    // The most confident alternative that clears `match_min_confidence`
+    FirstMatch,
+}
+
+impl Default for NBestSettings {
+    fn default() -> Self {
+        NBestSettings {
+            enabled: false,
+            count: 3,
+            confidence_weight: 1.0,
+            selection: NBestSelection::default(),
+        }
+    }
 }
 
 fn default_voice_feedback() -> VoiceFeedbackSettings {
@@ -45,6 +485,182 @@ fn default_voice_feedback() -> VoiceFeedbackSettings {
         announce_recording: true,
         confirm_commands: true,
         announce_errors: true,
+        quiet_hours: None,
+        natural_readback: false,
+        suppress_self_audio: false,
+        tts_engine: None,
+        voice_name: None,
+        rate: default_tts_rate(),
+        pitch: default_tts_pitch(),
+        volume: default_tts_volume(),
+        announce_cooldown: false,
+        announce_no_speech: false,
+        announce_short_recordings: false,
+    }
+}
+
+fn default_use_gpu() -> bool {
+    true
+}
+
+fn default_language() -> String {
+    "en".to_string()
+}
+
+fn default_min_language_confidence() -> f32 {
+    0.5
+}
+
+fn default_match_min_confidence() -> f32 {
+    suggestions::DEFAULT_MATCH_MIN_CONFIDENCE
+}
+
+fn default_suggestion_min_confidence() -> f32 {
+    suggestions::DEFAULT_SUGGESTION_MIN_CONFIDENCE
+}
+
+fn default_silence_threshold() -> f32 {
+    0.01
+}
+
+fn default_silence_duration_ms() -> u64 {
+    1500
+}
+
+fn default_wake_window_ms() -> u64 {
+    2000
+}
+
+fn default_wake_interval_ms() -> u64 {
+    1000
+}
+
+fn default_max_recording_secs() -> u64 {
+    300
+}
+
+fn default_combo_window_ms() -> u64 {
+    150
+}
+
+fn default_chain_words() -> Vec<String> {
+    vec!["and".to_string(), "then".to_string()]
+}
+
+fn default_max_chained_commands() -> usize {
+    3
+}
+
+fn default_cancel_key() -> String {
+    "Escape".to_string()
+}
+
+fn default_repeat_phrase() -> String {
+    "repeat that".to_string()
+}
+
+fn default_arg_trim_words() -> Vec<String> {
+    ["please", "thanks", "thank you"].iter().map(|w| w.to_string()).collect()
+}
+
+impl Settings {
+    fn previewing(&self) -> bool {
+        self.preview_mode || self.dry_run
+    }
+    
+    fn cancel_keys(&self) -> Result<Option<Vec<Keycode>>, String> {
+        if self.cancel_key.trim().is_empty() {
+            return Ok(None);
+        }
+        parse_keybind(&self.cancel_key).map(Some).map_err(|e| format!("cancel_key: {}", e))
+    }
+
+    // `keybinds` wins; without it the single `keybind` (default F8) records commands
+    fn keybinds(&self) -> Vec<Keybind> {
+        if !self.keybinds.is_empty() {
+            return self.keybinds.clone();
+        }
+        vec![Keybind {
+            keys: self.keybind.clone().unwrap_or_else(|| DEFAULT_KEYBIND.to_string()),
+            action: KeybindAction::Command,
+        }]
+    }
+    
+    fn command_guard(&self) -> Result<CommandGuard, AssistantError> {
+        CommandGuard::new(&self.denylist, &self.allowlist, self.allowlist_only)
+            .map_err(|e| AssistantError::InvalidSettings(format!("denylist/allowlist: {}", e)))
+    }
+    
+    fn transcribe_options(&self) -> TranscribeOptions {
+        let mut decoding = self.decoding_overrides.apply(self.decoding_profile.params());
+        if let Some(sampling) = &self.sampling {
+            decoding = sampling.apply(decoding);
+        }
+        TranscribeOptions {
+            language: self.language.clone(),
+            default_language: self.default_language.clone(),
+            min_language_confidence: self.min_language_confidence,
+            decoding,
+            task: self.task,
+            initial_prompt: match &self.initial_prompt {
+                Some(prompt) => Some(prompt.clone()).filter(|prompt| !prompt.trim().is_empty()),
+                None => decoding.prompt_with_shortcuts.then(|| shortcut_prompt(&self.shortcuts, &self.vocabulary)),
+            },
+        }
+    }
+    
+    // Catch config mistakes at startup instead of when the shortcut is spoken
+    fn validate(&self) -> Result<(), AssistantError> {
+        let bindings = parse_keybinds(&self.keybinds()).map_err(AssistantError::InvalidSettings)?;
+        if let Some(cancel) = self.cancel_keys().map_err(AssistantError::InvalidSettings)? {
+            let same_keys = |keys: &Vec<Keycode>| keys.len() == cancel.len() && cancel.iter().all(|key| keys.contains(key));
+            if bindings.iter().any(|(keys, _)| same_keys(keys)) {
+                return Err(AssistantError::InvalidSettings(format!("cancel_key '{}' is also a keybind", self.cancel_key)));
+            }
+        }
+        self.command_guard()?;
+        if self.wake_word.as_deref().is_some_and(|word| builtins::normalize(word).is_empty()) {
+            return Err(AssistantError::InvalidSettings("wake_word is empty".to_string()));
+        }
+        if self.wake_word.is_some() && (self.wake_window_ms == 0 || self.wake_interval_ms == 0) {
+            return Err(AssistantError::InvalidSettings("wake_window_ms and wake_interval_ms must be above 0".to_string()));
+        }
+        for (name, value) in [
+            ("match_min_confidence", self.match_min_confidence),
+            ("suggestion_min_confidence", self.suggestion_min_confidence),
+            ("min_language_confidence", self.min_language_confidence),
+        ] {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(AssistantError::InvalidSettings(format!("{} must be between 0.0 and 1.0, not {}", name, value)));
+            }
+        }
+        if self.language != "auto" {
+            transcribe::check_language(&self.language).map_err(|e| AssistantError::InvalidSettings(format!("language: {}", e)))?;
+        }
+        transcribe::check_language(&self.default_language)
+            .map_err(|e| AssistantError::InvalidSettings(format!("default_language: {}", e)))?;
+        
+        for (phrase, shortcut) in &self.shortcuts {
+            if let Some(keys) = &shortcut.keys {
+                keystrokes::parse_key_sequence(keys).map_err(|e| {
+                    AssistantError::InvalidSettings(format!("shortcut '{}' has invalid keys: {}", phrase, e))
+                })?;
+                continue;
+            }
+            
+            let empty = if shortcut.steps.is_empty() {
+                shortcut.command.trim().is_empty()
+            } else {
+                shortcut.steps.iter().any(|step| step.command.trim().is_empty())
+            };
+            if empty {
+                return Err(AssistantError::InvalidSettings(format!(
+                    "shortcut '{}' has an empty command",
+                    phrase
+                )));
+            }
+        }
+        Ok(())
     }
 }
 
@@ -55,77 +671,318 @@ impl Default for Settings {
         // Cross-platform default commands
         #[cfg(target_os = "macos")]
         {
-            shortcuts.insert("open terminal".to_string(), "open -a Terminal".to_string());
-            shortcuts.insert("take screenshot".to_string(), "screencapture -i screenshot.png".to_string());
-            shortcuts.insert("open browser".to_string(), "open -a Safari".to_string());
-            shortcuts.insert("open finder".to_string(), "open .".to_string());
+            shortcuts.insert("open terminal".to_string(), "open -a Terminal".into());
+            shortcuts.insert("take screenshot".to_string(), "screencapture -i screenshot.png".into());
+            shortcuts.insert("open browser".to_string(), "open -a Safari".into());
+            shortcuts.insert("open finder".to_string(), "open .".into());
         }
         
         #[cfg(target_os = "linux")]
         {
-            shortcuts.insert("open terminal".to_string(), "gnome-terminal || konsole || xterm".to_string());
-            shortcuts.insert("take screenshot".to_string(), "gnome-screenshot || spectacle || scrot".to_string());
-            shortcuts.insert("open browser".to_string(), "xdg-open https://google.com".to_string());
-            shortcuts.insert("open file manager".to_string(), "xdg-open .".to_string());
+            shortcuts.insert("open terminal".to_string(), "gnome-terminal || konsole || xterm".into());
+            shortcuts.insert("take screenshot".to_string(), "gnome-screenshot || spectacle || scrot".into());
+            shortcuts.insert("open browser".to_string(), "xdg-open https://google.com".into());
+            shortcuts.insert("open file manager".to_string(), "xdg-open .".into());
         }
         
         #[cfg(target_os = "windows")]
         {
-            shortcuts.insert("open terminal".to_string(), "cmd".to_string());
-            shortcuts.insert("take screenshot".to_string(), "snippingtool".to_string());
-            shortcuts.insert("open browser".to_string(), "start https://google.com".to_string());
-            shortcuts.insert("open explorer".to_string(), "explorer .".to_string());
+            shortcuts.insert("open terminal".to_string(), "cmd".into());
+            shortcuts.insert("take screenshot".to_string(), "snippingtool".into());
+            shortcuts.insert("open browser".to_string(), "start https://google.com".into());
+            shortcuts.insert("open explorer".to_string(), "explorer .".into());
         }
         
         Settings {
-            keybind: "F8".to_string(),
+            keybind: Some(DEFAULT_KEYBIND.to_string()),
+            keybinds: Vec::new(),
             whisper_model_path: "./ggml-base.en.bin".to_string(),
             shortcuts,
+            shortcuts_file: None,
+            own_shortcuts: HashMap::new(),
+            included_toggles: HashMap::new(),
+            regex_shortcuts: RegexShortcuts::default(),
             voice_feedback: default_voice_feedback(),
+            notifications: NotificationSettings::default(),
+            audio_cues: false,
+            http_server: HttpServerSettings::default(),
+            skip_memory_check: false,
+            skip_model_check: false,
+            use_gpu: default_use_gpu(),
+            startup_accuracy_check: None,
+            archive_history_on_clear: false,
+            history_format: HistoryFormat::default(),
+            persist_runtime_state: false,
+            language: default_language(),
+            default_language: default_language(),
+            min_language_confidence: default_min_language_confidence(),
+            nbest: NBestSettings::default(),
+            match_min_confidence: default_match_min_confidence(),
+            suggestion_min_confidence: default_suggestion_min_confidence(),
+            circuit_breaker: CircuitBreakerSettings::default(),
+            dictation: false,
+            copy_to_clipboard: false,
+            auto_stop_on_silence: false,
+            silence_threshold: default_silence_threshold(),
+            silence_duration_ms: default_silence_duration_ms(),
+            normalize_audio: false,
+            trim_silence: false,
+            max_recording_secs: default_max_recording_secs(),
+            wake_word: None,
+            wake_window_ms: default_wake_window_ms(),
+            wake_interval_ms: default_wake_interval_ms(),
+            input_device: None,
+            caption_fifo: None,
+            temp_dir: None,
+            streaming_preview: false,
+            decoding_profile: DecodingProfile::default(),
+            decoding_overrides: DecodingOverrides::default(),
+            vocabulary: Vec::new(),
+            initial_prompt: None,
+            sampling: None,
+            homophones: Vec::new(),
+            confirmation_fuzzy_threshold: None,
+            recording_mode: RecordingMode::default(),
+            task: Task::default(),
+            combo_window_ms: default_combo_window_ms(),
+            post_command_settle_ms: 0,
+            preview_mode: false,
+            dry_run: false,
+            app_launch_fallback: false,
+            repeat_phrase: default_repeat_phrase(),
+            cooldown_ms: 0,
+            cancel_key: default_cancel_key(),
+            allow_command_chaining: false,
+            chain_words: default_chain_words(),
+            max_chained_commands: default_max_chained_commands(),
+            numeric_command_template: None,
+            max_words_for_command_match: None,
+            assistant_name: None,
+            require_assistant_name: false,
+            arg_trim_words: default_arg_trim_words(),
+            denylist: Vec::new(),
+            allowlist: Vec::new(),
+            allowlist_only: false,
+        }
+    }
+}
+
+// Toggle: press to start, press again to stop. Hold: push-to-talk, stops on release.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, JsonSchema)]
+enum RecordingMode {
+    #[default]
+    Toggle,
+    Hold,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct Keybind {
+    keys: String,
+    action: KeybindAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+enum KeybindAction {
+    // Record a command, matched against the shortcuts
+    Command,
+    // Record text to type into the focused window
+    Dictation,
+    ShowStats,
+    Reload,
+}
+
+impl KeybindAction {
+    fn describe(self) -> &'static str {
+        match self {
+            KeybindAction::Command => "start/stop recording a command",
+            KeybindAction::Dictation => "start/stop dictating",
+            KeybindAction::ShowStats => "show command statistics",
+            KeybindAction::Reload => "reload the settings",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HotkeyAction {
+    ToggleRecording { dictation: bool },
+    ShowStatistics,
+    ShowSuggestions,
+    Reload,
+}
+
+impl From<KeybindAction> for HotkeyAction {
+    fn from(action: KeybindAction) -> Self {
+        match action {
+            KeybindAction::Command => HotkeyAction::ToggleRecording { dictation: false },
+            KeybindAction::Dictation => HotkeyAction::ToggleRecording { dictation: true },
+            KeybindAction::ShowStats => HotkeyAction::ShowStatistics,
+            KeybindAction::Reload => HotkeyAction::Reload,
         }
     }
 }
 
+// A finished recording along with the format it was captured in
+#[derive(Debug, Clone, PartialEq)]
+struct Recording {
+    samples: Vec<f32>,
+    sample_rate: u32,
+    channels: u16,
+}
+
+impl Recording {
+    fn duration_ms(&self) -> u64 {
+        let frames = self.samples.len() as u64 / self.channels.max(1) as u64;
+        frames * 1000 / self.sample_rate.max(1) as u64
+    }
+}
+
 #[derive(Clone)]
 struct AudioRecorder {
+    // Part of the input device name; None records from the system default
+    device_name: Option<String>,
+    // RMS threshold and how long it must stay below it to stop by itself
+    auto_stop: Option<(f32, Duration)>,
+    silence: Arc<Mutex<Option<audio::SilenceDetector>>>,
+    // RMS threshold for cutting quiet ends off finished recordings
+    trim_threshold: Option<f32>,
+    // Recordings left running are cut off here instead of growing without bound
+    max_duration: Option<Duration>,
+    limit_reached: Arc<AtomicBool>,
     samples: Arc<Mutex<Vec<f32>>>,
-    recording: Arc<Mutex<bool>>,
+    // Read by the audio callback on every buffer, so it must never wait on a lock
+    recording: Arc<AtomicBool>,
+    format: Arc<Mutex<(u32, u16)>>,
+    started_at: Arc<Mutex<Option<Instant>>>,
 }
 
 impl AudioRecorder {
-    fn new() -> Self {
+    fn new(device_name: Option<String>) -> Self {
         AudioRecorder {
+            device_name,
+            auto_stop: None,
+            silence: Arc::new(Mutex::new(None)),
+            trim_threshold: None,
+            max_duration: None,
+            limit_reached: Arc::new(AtomicBool::new(false)),
             samples: Arc::new(Mutex::new(Vec::new())),
-            recording: Arc::new(Mutex::new(false)),
+            recording: Arc::new(AtomicBool::new(false)),
+            format: Arc::new(Mutex::new((16000, 1))),
+            started_at: Arc::new(Mutex::new(None)),
         }
     }
 
-    fn start_recording(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let host = cpal::default_host();
-        let device = host.default_input_device()
-            .ok_or("No input device available")?;
+    fn with_auto_stop(mut self, threshold: f32, duration: Duration) -> Self {
+        self.auto_stop = Some((threshold, duration));
+        self
+    }
+    
+    fn with_trim_silence(mut self, threshold: f32) -> Self {
+        self.trim_threshold = Some(threshold);
+        self
+    }
+    
+    fn with_max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration);
+        self
+    }
+    
+    // Set by the capture callback once the speaker has gone quiet
+    fn silence_detected(&self) -> bool {
+        self.silence.lock().unwrap().as_ref().is_some_and(|detector| detector.is_triggered())
+    }
+    
+    fn limit_reached(&self) -> bool {
+        self.limit_reached.load(Ordering::Acquire)
+    }
+    
+    fn is_recording(&self) -> bool {
+        self.recording.load(Ordering::Acquire)
+    }
+    
+    // Whether the recording ended by itself and should be handed over
+    fn should_stop(&self) -> bool {
+        self.silence_detected() || self.limit_reached()
+    }
+    
+    // Called from the capture loop rather than the audio callback, to keep that cheap
+    fn check_limit(&self) -> bool {
+        let Some(max_duration) = self.max_duration else {
+            return false;
+        };
+        let (sample_rate, channels) = *self.format.lock().unwrap();
+        let limit = (max_duration.as_secs_f64() * sample_rate as f64) as usize * channels as usize;
+        
+        let mut samples = self.samples.lock().unwrap();
+        if samples.len() < limit {
+            return false;
+        }
+        self.recording.store(false, Ordering::Release);
+        samples.truncate(limit);
+        self.limit_reached.store(true, Ordering::Release);
+        true
+    }
+    
+    // Resets the recorder and marks it as recording before the capture thread exists, so a
+    // stop that arrives before the thread gets going still ends it
+    fn arm(&self) {
+        self.samples.lock().unwrap().clear();
+        *self.silence.lock().unwrap() = None;
+        *self.started_at.lock().unwrap() = Some(Instant::now());
+        self.limit_reached.store(false, Ordering::Release);
+        self.recording.store(true, Ordering::Release);
+    }
+    
+    fn spawn_capture(&self) -> thread::JoinHandle<()> {
+        self.arm();
+        let recorder = self.clone();
+        thread::spawn(move || {
+            if let Err(e) = recorder.capture() {
+                recorder.recording.store(false, Ordering::Release);
+                error!("Recording error: {}", e);
+            }
+        })
+    }
+    
+    // Called from the audio callback with every buffer, converted to f32
+    fn push_samples(&self, data: &[f32]) {
+        if !self.is_recording() {
+            return;
+        }
+        self.samples.lock().unwrap().extend_from_slice(data);
+        if let Some(detector) = self.silence.lock().unwrap().as_mut() {
+            detector.feed(data);
+        }
+    }
+    
+    // Keeps the stream alive until stopped or cut off at the limit
+    fn hold_open(&self) {
+        while self.is_recording() && !self.check_limit() {
+            thread::sleep(CAPTURE_POLL);
+        }
+    }
+    
+    fn capture(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let device = devices::select_input_device(self.device_name.as_deref())?;
         
         let config = device.default_input_config()?;
         let sample_rate = config.sample_rate().0;
         
-        println!("Recording started... (sample rate: {} Hz)", sample_rate);
+        debug!("Recording started... (sample rate: {} Hz)", sample_rate);
         
-        *self.recording.lock().unwrap() = true;
-        self.samples.lock().unwrap().clear();
+        *self.format.lock().unwrap() = (sample_rate, config.channels());
+        *self.silence.lock().unwrap() = self.auto_stop.map(|(threshold, duration)| {
+            audio::SilenceDetector::new(threshold, duration, sample_rate * config.channels() as u32)
+        });
         
-        let samples_clone = Arc::clone(&self.samples);
-        let recording_clone = Arc::clone(&self.recording);
+        let recorder = self.clone();
+        let capture = move |data: &[f32]| recorder.push_samples(data);
         
         let stream = match config.sample_format() {
             cpal::SampleFormat::F32 => {
                 device.build_input_stream(
                     &config.into(),
-                    move |data: &[f32], _: &_| {
-                        if *recording_clone.lock().unwrap() {
-                            samples_clone.lock().unwrap().extend_from_slice(data);
-                        }
-                    },
-                    |err| eprintln!("Stream error: {}", err),
+                    move |data: &[f32], _: &_| capture(data),
+                    |err| error!("Stream error: {}", err),
                     None
                 )?
             }
@@ -133,14 +990,12 @@ impl AudioRecorder {
                 device.build_input_stream(
                     &config.into(),
                     move |data: &[i16], _: &_| {
-                        if *recording_clone.lock().unwrap() {
-                            let float_data: Vec<f32> = data.iter()
-                                .map(|&s| s as f32 / i16::MAX as f32)
-                                .collect();
-                            samples_clone.lock().unwrap().extend_from_slice(&float_data);
-                        }
+                        let float_data: Vec<f32> = data.iter()
+                            .map(|&s| s as f32 / i16::MAX as f32)
+                            .collect();
+                        capture(&float_data);
                     },
-                    |err| eprintln!("Stream error: {}", err),
+                    |err| error!("Stream error: {}", err),
                     None
                 )?
             }
@@ -148,14 +1003,12 @@ impl AudioRecorder {
                 device.build_input_stream(
                     &config.into(),
                     move |data: &[u16], _: &_| {
-                        if *recording_clone.lock().unwrap() {
-                            let float_data: Vec<f32> = data.iter()
-                                .map(|&s| (s as f32 - u16::MAX as f32 / 2.0) / (u16::MAX as f32 / 2.0))
-                                .collect();
-                            samples_clone.lock().unwrap().extend_from_slice(&float_data);
-                        }
+                        let float_data: Vec<f32> = data.iter()
+                            .map(|&s| (s as f32 - u16::MAX as f32 / 2.0) / (u16::MAX as f32 / 2.0))
+                            .collect();
+                        capture(&float_data);
                     },
-                    |err| eprintln!("Stream error: {}", err),
+                    |err| error!("Stream error: {}", err),
                     None
                 )?
             }
@@ -163,118 +1016,361 @@ impl AudioRecorder {
         };
         
         stream.play()?;
-        
-        // Keep stream alive while recording
-        while *self.recording.lock().unwrap() {
-            thread::sleep(Duration::from_millis(100));
-        }
-        
+        self.hold_open();
         Ok(())
     }
 
-    fn stop_recording(&self) -> Vec<f32> {
-        *self.recording.lock().unwrap() = false;
-        println!("Recording stopped.");
-        self.samples.lock().unwrap().clone()
+    // What has been captured so far, without stopping; the wake listener reads it in chunks
+    fn take_recording(&self) -> Recording {
+        let (sample_rate, channels) = *self.format.lock().unwrap();
+        let samples = std::mem::take(&mut *self.samples.lock().unwrap());
+        Recording {
+            samples: audio::downmix(&samples, channels as usize),
+            sample_rate,
+            channels: 1,
+        }
     }
-
-    fn save_wav(&self, samples: &[f32], path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let spec = WavSpec {
+    
+    // Copies the mono frames from `offset` on, leaving the recording running
+    fn peek_since(&self, offset: usize) -> Recording {
+        let (sample_rate, channels) = *self.format.lock().unwrap();
+        let channels = channels.max(1) as usize;
+        let samples = self.samples.lock().unwrap();
+        // Whole frames only, in case the callback is part way through one
+        let end = samples.len() - samples.len() % channels;
+        let new = samples.get(offset * channels..end).unwrap_or_default();
+        Recording {
+            samples: audio::downmix(new, channels),
+            sample_rate,
+            channels: 1,
+        }
+    }
+    
+    fn stop_recording(&self) -> Recording {
+        self.recording.store(false, Ordering::Release);
+        let (sample_rate, channels) = *self.format.lock().unwrap();
+        // Taken rather than copied, so the callback only ever waits for a pointer swap
+        let samples = std::mem::take(&mut *self.samples.lock().unwrap());
+        // Stereo devices deliver interleaved frames; the WAV for Whisper is mono
+        let mut samples = audio::downmix(&samples, channels as usize);
+        if let Some(threshold) = self.trim_threshold {
+            let before = samples.len();
+            samples = audio::trim_silence(&samples, threshold, sample_rate, TRIM_PADDING).to_vec();
+            debug!("Trimmed {} ms of silence", (before - samples.len()) as u64 * 1000 / sample_rate.max(1) as u64);
+        }
+        let recording = Recording {
+            samples,
+            sample_rate,
             channels: 1,
-            sample_rate: 16000,
-            bits_per_sample: 32,
-            sample_format: hound::SampleFormat::Float,
         };
         
-        let mut writer = WavWriter::create(path, spec)?;
-        for &sample in samples {
-            writer.write_sample(sample)?;
+        // The sample count is what Whisper gets; the wall clock also covers stream startup
+        let elapsed = self.started_at.lock().unwrap().take().map(|t| t.elapsed().as_millis());
+        match elapsed {
+            Some(elapsed) => info!("Recording stopped. ({} ms of audio, {} ms held)", recording.duration_ms(), elapsed),
+            None => info!("Recording stopped. ({} ms of audio)", recording.duration_ms()),
         }
-        writer.finalize()?;
-        
-        Ok(())
+        recording
     }
 }
 
-fn transcribe_audio(whisper_path: &str, audio_path: &str) -> Result<String, Box<dyn std::error::Error>> {
-    // Load whisper model with parameters
-    let ctx = WhisperContext::new_with_params(whisper_path, whisper_rs::WhisperContextParameters::default())?;
-    
-    // Create parameters
-    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
-    params.set_print_special(false);
-    params.set_print_progress(false);
-    params.set_print_timestamps(false);
-    params.set_language(Some("en"));
+// Devices capture at their native rate (often 44.1 or 48 kHz); Whisper wants 16 kHz
+fn resample_to_16k(samples: &[f32], sample_rate: u32) -> Vec<f32> {
+    audio::resample(samples, sample_rate, audio::WHISPER_SAMPLE_RATE)
+}
+
+fn execute_command(command: &str, guard: &CommandGuard) -> Result<(), Box<dyn std::error::Error>> {
+    // `sh -c ""` succeeds silently, which would hide a broken shortcut
+    if command.trim().is_empty() {
+        return Err("Refusing to run an empty command".into());
+    }
+    check_guard(command, guard)?;
     
-    // Load and process audio
-    let mut reader = hound::WavReader::open(audio_path)?;
-    let samples: Vec<f32> = reader.samples::<f32>()
-        .map(|s| s.unwrap())
-        .collect();
+    debug!("Executing command: {}", command);
+    shell_command(command).spawn()?;
+    Ok(())
+}
+
+fn check_guard(command: &str, guard: &CommandGuard) -> Result<(), AssistantError> {
+    guard.check(command).map_err(|reason| AssistantError::CommandBlocked { command: command.to_string(), reason })
+}
+
+fn shell_command(command: &str) -> Command {
+    #[cfg(target_os = "windows")]
+    {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    }
     
-    // Create a state for processing
-    let mut state = ctx.create_state()?;
+    #[cfg(not(target_os = "windows"))]
+    {
+        let mut shell = Command::new("sh");
+        shell.arg("-c").arg(command);
+        shell
+    }
+}
+
+// Runs the command to completion and returns its stdout. A nonzero exit is an
+// AssistantError::CommandFailed carrying stderr (or stdout if stderr was empty).
+fn run_captured(command: &str, guard: &CommandGuard) -> Result<String, Box<dyn std::error::Error>> {
+    if command.trim().is_empty() {
+        return Err("Refusing to run an empty command".into());
+    }
+    check_guard(command, guard)?;
     
-    // Run whisper
-    state.full(params, &samples)?;
+    debug!("Executing command and waiting for it: {}", command);
+    let output = shell_command(command).output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    if !output.status.success() {
+        return Err(AssistantError::CommandFailed {
+            command: command.to_string(),
+            status: output.status.code(),
+            output: if stderr.is_empty() { stdout } else { stderr },
+        }
+        .into());
+    }
     
-    // Get transcription
-    let num_segments = state.full_n_segments()?;
-    let mut transcription = String::new();
+    if !stdout.is_empty() {
+        info!("📤 {}", stdout);
+    }
+    if !stderr.is_empty() {
+        warn!("{}", stderr);
+    }
+    Ok(stdout)
+}
+
+fn run_shortcut(shortcut: &Shortcut, voice: &VoiceFeedback, guard: &CommandGuard) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(keys) = &shortcut.keys {
+        return keystrokes::send_keys(keys);
+    }
+    if shortcut.waits() && !shortcut.is_macro() {
+        let output = run_captured(&shortcut.command, guard)?;
+        if shortcut.speak_output && !output.is_empty() {
+            voice.speak(&truncate_for_speech(&output, MAX_SPOKEN_OUTPUT_CHARS));
+        }
+        return Ok(());
+    }
+    if !shortcut.is_macro() {
+        return execute_command(&shortcut.command, guard);
+    }
     
-    for i in 0..num_segments {
-        let segment = state.full_get_segment_text(i)?;
-        transcription.push_str(&segment);
-        transcription.push(' ');
+    // Refuse the whole macro up front rather than part way through
+    let steps = shortcut.steps();
+    for step in &steps {
+        check_guard(&step.command, guard)?;
     }
     
-    Ok(transcription.trim().to_string())
+    // Runs on the transcription worker, so sleeping between steps doesn't hold up the
+    // hotkey, and a failed step is reported like any other failed command
+    shortcuts::run_macro(&steps, |command| execute_command(command, guard))
 }
 
-fn execute_command(command: &str) -> Result<(), Box<dyn std::error::Error>> {
-    println!("Executing command: {}", command);
-    
-    #[cfg(target_os = "windows")]
-    {
-        Command::new("cmd")
-            .args(&["/C", command])
-            .spawn()?;
+// Preview mode never runs anything, so there is nothing to confirm
+fn needs_confirmation(shortcut: &Shortcut, settings: &Settings) -> bool {
+    shortcut.confirm && !settings.previewing()
+}
+
+fn ask_confirmation(phrase: &str, shortcut: &Shortcut, voice: &VoiceFeedback) -> PendingConfirmation {
+    let prompt = format!("Run {}? Say yes to confirm", shortcut.display_command());
+    info!("❓ {}", prompt);
+    voice.speak(&prompt);
+    PendingConfirmation::Shortcut { phrase: phrase.to_string(), shortcut: shortcut.clone() }
+}
+
+fn run_confirmed_shortcut(phrase: &str, shortcut: &Shortcut, settings: &Settings, voice: &VoiceFeedback, guard: &CommandGuard, cooldowns: &mut Cooldowns) {
+    let run = || run_shortcut(shortcut, voice, guard);
+    if dispatch(phrase, &shortcut.display_command(), settings, voice, run) == CommandOutcome::Executed {
+        info!("✓ Executed: {}", phrase);
+        cooldowns.record(phrase, Instant::now());
+        if settings.voice_feedback.confirm_commands {
+            voice.speak(&format!("Executed {}", phrase));
+        }
+        show_command_feedback(&settings.notifications, phrase, shortcut);
+    }
+}
+
+// Runs a matched command and reports failures, including commands the guard refused.
+// In preview mode it only says what would have run, so a new configuration can be tried
+// out safely.
+fn dispatch<F>(phrase: &str, command: &str, settings: &Settings, voice: &VoiceFeedback, run: F) -> CommandOutcome
+where
+    F: FnOnce() -> Result<(), Box<dyn std::error::Error>>,
+{
+    if settings.previewing() {
+        info!("👀 Preview: would run: {} for {}", command, phrase);
+        if settings.voice_feedback.confirm_commands {
+            voice.speak(&format!("Would run {}", phrase));
+        }
+        return CommandOutcome::Suppressed;
     }
     
-    #[cfg(not(target_os = "windows"))]
-    {
-        Command::new("sh")
-            .arg("-c")
-            .arg(command)
-            .spawn()?;
+    match run() {
+        Ok(()) => CommandOutcome::Executed,
+        Err(e) if matches!(e.downcast_ref(), Some(AssistantError::CommandBlocked { .. })) => {
+            warn!("🚫 {}", e);
+            if settings.voice_feedback.announce_errors {
+                voice.speak("Command blocked");
+            }
+            CommandOutcome::Blocked
+        }
+        Err(e) => {
+            error!("Failed to execute command: {}", e);
+            if settings.voice_feedback.announce_errors {
+                match e.downcast_ref::<AssistantError>() {
+                    Some(AssistantError::CommandFailed { output, .. }) if !output.is_empty() => {
+                        voice.speak(&format!("{} failed: {}", phrase, truncate_for_speech(output, MAX_SPOKEN_OUTPUT_CHARS)));
+                    }
+                    _ => voice.speak(&format!("Failed to execute {}", phrase)),
+                }
+            }
+            CommandOutcome::Failed
+        }
+    }
+}
+
+// Desktop notification and sound cue for a command that just ran
+fn show_command_feedback(settings: &NotificationSettings, phrase: &str, shortcut: &Shortcut) {
+    if settings.enabled {
+        let notification = notifications::notification_for(phrase, shortcut, settings.icon.as_deref());
+        if let Err(e) = notifications::show(&notification) {
+            warn!("Failed to show notification: {}", e);
+        }
     }
     
-    Ok(())
+    if settings.sound_cues {
+        if let Some(cue) = notifications::cue_for(shortcut, settings.cue.as_deref()) {
+            if let Err(e) = notifications::play_cue(cue) {
+                warn!("Failed to play sound cue: {}", e);
+            }
+        }
+    }
 }
 
 fn load_settings() -> Result<Settings, Box<dyn std::error::Error>> {
-    let settings_path = "voice_assistant_settings.json";
+    let settings_path = SETTINGS_PATH;
     
     if !Path::new(settings_path).exists() {
         let default_settings = Settings::default();
         let json = serde_json::to_string_pretty(&default_settings)?;
         fs::write(settings_path, json)?;
-        println!("Created default settings file: {}", settings_path);
+        info!("Created default settings file: {}", settings_path);
         return Ok(default_settings);
     }
     
     let contents = fs::read_to_string(settings_path)?;
-    let settings: Settings = serde_json::from_str(&contents)?;
+    let settings = parse_settings(&contents)?;
+    settings.validate()?;
     Ok(settings)
 }
 
-fn string_to_keycode(key: &str) -> Option<Keycode> {
-    match key.to_uppercase().as_str() {
-        "F1" => Some(Keycode::F1),
-        "F2" => Some(Keycode::F2),
-        "F3" => Some(Keycode::F3),
-        "F4" => Some(Keycode::F4),
+fn parse_settings(contents: &str) -> Result<Settings, Box<dyn std::error::Error>> {
+    let mut settings: Settings = serde_json::from_str(contents)?;
+    if let Some(files) = &settings.shortcuts_file {
+        settings.own_shortcuts = settings.shortcuts.clone();
+        for path in files.paths() {
+            let included = includes::load(path).map_err(AssistantError::InvalidSettings)?;
+            includes::merge(&mut settings.shortcuts, included, path);
+        }
+    }
+    settings.regex_shortcuts = RegexShortcuts::compile(&settings.shortcuts).map_err(AssistantError::InvalidSettings)?;
+    Ok(settings)
+}
+
+// Only the shortcuts, keybind and voice feedback settings apply on reload (the last through
+// apply_voice_settings); the model, audio device and the rest are set up once at startup
+fn apply_reloaded_settings(current: &Settings, reloaded: Settings) -> Settings {
+    let (keybind, keybinds) = match parse_keybinds(&reloaded.keybinds()) {
+        Ok(_) => (reloaded.keybind, reloaded.keybinds),
+        Err(e) => {
+            warn!("Keeping keybinds {}: {}", describe_keybinds(&current.keybinds()), e);
+            (current.keybind.clone(), current.keybinds.clone())
+        }
+    };
+    let mut shortcuts = reloaded.shortcuts;
+    for (phrase, enabled) in &current.included_toggles {
+        // A shortcut the settings file now defines itself is left as written
+        if reloaded.own_shortcuts.contains_key(phrase) {
+            continue;
+        }
+        if let Some(shortcut) = shortcuts.get_mut(phrase) {
+            shortcut.enabled = *enabled;
+        }
+    }
+    Settings {
+        keybind,
+        keybinds,
+        shortcuts,
+        shortcuts_file: reloaded.shortcuts_file,
+        own_shortcuts: reloaded.own_shortcuts,
+        regex_shortcuts: reloaded.regex_shortcuts,
+        voice_feedback: reloaded.voice_feedback,
+        ..current.clone()
+    }
+}
+
+// Hands reloaded voice feedback settings to the running VoiceFeedback. The TTS engine is
+// only chosen at startup, and dropping voice_name can't bring back the default voice.
+fn apply_voice_settings(voice: &VoiceFeedback, current: &VoiceFeedbackSettings, reloaded: &VoiceFeedbackSettings) {
+    if reloaded.tts_engine != current.tts_engine && current.enabled {
+        warn!("tts_engine changes apply after a restart.");
+    }
+    if reloaded.voice_name.is_none() && current.voice_name.is_some() {
+        warn!("Restart to go back to the default voice.");
+    }
+    voice.set_enabled(reloaded.enabled, reloaded.tts_engine.as_deref());
+    if reloaded.enabled {
+        voice.set_voice(reloaded.voice_name.as_deref());
+        voice.set_prosody(&reloaded.prosody());
+    }
+    voice.set_quiet_hours(reloaded.quiet_hours);
+    voice.set_natural_readback(reloaded.natural_readback);
+}
+
+// Edits to an included file reload the settings just like edits to the settings file
+fn watch_shortcut_files(watcher: &mut SettingsWatcher, settings: &Settings) {
+    for path in settings.shortcuts_file.iter().flat_map(ShortcutFiles::paths) {
+        if let Err(e) = watcher.watch_file(path) {
+            warn!("Not watching {} for changes, restart to apply edits: {}", path, e);
+        }
+    }
+}
+
+fn reload_settings(current: &Settings) -> Result<Settings, Box<dyn std::error::Error>> {
+    let contents = fs::read_to_string(SETTINGS_PATH)?;
+    let reloaded = parse_settings(&contents)?;
+    let settings = apply_reloaded_settings(current, reloaded);
+    settings.validate()?;
+    Ok(settings)
+}
+
+// Saving a shortcut toggle rewrites the file too, which shouldn't count as an edit
+fn same_settings(a: &Settings, b: &Settings) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+fn save_settings(settings: &Settings) -> Result<(), Box<dyn std::error::Error>> {
+    fs::write(SETTINGS_PATH, settings_json(settings)?)?;
+    Ok(())
+}
+
+// What save_settings writes: session-only state such as --dry-run is left out
+fn settings_json(settings: &Settings) -> serde_json::Result<String> {
+    if settings.shortcuts_file.is_some() {
+        // Included shortcuts stay in their own files
+        serde_json::to_string_pretty(&Settings { shortcuts: settings.own_shortcuts.clone(), ..settings.clone() })
+    } else {
+        serde_json::to_string_pretty(settings)
+    }
+}
+
+fn string_to_keycode(key: &str) -> Option<Keycode> {
+    match key.to_uppercase().as_str() {
+        "F1" => Some(Keycode::F1),
+        "F2" => Some(Keycode::F2),
+        "F3" => Some(Keycode::F3),
+        "F4" => Some(Keycode::F4),
         "F5" => Some(Keycode::F5),
         "F6" => Some(Keycode::F6),
         "F7" => Some(Keycode::F7),
@@ -284,49 +1380,1384 @@ fn string_to_keycode(key: &str) -> Option<Keycode> {
         "F11" => Some(Keycode::F11),
         "F12" => Some(Keycode::F12),
         "SPACE" => Some(Keycode::Space),
-        "LCTRL" => Some(Keycode::LControl),
+        "LCTRL" | "CTRL" => Some(Keycode::LControl),
         "RCTRL" => Some(Keycode::RControl),
-        "LSHIFT" => Some(Keycode::LShift),
+        "LSHIFT" | "SHIFT" => Some(Keycode::LShift),
         "RSHIFT" => Some(Keycode::RShift),
-        "LALT" => Some(Keycode::LAlt),
+        "LALT" | "ALT" => Some(Keycode::LAlt),
         "RALT" => Some(Keycode::RAlt),
+        "ESC" | "ESCAPE" => Some(Keycode::Escape),
         _ => None,
     }
 }
 
+// "F8" or a combo like "LCtrl+LShift+F8"; every key must be held together
+fn parse_keybind(keybind: &str) -> Result<Vec<Keycode>, String> {
+    let mut keys = Vec::new();
+    for token in keybind.split('+').map(str::trim) {
+        let key = string_to_keycode(token).ok_or_else(|| {
+            if token.is_empty() {
+                format!("keybind '{}' has an empty key", keybind)
+            } else {
+                format!("keybind '{}' has unknown key '{}' (use F1-F12, Space, Escape, LCtrl, RCtrl, LShift, RShift, LAlt or RAlt)", keybind, token)
+            }
+        })?;
+        if keys.contains(&key) {
+            return Err(format!("keybind '{}' lists '{}' twice", keybind, token));
+        }
+        keys.push(key);
+    }
+    Ok(keys)
+}
+
+fn parse_keybinds(keybinds: &[Keybind]) -> Result<Vec<(Vec<Keycode>, HotkeyAction)>, String> {
+    let mut parsed: Vec<(Vec<Keycode>, HotkeyAction)> = Vec::new();
+    for keybind in keybinds {
+        let keys = parse_keybind(&keybind.keys)?;
+        let same_keys = |other: &Vec<Keycode>| other.len() == keys.len() && keys.iter().all(|key| other.contains(key));
+        if parsed.iter().any(|(other, _)| same_keys(other)) {
+            return Err(format!("keybind '{}' is bound to more than one action", keybind.keys));
+        }
+        parsed.push((keys, keybind.action.into()));
+    }
+    
+    let records = |(_, action): &(Vec<Keycode>, HotkeyAction)| matches!(action, HotkeyAction::ToggleRecording { .. });
+    if !parsed.iter().any(records) {
+        return Err("no keybind starts recording, bind one to Command or Dictation".to_string());
+    }
+    Ok(parsed)
+}
+
+fn describe_keybinds(keybinds: &[Keybind]) -> String {
+    keybinds
+        .iter()
+        .map(|keybind| format!("{} ({:?})", keybind.keys, keybind.action))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Every key that starts or stops a recording, whichever kind
+fn recording_keys(settings: &Settings) -> Vec<Keycode> {
+    parse_keybinds(&settings.keybinds())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(_, action)| matches!(action, HotkeyAction::ToggleRecording { .. }))
+        .flat_map(|(keys, _)| keys)
+        .collect()
+}
+
+enum PendingConfirmation {
+    Builtin(BuiltinCommand),
+    Shortcut { phrase: String, shortcut: Shortcut },
+}
+
+// Everything the transcription worker needs to turn a recording into an action
+struct Assistant {
+    // Swapped out by the hotkey loop when the settings file changes
+    settings: Arc<RwLock<Arc<Settings>>>,
+    whisper: Arc<WhisperContext>,
+    voice: VoiceFeedback,
+    history: Arc<Mutex<CommandHistory>>,
+    history_path: String,
+    suggestions: SmartSuggestions,
+    // Command waiting for a spoken yes/no
+    pending_confirmation: Option<PendingConfirmation>,
+    runtime: RuntimeState,
+    captions: Option<CaptionSink>,
+    // Shared with the hotkey loop, which stops capturing while it is open
+    breaker: Option<Arc<Mutex<CircuitBreaker>>>,
+    // Built once at startup; the lists aren't reloaded
+    guard: CommandGuard,
+    cooldowns: Cooldowns,
+    // --transcribe-file --match prints what was heard, as --transcribe-file alone does
+    echo_transcription: bool,
+}
+
+impl Assistant {
+    // A snapshot, so one recording is handled with one set of settings throughout
+    fn settings(&self) -> Arc<Settings> {
+        Arc::clone(&self.settings.read().unwrap())
+    }
+}
+
+// Applies a runtime toggle and, if enabled, writes it out for the next launch
+fn update_runtime_state(assistant: &mut Assistant, update: impl FnOnce(&mut RuntimeState)) {
+    update(&mut assistant.runtime);
+    assistant.voice.set_muted(assistant.runtime.muted);
+    if assistant.settings().persist_runtime_state {
+        if let Err(e) = assistant.runtime.save(STATE_PATH) {
+            warn!("Failed to save runtime state: {}", e);
+        }
+    }
+}
+
+fn clear_history(history: &mut CommandHistory, path: &str, archive: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if archive {
+        if let Some(archive_path) = CommandHistory::archive(path)? {
+            info!("Archived previous history to {}", archive_path);
+        }
+    }
+    
+    history.clear();
+    history.save(path)
+}
+
+fn run_builtin(assistant: &mut Assistant, command: BuiltinCommand) {
+    let settings = assistant.settings();
+    let voice = &assistant.voice;
+    
+    match command {
+        BuiltinCommand::ClearHistory => {
+            let mut history = assistant.history.lock().unwrap();
+            match clear_history(&mut history, &assistant.history_path, settings.archive_history_on_clear) {
+                Ok(()) => {
+                    info!("🗑️  Command history cleared.");
+                    if settings.voice_feedback.confirm_commands {
+                        voice.speak("History cleared");
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to clear history: {}", e);
+                    if settings.voice_feedback.announce_errors {
+                        voice.speak("Failed to clear history");
+                    }
+                }
+            }
+        }
+        BuiltinCommand::ReadBackLast => {
+            let message = builtins::readback_message(&assistant.history.lock().unwrap());
+            info!("🔁 {}", message);
+            // Always spoken: this is the whole point of the command
+            voice.speak(&message);
+        }
+        BuiltinCommand::SetShortcutEnabled { phrase, enabled } => {
+            let action = if enabled { "enable" } else { "disable" };
+            let target = settings
+                .shortcuts
+                .keys()
+                .find(|key| builtins::normalize(key) == phrase)
+                .map(|key| key.as_str())
+                .or_else(|| match assistant.suggestions.find_best_match(&phrase, &settings.shortcuts) {
+                    Some((key, score)) if assistant.suggestions.is_confident(score) => Some(key),
+                    _ => None,
+                });
+            
+            let Some(target) = target else {
+                info!("No shortcut named '{}' to {}.", phrase, action);
+                if settings.voice_feedback.announce_errors {
+                    voice.speak(&format!("No shortcut named {}", phrase));
+                }
+                return;
+            };
+            
+            // Copy-on-write so the change is visible to the next recording and saved to disk
+            let mut updated = (*settings).clone();
+            if let Some(shortcut) = updated.shortcuts.get_mut(target) {
+                shortcut.enabled = enabled;
+            }
+            match updated.own_shortcuts.get_mut(target) {
+                Some(shortcut) => shortcut.enabled = enabled,
+                None if updated.shortcuts_file.is_some() => {
+                    info!("'{}' comes from a shortcuts_file, so it stays {}d for this session only.", target, action);
+                    updated.included_toggles.insert(target.to_string(), enabled);
+                }
+                None => {}
+            }
+            // Held across the write so the reload it sets off compares against the new settings
+            let mut live = assistant.settings.write().unwrap();
+            match save_settings(&updated) {
+                Ok(()) => {
+                    *live = Arc::new(updated);
+                    drop(live);
+                    info!("🔧 Shortcut '{}' {}d.", target, action);
+                    if settings.voice_feedback.confirm_commands {
+                        voice.speak(&format!("{} {}d", target, action));
+                    }
+                }
+                Err(e) => {
+                    drop(live);
+                    error!("Failed to save settings, so '{}' is unchanged: {}", target, e);
+                    if settings.voice_feedback.announce_errors {
+                        voice.speak("Failed to save settings");
+                    }
+                }
+            }
+        }
+        BuiltinCommand::SetMuted(muted) => {
+            // Confirm before muting and after unmuting, so there's always one spoken word
+            if muted && settings.voice_feedback.confirm_commands {
+                voice.speak("Muted");
+            }
+            update_runtime_state(assistant, |state| state.muted = muted);
+            info!("{}", if muted { "🔇 Voice feedback muted." } else { "🔊 Voice feedback unmuted." });
+            if !muted && settings.voice_feedback.confirm_commands {
+                assistant.voice.speak("Unmuted");
+            }
+        }
+        BuiltinCommand::SetPaused(paused) => {
+            update_runtime_state(assistant, |state| state.paused = paused);
+            info!("{}", if paused { "⏸️  Paused. Say \"resume listening\" to continue." } else { "▶️  Listening again." });
+            if settings.voice_feedback.confirm_commands {
+                assistant.voice.speak(if paused { "Paused" } else { "Listening" });
+            }
+        }
+        BuiltinCommand::OpenSettings | BuiltinCommand::OpenHistory => {
+            let (name, path) = if command == BuiltinCommand::OpenSettings {
+                ("settings", SETTINGS_PATH)
+            } else {
+                ("history", assistant.history_path.as_str())
+            };
+            match launcher::open_file(path, |path| open::that(path)) {
+                Ok(resolved) => {
+                    info!("📝 Opened {}", resolved.display());
+                    if settings.voice_feedback.confirm_commands {
+                        voice.speak(&format!("Opening {}", name));
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to open {} file: {}", name, e);
+                    if settings.voice_feedback.announce_errors {
+                        voice.speak(&format!("Couldn't open the {} file", name));
+                    }
+                }
+            }
+        }
+    }
+}
+
+// `dictation` is set for recordings made with a Dictation keybind
+fn process_recording(assistant: &mut Assistant, mut recording: Recording, dictation: bool) {
+    let settings = assistant.settings();
+    let dictation = dictation || settings.dictation;
+    let voice = &assistant.voice;
+    let suggestions_engine = &assistant.suggestions;
+    
+    // Recordings queued before the breaker opened are part of the same loop
+    if assistant.breaker.as_ref().is_some_and(|breaker| breaker.lock().unwrap().is_open(Instant::now())) {
+        info!("🔌 Skipping recording while capture is suspended.");
+        return;
+    }
+    
+    debug!("Processing audio...");
+    
+    // Track processing start time
+    let start_time = Instant::now();
+    
+    // Very short recordings are usually a clipped start or an accidental tap
+    let audio_duration_ms = recording.duration_ms();
+    if audio_duration_ms < SHORT_RECORDING_MS {
+        warn!("Very short recording ({} ms), it may have been cut off.", audio_duration_ms);
+        if settings.voice_feedback.announce_short_recordings {
+            voice.speak("That recording was very short");
+        }
+    }
+    
+    if settings.normalize_audio {
+        let gain = audio::normalize_peak(&mut recording.samples, NORMALIZE_TARGET_PEAK);
+        debug!("Normalized recording (gain {:.1}x)", gain);
+    }
+    
+    // Transcribe
+    let samples = resample_to_16k(&recording.samples, recording.sample_rate);
+    let options = settings.transcribe_options();
+    let result = if settings.nbest.enabled {
+        transcribe_nbest(&assistant.whisper, &samples, &options, settings.nbest.count)
+            .map(|hypotheses| pick_hypothesis(hypotheses, suggestions_engine, &settings))
+    } else {
+        transcribe_samples(&assistant.whisper, &samples, &options)
+    };
+    
+    if let (true, Ok(text)) = (assistant.echo_transcription, &result) {
+        println!("{}", text.trim());
+    }
+    
+    match result {
+        // Not worth matching, announcing as a miss or keeping in the history
+        Ok(text) if transcribe::is_blank(&text) => {
+            info!("No speech in the recording (\"{}\"), ignoring.", text.trim());
+            if settings.voice_feedback.announce_no_speech {
+                voice.speak("I didn't hear anything");
+            }
+        }
+        Ok(text) => {
+            info!("Transcription: {}", text);
+            if let Some(captions) = &assistant.captions {
+                captions.write_line(&text);
+            }
+            if settings.copy_to_clipboard && !text.trim().is_empty() {
+                match clipboard::set_clipboard(text.trim()) {
+                    Ok(()) => info!("📋 Copied to clipboard"),
+                    Err(e) => {
+                        error!("Failed to copy to clipboard: {}", e);
+                        if settings.voice_feedback.announce_errors {
+                            voice.speak("Failed to copy to clipboard");
+                        }
+                    }
+                }
+            }
+            
+            // A pending confirmation consumes the next utterance
+            if let Some(pending) = assistant.pending_confirmation.take() {
+                let fuzzy = settings.confirmation_fuzzy_threshold.map(|threshold| (suggestions_engine, threshold));
+                if builtins::classify_response(&text, fuzzy) == Some(true) {
+                    match pending {
+                        PendingConfirmation::Builtin(command) => run_builtin(assistant, command),
+                        PendingConfirmation::Shortcut { phrase, shortcut } => {
+                            run_confirmed_shortcut(&phrase, &shortcut, &settings, &assistant.voice, &assistant.guard, &mut assistant.cooldowns);
+                        }
+                    }
+                } else {
+                    info!("Cancelled.");
+                    voice.clear_queue();
+                    if settings.voice_feedback.confirm_commands {
+                        voice.speak("Cancelled");
+                    }
+                }
+                return;
+            }
+            
+            let Some(text) = addressed_text(&text, &settings) else {
+                info!("Not addressed to {}, ignoring.", settings.assistant_name.as_deref().unwrap_or_default());
+                return;
+            };
+            
+            if let Some(command) = builtins::parse_builtin(&text) {
+                if command.needs_confirmation() {
+                    info!("❓ {}", command.confirmation_prompt());
+                    voice.speak(command.confirmation_prompt());
+                    assistant.pending_confirmation = Some(PendingConfirmation::Builtin(command));
+                } else {
+                    run_builtin(assistant, command);
+                }
+                return;
+            }
+            
+            if assistant.runtime.paused {
+                info!("⏸️  Paused, not running anything. Say \"resume listening\" to continue.");
+                return;
+            }
+            
+            // "open terminal and open browser" runs both
+            let segments = chained_commands(&text, &settings, dictation);
+            let count = segments.len();
+            for (i, segment) in segments.into_iter().enumerate() {
+                handle_command(assistant, &settings, segment, dictation, start_time, audio_duration_ms);
+                // The answer is the next utterance, so the rest of the chain can't go ahead
+                if assistant.pending_confirmation.is_some() && i + 1 < count {
+                    info!("⛓️  Skipping the rest of the chain while waiting for confirmation.");
+                    break;
+                }
+            }
+        }
+        Err(e) => {
+            error!("Transcription error: {}", e);
+            if settings.audio_cues {
+                cues::play(Cue::Error);
+            }
+            // Announce transcription error
+            if settings.voice_feedback.announce_errors {
+                voice.speak("Transcription failed");
+            }
+            
+            // Record failed transcription in history
+            let duration_ms = start_time.elapsed().as_millis() as u64;
+            let entry = CommandEntry {
+                timestamp: Local::now(),
+                transcription: FAILED_TRANSCRIPTION.to_string(),
+                command_matched: None,
+                command_executed: None,
+                success: false,
+                duration_ms,
+                audio_duration_ms,
+                outcome: Some(CommandOutcome::Failed),
+            };
+            let mut history = assistant.history.lock().unwrap();
+            history.add_entry(entry);
+            if let Err(e) = history.save(&assistant.history_path) {
+                error!("Failed to save history: {}", e);
+            }
+            drop(history);
+            update_breaker(assistant, Some(FAILED_TRANSCRIPTION));
+        }
+    }
+}
+
+// Counts identical failures in a row; None means the recording did what was asked
+fn update_breaker(assistant: &Assistant, failure: Option<&str>) {
+    let Some(breaker) = &assistant.breaker else {
+        return;
+    };
+    let mut breaker = breaker.lock().unwrap();
+    let Some(failure) = failure else {
+        breaker.record_success();
+        return;
+    };
+    
+    if breaker.record_failure(&builtins::normalize(failure), Instant::now()) {
+        let settings = assistant.settings();
+        let settings = &settings.circuit_breaker;
+        warn!("🔌 The last {} recordings all failed as \"{}\".", settings.threshold, failure);
+        warn!("   The microphone is probably picking up the assistant's own voice or other audio,");
+        warn!("   so capture and voice feedback are suspended to break the loop.");
+        match settings.cooldown_secs {
+            0 => warn!("   Check the input device and restart the assistant."),
+            secs => warn!("   Check the input device; recording resumes in {} seconds.", secs),
+        }
+    }
+}
+
+// The transcription with the assistant's name stripped, or None if the name is required but missing
+fn addressed_text(text: &str, settings: &Settings) -> Option<String> {
+    let Some(name) = &settings.assistant_name else {
+        return Some(text.to_string());
+    };
+    match builtins::strip_assistant_name(text, name) {
+        Some(stripped) => Some(stripped),
+        None if settings.require_assistant_name => None,
+        None => Some(text.to_string()),
+    }
+}
+
+fn settle_delay(settings: &Settings, command_executed: bool) -> Option<Duration> {
+    (command_executed && settings.post_command_settle_ms > 0).then(|| Duration::from_millis(settings.post_command_settle_ms))
+}
+
+fn is_too_long_for_command(text: &str, settings: &Settings) -> bool {
+    settings.max_words_for_command_match
+        .is_some_and(|max_words| text.split_whitespace().count() > max_words)
+}
+
+// Exact phrase matches first, then phrases with placeholders, then `re:` patterns
+fn find_shortcut_match(text: &str, settings: &Settings) -> Option<(String, Shortcut)> {
+    if is_too_long_for_command(text, settings) {
+        return None;
+    }
+    
+    shortcuts::find_exact(text, &settings.shortcuts, &Homophones::new(&settings.homophones))
+        .and_then(|(phrase, shortcut)| {
+            let shortcut = shortcuts::fill_args(phrase, shortcut, text, &settings.arg_trim_words)?;
+            Some((phrase.clone(), shortcut))
+        })
+        .or_else(|| shortcuts::find_template(text, &settings.shortcuts, &settings.arg_trim_words))
+        .or_else(|| settings.regex_shortcuts.find(text, &settings.shortcuts, &settings.arg_trim_words))
+}
+
+// Only warns: a noisy sample phrase shouldn't keep the assistant from starting
+fn run_accuracy_check(whisper: &WhisperContext, check: &AccuracyCheckSettings, settings: &Settings) {
+    let options = settings.transcribe_options();
+    match transcribe_audio(whisper, &check.audio_path, &options) {
+        Ok(text) => {
+            let error_rate = transcribe::word_error_rate(&check.expected_text, &text);
+            if error_rate > check.max_word_error_rate {
+                warn!("Startup accuracy check failed ({:.0}% word error rate).", error_rate * 100.0);
+                warn!("   Expected: \"{}\"", check.expected_text);
+                warn!("   Heard:    \"{}\"", text.trim());
+                warn!("   The model may be corrupt or not suited to language '{}'.", settings.language);
+            } else {
+                info!("✓ Startup accuracy check passed ({:.0}% word error rate)", error_rate * 100.0);
+            }
+        }
+        Err(e) => warn!("Startup accuracy check couldn't run: {}", e),
+    }
+}
+
+// Matches one command's worth of transcription, runs it and records the attempt in history
+fn handle_command(assistant: &mut Assistant, settings: &Settings, text: String, dictation: bool, start_time: Instant, audio_duration_ms: u64) {
+    let voice = &assistant.voice;
+    let suggestions_engine = &assistant.suggestions;
+    let guard = &assistant.guard;
+    
+    // The repeated transcription goes through matching again, so captured arguments,
+    // confirmation and the command guard all apply as they did the first time
+    let text = if builtins::is_repeat_request(&text, &settings.repeat_phrase) {
+        let history = assistant.history.lock().unwrap();
+        let Some(entry) = builtins::last_executed(&history) else {
+            info!("🔁 Nothing to repeat yet.");
+            if settings.voice_feedback.announce_errors {
+                voice.speak("Nothing to repeat");
+            }
+            return;
+        };
+        let phrase = builtins::repeat_label(entry);
+        info!("🔁 Repeating: {}", phrase);
+        if settings.voice_feedback.confirm_commands {
+            voice.speak(&format!("Repeating {}", phrase));
+        }
+        entry.transcription.clone()
+    } else {
+        text
+    };
+    
+    // Check for shortcuts using fuzzy matching
+    let lower_text = text.to_lowercase();
+    let mut command_executed = false;
+    let mut suppressed = false;
+    let mut blocked = false;
+    let mut matched_phrase = None;
+    let mut executed_command = None;
+    
+    // Dictation mode types everything out and never runs a shortcut
+    if dictation {
+        executed_command = Some(DICTATION_COMMAND.to_string());
+        let keybind = recording_keys(settings);
+        let keybind_held = || {
+            let keys = DeviceState::new().get_keys();
+            keybind.iter().any(|key| keys.contains(key))
+        };
+        let run = || dictation::type_text(&text, keybind_held, DICTATION_MAX_WAIT);
+        match dispatch(text.trim(), DICTATION_COMMAND, settings, voice, run) {
+            CommandOutcome::Executed => {
+                info!("⌨️  Typed: {}", text.trim());
+                command_executed = true;
+            }
+            CommandOutcome::Suppressed => suppressed = true,
+            _ => {}
+        }
+    }
+    
+    // A bare number goes to the numeric template instead of the shortcuts
+    let numeric = settings.numeric_command_template
+        .as_deref()
+        .filter(|_| !dictation)
+        .and_then(|template| numbers::numeric_command(template, &text));
+    if let Some(command) = numeric {
+        matched_phrase = Some(text.trim().to_string());
+        executed_command = Some(command.clone());
+        
+        match dispatch(text.trim(), &command, settings, voice, || execute_command(&command, guard)) {
+            CommandOutcome::Executed => {
+                info!("✓ Executed numeric command: {}", command);
+                command_executed = true;
+                if settings.voice_feedback.confirm_commands {
+                    voice.speak(&format!("Executed {}", text.trim()));
+                }
+            }
+            CommandOutcome::Suppressed => suppressed = true,
+            CommandOutcome::Blocked => blocked = true,
+            _ => {}
+        }
+    }
+    
+    // A long sentence can contain a short phrase by accident
+    let is_dictation = dictation || is_too_long_for_command(&text, settings);
+    if is_dictation && !dictation {
+        info!("📝 Long transcription, treating it as dictation instead of a command.");
+    }
+    
+    // Then try exact matching, then phrases with placeholders
+    let exact = if matched_phrase.is_some() || dictation {
+        None
+    } else {
+        find_shortcut_match(&text, settings)
+    };
+    if let Some((phrase, shortcut)) = exact.as_ref().filter(|(_, shortcut)| needs_confirmation(shortcut, settings)) {
+        matched_phrase = Some(phrase.clone());
+        executed_command = Some(shortcut.display_command());
+        // Checked before asking, so a double-fired command isn't asked about again
+        if !cooling_down(&assistant.cooldowns, phrase, shortcut, settings, voice) {
+            assistant.pending_confirmation = Some(ask_confirmation(phrase, shortcut, voice));
+        }
+        suppressed = true;
+    } else if let Some((phrase, shortcut)) = &exact {
+        matched_phrase = Some(phrase.clone());
+        executed_command = Some(shortcut.display_command());
+        
+        let run = || run_shortcut(shortcut, voice, guard);
+        let outcome = if cooling_down(&assistant.cooldowns, phrase, shortcut, settings, voice) {
+            CommandOutcome::Suppressed
+        } else {
+            dispatch(phrase, &shortcut.display_command(), settings, voice, run)
+        };
+        suppressed = outcome == CommandOutcome::Suppressed;
+        blocked = outcome == CommandOutcome::Blocked;
+        if outcome == CommandOutcome::Executed {
+            info!("✓ Executed: {}", phrase);
+            command_executed = true;
+            assistant.cooldowns.record(phrase, Instant::now());
+            // Announce success
+            if settings.voice_feedback.confirm_commands {
+                voice.speak(&format!("Executed {}", phrase));
+            }
+            show_command_feedback(&settings.notifications, phrase, shortcut);
+        }
+    }
+    
+    // If no exact match, try fuzzy matching
+    if !command_executed && matched_phrase.is_none() && !is_dictation {
+        let active_shortcuts = shortcuts::enabled_only(&settings.shortcuts);
+        if let Some((best_match, score)) = suggestions_engine.find_best_match(&lower_text, &active_shortcuts) {
+            info!("🤔 No exact match, but found: '{}' ({}% similarity)", best_match, (score * 100.0) as i32);
+            
+            if suggestions_engine.is_confident(score) {
+                // High confidence - execute automatically
+                matched_phrase = Some(best_match.to_string());
+                let shortcut = active_shortcuts
+                    .get(best_match)
+                    .and_then(|shortcut| shortcuts::fill_args(best_match, shortcut, &text, &settings.arg_trim_words));
+                if let Some(shortcut) = shortcut.as_ref().filter(|shortcut| needs_confirmation(shortcut, settings)) {
+                    executed_command = Some(shortcut.display_command());
+                    if !cooling_down(&assistant.cooldowns, best_match, shortcut, settings, voice) {
+                        assistant.pending_confirmation = Some(ask_confirmation(best_match, shortcut, voice));
+                    }
+                    suppressed = true;
+                } else if let Some(shortcut) = &shortcut {
+                    executed_command = Some(shortcut.display_command());
+                    
+                    let run = || run_shortcut(shortcut, voice, guard);
+                    let outcome = if cooling_down(&assistant.cooldowns, best_match, shortcut, settings, voice) {
+                        CommandOutcome::Suppressed
+                    } else {
+                        dispatch(best_match, &shortcut.display_command(), settings, voice, run)
+                    };
+                    suppressed = outcome == CommandOutcome::Suppressed;
+                    blocked = outcome == CommandOutcome::Blocked;
+                    if outcome == CommandOutcome::Executed {
+                        info!("✓ Auto-executed fuzzy match: {}", best_match);
+                        command_executed = true;
+                        assistant.cooldowns.record(best_match, Instant::now());
+                        if settings.voice_feedback.confirm_commands {
+                            voice.speak(&format!("Executed {}", best_match));
+                        }
+                        show_command_feedback(&settings.notifications, best_match, shortcut);
+                    }
+                }
+            }
+        }
+    }
+    
+    // Last resort: treat the transcription as the name of an app to open
+    if !command_executed && matched_phrase.is_none() && !is_dictation && settings.app_launch_fallback {
+        if let Some(app) = launcher::extract_app_name(&text) {
+            let command = launcher::launch_command(&app, launcher::Platform::current());
+            info!("🚀 No shortcut matched, trying to launch '{}'", app);
+            executed_command = Some(command.clone());
+            
+            match dispatch(&app, &command, settings, voice, || execute_command(&command, guard)) {
+                CommandOutcome::Executed => {
+                    command_executed = true;
+                    if settings.voice_feedback.confirm_commands {
+                        voice.speak(&format!("Opening {}", app));
+                    }
+                }
+                CommandOutcome::Suppressed => suppressed = true,
+                CommandOutcome::Blocked => blocked = true,
+                _ => {}
+            }
+        }
+    }
+    
+    let mut history = assistant.history.lock().unwrap();
+    
+    if !command_executed && !suppressed && !blocked && matched_phrase.is_none() && !is_dictation {
+        info!("No matching shortcut found.");
+        
+        // Show smart suggestions
+        let suggestions = suggestions_engine.get_suggestions_for_failed_command(&text, &history, &settings.shortcuts);
+        if !suggestions.is_empty() {
+            println!("\n💡 Suggestions:");
+            for suggestion in &suggestions {
+                println!("   {}", suggestion);
+            }
+            
+            // Also show time-based suggestions
+            let time_suggestions = suggestions_engine.get_time_based_suggestions(&history, 3);
+            if !time_suggestions.is_empty() {
+                println!("   Commands you often use at this time: {}", time_suggestions.join(", "));
+            }
+        }
+        
+        // Announce no match, offering the nearest phrase if there is one
+        if settings.voice_feedback.announce_errors {
+            match suggestions_engine.spoken_suggestion(&lower_text, &settings.shortcuts) {
+                Some(suggestion) => voice.speak(&suggestion),
+                None => voice.speak("No matching command found"),
+            }
+        }
+    }
+    
+    // Record in history
+    let duration_ms = start_time.elapsed().as_millis() as u64;
+    let outcome = if suppressed {
+        CommandOutcome::Suppressed
+    } else if blocked {
+        CommandOutcome::Blocked
+    } else if command_executed {
+        CommandOutcome::Executed
+    } else if executed_command.is_some() {
+        CommandOutcome::Failed
+    } else {
+        CommandOutcome::NoMatch
+    };
+    let failure = matches!(outcome, CommandOutcome::Failed | CommandOutcome::NoMatch).then(|| text.clone());
+    if settings.audio_cues && (failure.is_some() || outcome == CommandOutcome::Blocked) {
+        cues::play(Cue::Error);
+    }
+    let entry = CommandEntry {
+        timestamp: Local::now(),
+        transcription: text,
+        command_matched: matched_phrase,
+        command_executed: executed_command,
+        success: command_executed,
+        duration_ms,
+        audio_duration_ms,
+        outcome: Some(outcome),
+    };
+    history.add_entry(entry);
+    
+    // Save history
+    if let Err(e) = history.save(&assistant.history_path) {
+        error!("Failed to save history: {}", e);
+    }
+    drop(history);
+    
+    update_breaker(assistant, failure.as_deref());
+    
+    // Give a window the command opened time to appear before the next queued recording is handled
+    if let Some(delay) = settle_delay(settings, command_executed) {
+        thread::sleep(delay);
+    }
+}
+
+// Whether the shortcut ran too recently to run again; counted as deliberately not run
+fn cooling_down(cooldowns: &Cooldowns, phrase: &str, shortcut: &Shortcut, settings: &Settings, voice: &VoiceFeedback) -> bool {
+    let cooldown = Duration::from_millis(shortcut.cooldown_ms.unwrap_or(settings.cooldown_ms));
+    let Some(remaining) = cooldowns.remaining(phrase, cooldown, Instant::now()) else {
+        return false;
+    };
+    info!("⏳ Ignoring '{}', it ran less than {} ms ago ({} ms left).", phrase, cooldown.as_millis(), remaining.as_millis());
+    if settings.voice_feedback.announce_cooldown {
+        voice.speak("Ignored, on cooldown");
+    }
+    true
+}
+
+// With allow_command_chaining, splits on the chain words when at least two of the pieces
+// name a shortcut, so "search for salt and pepper" still reaches the search shortcut whole
+fn chained_commands(text: &str, settings: &Settings, dictation: bool) -> Vec<String> {
+    if !settings.allow_command_chaining || dictation {
+        return vec![text.to_string()];
+    }
+    let mut segments = shortcuts::split_chain(text, &settings.chain_words);
+    let matching = segments.iter().filter(|segment| find_shortcut_match(segment, settings).is_some()).count();
+    if matching < 2 {
+        return vec![text.to_string()];
+    }
+    if segments.len() > settings.max_chained_commands {
+        warn!("⛓️  Only running the first {} of {} chained commands.", settings.max_chained_commands, segments.len());
+        segments.truncate(settings.max_chained_commands);
+    }
+    segments
+}
+
+fn pick_hypothesis(hypotheses: Vec<Hypothesis>, suggestions_engine: &SmartSuggestions, settings: &Settings) -> String {
+    for hypothesis in &hypotheses {
+        debug!("   Alternative: \"{}\" ({:.0}% confidence)", hypothesis.text, hypothesis.confidence * 100.0);
+    }
+    
+    let shortcuts = shortcuts::enabled_only(&settings.shortcuts);
+    // The same matching dispatch will do, so the chosen text is one that really runs
+    let matches = |text: &str| find_shortcut_match(text, settings).map(|(phrase, _)| phrase);
+    let chosen = match settings.nbest.selection {
+        NBestSelection::Weighted => suggestions_engine.best_hypothesis_match(&hypotheses, &shortcuts, settings.nbest.confidence_weight, matches),
+        NBestSelection::FirstMatch => suggestions_engine.first_confident_match(&hypotheses, &shortcuts, matches),
+    };
+    let best = chosen.map(|m| m.hypothesis).unwrap_or(0);
+    hypotheses[best].text.clone()
+}
+
+// Records until the keybind is pressed or `max_duration` passes
+fn record_once(recorder: &AudioRecorder, device_state: &DeviceState, combo: &mut ComboDetector<Keycode>, max_duration: Duration) -> Recording {
+    let capture_thread = recorder.spawn_capture();
+    
+    let started = Instant::now();
+    while started.elapsed() < max_duration {
+        if combo.update(&device_state.get_keys(), Instant::now()) || recorder.should_stop() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    let recording = recorder.stop_recording();
+    let _ = capture_thread.join();
+    recording
+}
+
+// Captures on its own recorder while the main loop isn't recording. Each interval the
+// newest audio joins the window, which is transcribed only if someone spoke in it.
+fn spawn_wake_listener(settings: &Settings, whisper: Arc<WhisperContext>) -> WakeListener {
+    let (listener, signal) = wake::channel();
+    let wake_word = settings.wake_word.clone().unwrap_or_default();
+    let mut options = settings.transcribe_options();
+    options.initial_prompt = Some(wake_word.clone());
+    let interval = Duration::from_millis(settings.wake_interval_ms);
+    let threshold = settings.silence_threshold;
+    let recorder = AudioRecorder::new(settings.input_device.clone());
+    let mut window = RollingBuffer::new((audio::WHISPER_SAMPLE_RATE as u64 * settings.wake_window_ms / 1000) as usize);
+    
+    thread::spawn(move || {
+        let mut capture_thread: Option<thread::JoinHandle<()>> = None;
+        loop {
+            if !signal.is_active() {
+                // Let go of the microphone while the real recording runs
+                if let Some(handle) = capture_thread.take() {
+                    recorder.stop_recording();
+                    let _ = handle.join();
+                    window.clear();
+                }
+                thread::sleep(WAKE_IDLE_POLL);
+                continue;
+            }
+            if capture_thread.is_none() {
+                capture_thread = Some(recorder.spawn_capture());
+            }
+            
+            thread::sleep(interval);
+            let chunk = recorder.take_recording();
+            window.push(&resample_to_16k(&chunk.samples, chunk.sample_rate));
+            if !window.has_speech(threshold) {
+                continue;
+            }
+            
+            match transcribe_samples(&whisper, &window.to_vec(), &options) {
+                Ok(text) if wake::heard_wake_word(&text, &wake_word) => {
+                    debug!("Wake word in: {}", text);
+                    window.clear();
+                    if !signal.notify() {
+                        break;
+                    }
+                }
+                Ok(text) => debug!("No wake word in: {}", text),
+                Err(e) => warn!("Wake word check failed: {}", e),
+            }
+        }
+    });
+    listener
+}
+
+// Transcribes the recording so far every PREVIEW_INTERVAL until dropped. Only new audio
+// is copied out of the recorder each time, so the capture callback is barely held up;
+// the final transcription of the whole recording is the one that gets acted on.
+struct StreamingPreview {
+    stop: Arc<AtomicBool>,
+}
+
+impl StreamingPreview {
+    fn spawn(recorder: AudioRecorder, whisper: Arc<WhisperContext>, settings: &Settings) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::clone(&stop);
+        let options = settings.transcribe_options();
+        let threshold = settings.silence_threshold;
+        
+        thread::spawn(move || {
+            let is_done = || stopped.load(Ordering::Acquire) || !recorder.is_recording();
+            let mut frames = 0;
+            let mut resampler: Option<audio::Resampler> = None;
+            let mut audio = Vec::new();
+            let mut shown = String::new();
+            loop {
+                let started = Instant::now();
+                while started.elapsed() < PREVIEW_INTERVAL && !is_done() {
+                    thread::sleep(CAPTURE_POLL);
+                }
+                if is_done() {
+                    break;
+                }
+                
+                let chunk = recorder.peek_since(frames);
+                frames += chunk.samples.len();
+                let resampler = resampler.get_or_insert_with(|| audio::Resampler::new(chunk.sample_rate, audio::WHISPER_SAMPLE_RATE));
+                audio.extend(resampler.process(&chunk.samples));
+                if !wake::has_speech(&audio, threshold) {
+                    continue;
+                }
+                
+                match transcribe_samples(&whisper, &audio, &options) {
+                    // Stale once the recording has stopped; the final pass speaks for it
+                    Ok(text) if !is_done() && !transcribe::is_blank(&text) && text.trim() != shown => {
+                        shown = text.trim().to_string();
+                        info!("✏️  {}", shown);
+                    }
+                    Ok(_) => {}
+                    Err(e) => debug!("Preview transcription failed: {}", e),
+                }
+            }
+        });
+        StreamingPreview { stop }
+    }
+}
+
+impl Drop for StreamingPreview {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+    }
+}
+
+// One state machine for every hotkey, so overlapping bindings resolve to a single action.
+// The user's bindings come first so they win over F1 and F2 on the same keys.
+fn build_hotkeys(bindings: Vec<(Vec<Keycode>, HotkeyAction)>, combo_window: Duration) -> Hotkeys<Keycode, HotkeyAction> {
+    bindings
+        .into_iter()
+        .fold(Hotkeys::new(combo_window), |hotkeys, (keys, action)| hotkeys.bind(keys, action))
+        .bind(vec![Keycode::F1], HotkeyAction::ShowStatistics)
+        .bind(vec![Keycode::F2], HotkeyAction::ShowSuggestions)
+}
+
+fn announce_recording_limit(settings: &Settings, voice: &VoiceFeedback) {
+    warn!("⏱️  Recording reached the {} s limit, stopping.", settings.max_recording_secs);
+    if settings.voice_feedback.announce_errors {
+        voice.speak("Recording limit reached");
+    }
+}
+
+// Stops capture, waits for the capture thread to release the device and queues the audio
+fn finish_recording(
+    recorder: &AudioRecorder,
+    capture_thread: Option<thread::JoinHandle<()>>,
+    queue: &TranscriptionQueue<(Recording, bool)>,
+    settings: &Settings,
+    voice: &VoiceFeedback,
+    dictation: bool,
+) {
+    let recording = recorder.stop_recording();
+    if let Some(handle) = capture_thread {
+        let _ = handle.join();
+    }
+    
+    if settings.audio_cues {
+        cues::play(Cue::Stop);
+    }
+    
+    if recording.samples.is_empty() {
+        return;
+    }
+    
+    // Announce processing
+    if settings.voice_feedback.announce_recording {
+        voice.speak("Processing");
+    }
+    
+    if queue.submit((recording, dictation)).is_err() {
+        warn!("Still busy with earlier recordings, dropping this one.");
+        if settings.voice_feedback.announce_errors {
+            voice.speak("Still processing, try again");
+        }
+    }
+}
+
+// Stops capture and throws the audio away: nothing is transcribed or added to the history
+fn cancel_recording(recorder: &AudioRecorder, capture_thread: Option<thread::JoinHandle<()>>, settings: &Settings, voice: &VoiceFeedback) {
+    recorder.stop_recording();
+    if let Some(handle) = capture_thread {
+        let _ = handle.join();
+    }
+    info!("🚫 Recording cancelled.");
+    
+    if settings.audio_cues {
+        cues::play(Cue::Stop);
+    }
+    if settings.voice_feedback.announce_recording {
+        voice.speak("Cancelled");
+    }
+}
+
+// The decoder already gives 16 kHz mono; silence is trimmed as the recorder would have
+fn file_recording(path: &str, settings: &Settings) -> Result<Recording, Box<dyn std::error::Error>> {
+    let mut samples = audio::load_audio_file(path)?;
+    if settings.trim_silence {
+        samples = audio::trim_silence(&samples, settings.silence_threshold, audio::WHISPER_SAMPLE_RATE, TRIM_PADDING).to_vec();
+    }
+    Ok(Recording {
+        samples,
+        sample_rate: audio::WHISPER_SAMPLE_RATE,
+        channels: 1,
+    })
+}
+
+// One-shot mode hands a single recording to the regular dispatch path instead of looping
+fn run_once<H: FnOnce(Recording)>(recording: Recording, handle: H) -> bool {
+    if recording.samples.is_empty() {
+        warn!("Nothing was recorded.");
+        return false;
+    }
+    handle(recording);
+    true
+}
+
+fn print_statistics(history: &CommandHistory) {
+    let stats = history.get_statistics();
+    stats.print_summary();
+    
+    let today = Local::now().date_naive();
+    let today_stats = history.get_statistics_where(|e| e.timestamp.date_naive() == today);
+    println!("Today: {} commands, {} successful", today_stats.total_commands, today_stats.successful_commands);
+    
+    let distribution = history.get_time_distribution();
+    distribution.print_time_distribution(TimeGrouping::Hour);
+    distribution.print_time_distribution(TimeGrouping::Day);
+    
+    // Show recent commands
+    println!("\n📜 Recent commands:");
+    for entry in history.get_recent_entries(5) {
+        println!("  {} - \"{}\" -> {}",
+            entry.timestamp.format("%H:%M:%S"),
+            entry.transcription,
+            if entry.success { "✓" } else { "✗" }
+        );
+    }
+    println!();
+}
+
+#[cfg(feature = "http-server")]
+fn start_http_server(settings: &Settings, whisper: &Arc<WhisperContext>, history: &Arc<Mutex<CommandHistory>>) {
+    let address = &settings.http_server.address;
+    let temp_dir = settings.temp_dir.as_ref().map_or_else(std::env::temp_dir, std::path::PathBuf::from);
+    match server::spawn(address, Arc::clone(whisper), settings.transcribe_options(), Arc::clone(history), temp_dir) {
+        Ok(bound) => info!("🌐 HTTP server listening on http://{}", bound),
+        Err(e) => error!("Failed to start the HTTP server on {}: {}", address, e),
+    }
+}
+
+#[cfg(not(feature = "http-server"))]
+fn start_http_server(_settings: &Settings, _whisper: &Arc<WhisperContext>, _history: &Arc<Mutex<CommandHistory>>) {
+    warn!("http_server is enabled, but this build has no HTTP support; rebuild with --features http-server");
+}
+
+// Records the room with nobody talking and offers a threshold a little above its noise
+fn calibrate_silence_threshold(settings: &mut Settings, assume_yes: bool) -> Result<(), Box<dyn std::error::Error>> {
+    println!("Measuring background noise for {} seconds, please stay quiet...", CALIBRATION_LENGTH.as_secs());
+    let recorder = AudioRecorder::new(settings.input_device.clone());
+    let capture_thread = recorder.spawn_capture();
+    thread::sleep(CALIBRATION_LENGTH);
+    let recording = recorder.stop_recording();
+    let _ = capture_thread.join();
+    if recording.samples.is_empty() {
+        return Err("Nothing was recorded, check the input device (--list-devices)".into());
+    }
+    
+    let floor = audio::noise_floor(&recording.samples, recording.sample_rate);
+    let threshold = audio::suggested_threshold(floor);
+    println!("Noise floor: {:.4} RMS", floor);
+    println!("Recommended silence_threshold: {} (currently {})", threshold, settings.silence_threshold);
+    
+    if assume_yes || confirm_on_stdin(&format!("Save silence_threshold = {} to {}?", threshold, SETTINGS_PATH)) {
+        settings.silence_threshold = threshold;
+        save_settings(settings)?;
+        println!("Saved.");
+    }
+    Ok(())
+}
+
+fn print_search_results(entries: &[&CommandEntry]) {
+    if entries.is_empty() {
+        println!("No matching history entries.");
+    }
+    for entry in entries {
+        let command = entry.command_executed.as_deref().or(entry.command_matched.as_deref()).unwrap_or("no match");
+        println!("{} {} \"{}\" -> {}",
+            entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            if entry.success { "✓" } else { "✗" },
+            entry.transcription,
+            command
+        );
+    }
+}
+
+fn confirm_on_stdin(prompt: &str) -> bool {
+    print!("{} [y/N] ", prompt);
+    let _ = io::stdout().flush();
+    
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    builtins::classify_response(&answer, None) == Some(true)
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("Voice Command Assistant Starting...");
+    // Info and above by default; RUST_LOG=debug shows timings and other internals
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .format_timestamp(None)
+        .format_target(false)
+        .init();
+    
+    let cli = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+    
+    if cli.show_help {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+    
+    if cli.print_default_config {
+        println!("{}", serde_json::to_string_pretty(&Settings::default())?);
+        return Ok(());
+    }
+    
+    if cli.print_schema {
+        println!("{}", serde_json::to_string_pretty(&schemars::schema_for!(Settings))?);
+        return Ok(());
+    }
+    
+    if cli.list_devices {
+        let names = devices::input_device_names();
+        if names.is_empty() {
+            println!("No input devices found.");
+        }
+        for name in names {
+            let note = if devices::is_loopback_device(&name) { "  (loopback/monitor)" } else { "" };
+            println!("{}{}", name, note);
+        }
+        return Ok(());
+    }
+    
+    if cli.list_voices {
+        let settings = load_settings()?;
+        let voices = tts::describe_voices(settings.voice_feedback.tts_engine.as_deref())?;
+        if voices.is_empty() {
+            println!("No voices found.");
+        }
+        for voice in voices {
+            println!("{}", voice);
+        }
+        return Ok(());
+    }
+    
+    if cli.calibrate {
+        let mut settings = load_settings()?;
+        calibrate_silence_threshold(&mut settings, cli.assume_yes)?;
+        return Ok(());
+    }
+    
+    if cli.stats {
+        print_statistics(&CommandHistory::load(HISTORY_PATH)?);
+        return Ok(());
+    }
+    
+    if let Some(query) = &cli.search {
+        let history = CommandHistory::load(HISTORY_PATH)?;
+        let since = cli.since.and_then(|date| date.and_hms_opt(0, 0, 0)?.and_local_timezone(Local).earliest());
+        print_search_results(&history.search(query, since, cli.failures));
+        return Ok(());
+    }
+    
+    if cli.clear_history {
+        let settings = load_settings()?;
+        if !cli.assume_yes && !confirm_on_stdin("Clear all command history?") {
+            println!("Aborted.");
+            return Ok(());
+        }
+        
+        let mut history = CommandHistory::load(HISTORY_PATH)?.with_format(settings.history_format);
+        clear_history(&mut history, HISTORY_PATH, settings.archive_history_on_clear)?;
+        println!("Command history cleared.");
+        return Ok(());
+    }
+    
+    // Captions only: the file is prepared like a recording but never matched
+    if let Some(path) = cli.transcribe_file.as_ref().filter(|_| cli.no_match || cli.timestamps) {
+        let settings = load_settings()?;
+        let options = settings.transcribe_options();
+        if !settings.skip_model_check {
+            model::check_model_file(&settings.whisper_model_path)?;
+        }
+        let whisper = transcribe::load_model(&settings.whisper_model_path, settings.use_gpu)?;
+        let recording = file_recording(path, &settings)?;
+        let segments = transcribe_segments(&whisper, &recording.samples, &options)?;
+        if cli.timestamps {
+            for segment in &segments {
+                println!("{}", segment.timeline_line());
+            }
+        } else {
+            println!("{}", transcribe::segments_text(&segments));
+        }
+        return Ok(());
+    }
+    
+    info!("Voice Command Assistant Starting...");
     
     // Load settings
-    let settings = load_settings()?;
-    println!("Settings loaded successfully");
-    println!("Keybind: {}", settings.keybind);
-    println!("Shortcuts: {:?}", settings.shortcuts);
+    let mut settings = load_settings()?;
+    if cli.dry_run {
+        settings.dry_run = true;
+    }
+    let settings = Arc::new(settings);
+    debug!("Settings loaded successfully");
+    info!("Keybinds: {}", describe_keybinds(&settings.keybinds()));
+    debug!("Shortcuts: {:?}", settings.shortcuts);
     
     // Check if whisper model exists
     if !Path::new(&settings.whisper_model_path).exists() {
-        eprintln!("Whisper model not found at: {}", settings.whisper_model_path);
-        eprintln!("Please download a ggml model from https://huggingface.co/ggerganov/whisper.cpp");
+        error!("Whisper model not found at: {}", settings.whisper_model_path);
+        error!("Please download a ggml model from https://huggingface.co/ggerganov/whisper.cpp");
         return Err("Whisper model not found".into());
     }
     
+    // Catch interrupted downloads before whisper.cpp fails on them
+    if !settings.skip_model_check {
+        model::check_model_file(&settings.whisper_model_path)?;
+    }
+    
+    // Refuse to load models that would likely exhaust system memory
+    if !settings.skip_memory_check {
+        model::check_memory(&settings.whisper_model_path)?;
+    }
+    
+    let whisper = Arc::new(transcribe::load_model(&settings.whisper_model_path, settings.use_gpu)?);
+    if settings.task == Task::Translate && !whisper.is_multilingual() {
+        warn!("English-only models can't translate; use a multilingual model (without .en) for the Translate task.");
+    }
+    
+    if let Some(check) = &settings.startup_accuracy_check {
+        run_accuracy_check(&whisper, check, &settings);
+    }
+    
+    // Toggles from the last run, if they're kept
+    let runtime = if settings.persist_runtime_state {
+        RuntimeState::load(STATE_PATH)
+    } else {
+        RuntimeState::default()
+    };
+    
     // Initialize voice feedback
-    let voice = VoiceFeedback::new(settings.voice_feedback.enabled);
+    let voice = VoiceFeedback::new(settings.voice_feedback.enabled, settings.voice_feedback.tts_engine.as_deref())
+        .with_voice(settings.voice_feedback.voice_name.as_deref())
+        .with_prosody(&settings.voice_feedback.prosody())
+        .with_quiet_hours(settings.voice_feedback.quiet_hours)
+        .with_natural_readback(settings.voice_feedback.natural_readback);
+    voice.set_muted(runtime.muted);
     
     // Load command history
-    let history_path = "command_history.json";
-    let mut history = CommandHistory::load(history_path).unwrap_or_else(|_| CommandHistory::new());
+    let history = Arc::new(Mutex::new(
+        CommandHistory::load(HISTORY_PATH).unwrap_or_else(|_| CommandHistory::new()).with_format(settings.history_format)
+    ));
+    
+    if settings.http_server.enabled && cli.transcribe_file.is_none() {
+        start_http_server(&settings, &whisper, &history);
+    }
     
     // Initialize smart suggestions
-    let suggestions_engine = SmartSuggestions::new();
+    let suggestions_engine = SmartSuggestions::with_thresholds(settings.match_min_confidence, settings.suggestion_min_confidence)
+        .with_homophones(Homophones::new(&settings.homophones));
+    
+    let breaker = settings.circuit_breaker.enabled.then(|| {
+        let cooldown_secs = settings.circuit_breaker.cooldown_secs;
+        Arc::new(Mutex::new(CircuitBreaker::new(
+            settings.circuit_breaker.threshold,
+            Duration::from_secs(settings.circuit_breaker.window_secs),
+            (cooldown_secs > 0).then(|| Duration::from_secs(cooldown_secs)),
+        )))
+    });
+    
+    let bindings = parse_keybinds(&settings.keybinds())?;
+    let live_settings = Arc::new(RwLock::new(Arc::clone(&settings)));
+    let mut assistant = Assistant {
+        settings: Arc::clone(&live_settings),
+        whisper: Arc::clone(&whisper),
+        voice: voice.clone(),
+        history: Arc::clone(&history),
+        history_path: HISTORY_PATH.to_string(),
+        suggestions: SmartSuggestions::with_thresholds(settings.match_min_confidence, settings.suggestion_min_confidence)
+            .with_homophones(Homophones::new(&settings.homophones)),
+        pending_confirmation: None,
+        runtime,
+        breaker: breaker.clone(),
+        guard: settings.command_guard()?,
+        cooldowns: Cooldowns::default(),
+        echo_transcription: cli.transcribe_file.is_some(),
+        captions: settings.caption_fifo.as_deref().and_then(|path| match CaptionSink::open(path) {
+            Ok(sink) => {
+                info!("📝 Writing captions to {}", path);
+                Some(sink)
+            }
+            Err(e) => {
+                warn!("Captions disabled, couldn't create {}: {}", path, e);
+                None
+            }
+        }),
+    };
+    
+    // A file in place of the microphone, handled exactly like a recording of it
+    if let Some(path) = &cli.transcribe_file {
+        let recording = file_recording(path, &settings)?;
+        run_once(recording, |recording| process_recording(&mut assistant, recording, false));
+        voice.finish(ONCE_SPEECH_MAX_WAIT);
+        return Ok(());
+    }
+    
+    // A monitor device records the assistant's own voice
+    devices::check_input_device(settings.input_device.as_deref());
     
     let device_state = DeviceState::new();
-    let recorder = AudioRecorder::new();
+    let mut recorder = AudioRecorder::new(settings.input_device.clone());
+    // Hands-free recordings have no key press to end them
+    if settings.auto_stop_on_silence || settings.wake_word.is_some() {
+        recorder = recorder.with_auto_stop(settings.silence_threshold, Duration::from_millis(settings.silence_duration_ms));
+    }
+    if settings.trim_silence {
+        recorder = recorder.with_trim_silence(settings.silence_threshold);
+    }
+    if settings.max_recording_secs > 0 {
+        recorder = recorder.with_max_duration(Duration::from_secs(settings.max_recording_secs));
+    }
     let mut is_recording = false;
-    let target_key = string_to_keycode(&settings.keybind)
-        .ok_or_else(|| format!("Invalid keybind: {}", settings.keybind))?;
+    let mut capture_thread = None;
+    let mut preview: Option<StreamingPreview> = None;
+    // Armed at the start of each recording
+    let mut cancel_combo: Option<ComboDetector<Keycode>> = None;
+    let combo_window = Duration::from_millis(settings.combo_window_ms);
+    
+    if cli.once {
+        // The first recording keybind stops it, and decides whether it's dictation; validated above
+        let (position, (keys, action)) = bindings
+            .into_iter()
+            .enumerate()
+            .find(|(_, (_, action))| matches!(action, HotkeyAction::ToggleRecording { .. }))
+            .ok_or("no keybind starts recording")?;
+        let dictation = action == HotkeyAction::ToggleRecording { dictation: true };
+        let mut record_combo = ComboDetector::new(keys, combo_window);
+        println!("🎤 Recording... press {} to stop.", settings.keybinds()[position].keys);
+        let recording = record_once(&recorder, &device_state, &mut record_combo, ONCE_MAX_RECORDING);
+        run_once(recording, |recording| process_recording(&mut assistant, recording, dictation));
+        // Let "Executed ..." and any errors be heard before exiting
+        voice.finish(ONCE_SPEECH_MAX_WAIT);
+        return Ok(());
+    }
+    
+    // Transcription and dispatch run on a worker so the hotkey stays responsive
+    let queue = TranscriptionQueue::new(RECORDING_QUEUE_CAPACITY, move |(recording, dictation)| {
+        process_recording(&mut assistant, recording, dictation);
+    });
+    
+    let mut hotkeys = build_hotkeys(bindings, combo_window);
+    
+    let wake_listener = settings.wake_word.as_ref().map(|wake_word| {
+        info!("👂 Listening for \"{}\"", wake_word);
+        spawn_wake_listener(&settings, Arc::clone(&whisper))
+    });
+    
+    let mut watcher = match SettingsWatcher::new(SETTINGS_PATH) {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            warn!("Not watching {} for changes, restart to apply edits: {}", SETTINGS_PATH, e);
+            None
+        }
+    };
+    if let Some(watcher) = &mut watcher {
+        watch_shortcut_files(watcher, &settings);
+    }
     
-    println!("Voice assistant ready! Press {} to start/stop recording.", settings.keybind);
+    if let Err(e) = shutdown::install() {
+        warn!("Ctrl-C will exit without saving or stopping speech: {}", e);
+    }
+    
+    println!("Voice assistant ready!");
+    for keybind in settings.keybinds() {
+        println!("Press {} to {}.", keybind.keys, keybind.action.describe());
+    }
     println!("Press F1 to show command statistics.");
     println!("Press F2 to show smart command suggestions.");
     
@@ -335,33 +2766,81 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         voice.speak("Voice assistant ready");
     }
     
-    loop {
+    // Set by a Reload keybind, handled at the top of the next pass
+    let mut reload_requested = false;
+    // Whether the recording in progress was started by a Dictation keybind
+    let mut recording_dictation = false;
+    
+    while !shutdown::requested() {
+        let file_changed = watcher.as_ref().is_some_and(|watcher| watcher.changed());
+        if let Some(watcher) = watcher.as_ref().filter(|_| file_changed) {
+            // Let the editor finish writing, and drop the events the rest of the save caused
+            thread::sleep(RELOAD_SETTLE);
+            watcher.changed();
+        }
+        if file_changed || reload_requested {
+            reload_requested = false;
+            let current = Arc::clone(&live_settings.read().unwrap());
+            match reload_settings(&current) {
+                Ok(reloaded) if same_settings(&reloaded, &current) => {
+                    if !file_changed {
+                        info!("🔄 {} is unchanged", SETTINGS_PATH);
+                    }
+                }
+                Ok(reloaded) => {
+                    if reloaded.keybinds() != current.keybinds() {
+                        // Validated by reload_settings
+                        hotkeys = build_hotkeys(parse_keybinds(&reloaded.keybinds())?, combo_window);
+                        info!("Keybinds: {}", describe_keybinds(&reloaded.keybinds()));
+                    }
+                    if let Some(watcher) = &mut watcher {
+                        watch_shortcut_files(watcher, &reloaded);
+                    }
+                    if reloaded.voice_feedback != current.voice_feedback {
+                        apply_voice_settings(&voice, &current.voice_feedback, &reloaded.voice_feedback);
+                    }
+                    info!("🔄 Reloaded {}", SETTINGS_PATH);
+                    if reloaded.voice_feedback.confirm_commands {
+                        voice.speak("Settings reloaded");
+                    }
+                    *live_settings.write().unwrap() = Arc::new(reloaded);
+                }
+                Err(e) => {
+                    warn!("Ignoring changes to {}: {}", SETTINGS_PATH, e);
+                    if current.voice_feedback.announce_errors {
+                        voice.speak("The settings file has an error");
+                    }
+                }
+            }
+        }
+        let settings = Arc::clone(&live_settings.read().unwrap());
+        
         let keys: Vec<Keycode> = device_state.get_keys();
+        let action = hotkeys.update(&keys, Instant::now());
+        let cancelled = is_recording && cancel_combo.as_mut().is_some_and(|combo| combo.update(&keys, Instant::now()));
         
         // Check for F1 to show statistics
-        if keys.contains(&Keycode::F1) {
-            let stats = history.get_statistics();
-            stats.print_summary();
-            
-            // Show recent commands
-            println!("\n📜 Recent commands:");
-            for entry in history.get_recent_entries(5) {
-                println!("  {} - \"{}\" -> {}",
-                    entry.timestamp.format("%H:%M:%S"),
-                    entry.transcription,
-                    if entry.success { "✓" } else { "✗" }
-                );
-            }
-            println!();
+        if action == Some(HotkeyAction::ShowStatistics) {
+            print_statistics(&history.lock().unwrap());
             
             // Wait for key release
-            while device_state.get_keys().contains(&Keycode::F1) {
+            while hotkeys.is_held(&device_state.get_keys()) && !shutdown::requested() {
+                thread::sleep(Duration::from_millis(50));
+            }
+            hotkeys.reset();
+        }
+        
+        if action == Some(HotkeyAction::Reload) {
+            reload_requested = true;
+            while hotkeys.is_held(&device_state.get_keys()) && !shutdown::requested() {
                 thread::sleep(Duration::from_millis(50));
             }
+            hotkeys.reset();
         }
         
         // Check for F2 to show smart suggestions
-        if keys.contains(&Keycode::F2) {
+        if action == Some(HotkeyAction::ShowSuggestions) {
+            let history = history.lock().unwrap();
             println!("\n🧠 Smart Command Suggestions:");
             println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
             
@@ -388,195 +2867,716 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             println!("💡 Tip: The assistant now supports fuzzy matching!");
             println!("   Say 'termnal' and it will understand 'terminal'");
             println!();
+            drop(history);
             
             // Wait for key release
-            while device_state.get_keys().contains(&Keycode::F2) {
+            while hotkeys.is_held(&device_state.get_keys()) && !shutdown::requested() {
                 thread::sleep(Duration::from_millis(50));
             }
+            hotkeys.reset();
         }
         
-        if keys.contains(&target_key) {
+        let breaker_open = breaker.as_ref().is_some_and(|breaker| breaker.lock().unwrap().is_open(Instant::now()));
+        // The wake word starts a command recording just like its key would
+        let woke = !is_recording && wake_listener.as_ref().is_some_and(WakeListener::heard);
+        let toggled = match action {
+            Some(HotkeyAction::ToggleRecording { dictation }) => Some(dictation),
+            _ if woke => Some(false),
+            _ => None,
+        };
+        if cancelled {
+            is_recording = false;
+            preview = None;
+            cancel_recording(&recorder, capture_thread.take(), &settings, &voice);
+            if let Some(listener) = &wake_listener {
+                listener.resume();
+            }
+            while hotkeys.is_held(&device_state.get_keys()) && !shutdown::requested() {
+                thread::sleep(Duration::from_millis(50));
+            }
+            hotkeys.reset();
+        } else if toggled.is_some() && !is_recording && breaker_open {
+            let remaining = breaker.as_ref().and_then(|breaker| breaker.lock().unwrap().remaining(Instant::now()));
+            match remaining {
+                Some(remaining) => warn!("🔌 Capture suspended after repeated failures, resumes in {} s.", remaining.as_secs() + 1),
+                None => warn!("🔌 Capture suspended after repeated failures. Restart the assistant to resume."),
+            }
+            // The wake word paused the listener on its way here
+            if let Some(listener) = &wake_listener {
+                listener.resume();
+            }
+            while hotkeys.is_held(&device_state.get_keys()) && !shutdown::requested() {
+                thread::sleep(Duration::from_millis(50));
+            }
+            hotkeys.reset();
+        } else if toggled.is_some() || (is_recording && recorder.should_stop()) {
             if !is_recording {
                 // Start recording
                 is_recording = true;
-                println!("\n🎤 Recording started...");
+                recording_dictation = toggled == Some(true);
+                if let Some(listener) = &wake_listener {
+                    listener.pause();
+                }
+                if woke {
+                    info!("👂 Heard the wake word, recording started...");
+                } else if recording_dictation {
+                    info!("🎤 Dictation started...");
+                } else {
+                    info!("🎤 Recording started...");
+                }
+                
+                if settings.audio_cues {
+                    cues::play(Cue::Start);
+                }
                 
                 // Announce recording start
                 if settings.voice_feedback.announce_recording {
-                    voice.speak("Recording");
+                    if settings.voice_feedback.suppress_self_audio {
+                        voice.speak_blocking("Recording");
+                    } else {
+                        voice.speak("Recording");
+                    }
+                }
+                if settings.voice_feedback.suppress_self_audio {
+                    voice.silence_before_capture(SELF_AUDIO_MAX_WAIT);
                 }
                 
-                let recorder_clone = recorder.clone();
-                
-                thread::spawn(move || {
-                    if let Err(e) = recorder_clone.start_recording() {
-                        eprintln!("Recording error: {}", e);
-                    }
-                });
+                // Validated with the rest of the settings
+                cancel_combo = settings.cancel_keys().ok().flatten().map(|keys| ComboDetector::new(keys, combo_window));
+                capture_thread = Some(recorder.spawn_capture());
+                if settings.streaming_preview {
+                    preview = Some(StreamingPreview::spawn(recorder.clone(), Arc::clone(&whisper), &settings));
+                }
                 
                 // Wait for key release
-                while device_state.get_keys().contains(&target_key) {
+                let mut cancelled = false;
+                while hotkeys.is_held(&device_state.get_keys()) && !recorder.should_stop() && !shutdown::requested() {
+                    if cancel_combo.as_mut().is_some_and(|combo| combo.update(&device_state.get_keys(), Instant::now())) {
+                        cancelled = true;
+                        break;
+                    }
                     thread::sleep(Duration::from_millis(50));
                 }
-            } else {
-                // Stop recording and process
-                is_recording = false;
-                let samples = recorder.stop_recording();
                 
-                if !samples.is_empty() {
-                    println!("Processing audio...");
-                    
-                    // Track processing start time
-                    let start_time = Instant::now();
-                    
-                    // Announce processing
-                    if settings.voice_feedback.announce_recording {
-                        voice.speak("Processing");
+                if cancelled {
+                    is_recording = false;
+                    preview = None;
+                    cancel_recording(&recorder, capture_thread.take(), &settings, &voice);
+                    if let Some(listener) = &wake_listener {
+                        listener.resume();
                     }
-                    
-                    // Save audio to temporary file
-                    let temp_audio = "temp_recording.wav";
-                    if let Err(e) = recorder.save_wav(&samples, temp_audio) {
-                        eprintln!("Failed to save audio: {}", e);
-                        continue;
+                    while hotkeys.is_held(&device_state.get_keys()) && !shutdown::requested() {
+                        thread::sleep(Duration::from_millis(50));
                     }
-                    
-                    // Transcribe
-                    match transcribe_audio(&settings.whisper_model_path, temp_audio) {
-                        Ok(text) => {
-                            println!("Transcription: {}", text);
-                            
-                            // Check for shortcuts using fuzzy matching
-                            let lower_text = text.to_lowercase();
-                            let mut command_executed = false;
-                            let mut matched_phrase = None;
-                            let mut executed_command = None;
-                            
-                            // First try exact matching
-                            for (phrase, command) in &settings.shortcuts {
-                                if lower_text.contains(&phrase.to_lowercase()) {
-                                    matched_phrase = Some(phrase.clone());
-                                    executed_command = Some(command.clone());
-                                    
-                                    if let Err(e) = execute_command(command) {
-                                        eprintln!("Failed to execute command: {}", e);
-                                        // Announce error
-                                        if settings.voice_feedback.announce_errors {
-                                            voice.speak(&format!("Failed to execute {}", phrase));
-                                        }
-                                    } else {
-                                        println!("✓ Executed: {}", phrase);
-                                        command_executed = true;
-                                        // Announce success
-                                        if settings.voice_feedback.confirm_commands {
-                                            voice.speak(&format!("Executed {}", phrase));
-                                        }
-                                    }
-                                    break;
-                                }
-                            }
-                            
-                            // If no exact match, try fuzzy matching
-                            if !command_executed && matched_phrase.is_none() {
-                                if let Some((best_match, score)) = suggestions_engine.find_best_match(&lower_text, &settings.shortcuts) {
-                                    println!("🤔 No exact match, but found: '{}' ({}% similarity)", best_match, (score * 100.0) as i32);
-                                    
-                                    if score >= 0.85 {
-                                        // High confidence - execute automatically
-                                        matched_phrase = Some(best_match.to_string());
-                                        if let Some(command) = settings.shortcuts.get(best_match) {
-                                            executed_command = Some(command.clone());
-                                            
-                                            if let Err(e) = execute_command(command) {
-                                                eprintln!("Failed to execute command: {}", e);
-                                                if settings.voice_feedback.announce_errors {
-                                                    voice.speak(&format!("Failed to execute {}", best_match));
-                                                }
-                                            } else {
-                                                println!("✓ Auto-executed fuzzy match: {}", best_match);
-                                                command_executed = true;
-                                                if settings.voice_feedback.confirm_commands {
-                                                    voice.speak(&format!("Executed {}", best_match));
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                            
-                            if !command_executed && matched_phrase.is_none() {
-                                println!("No matching shortcut found.");
-                                
-                                // Show smart suggestions
-                                let suggestions = suggestions_engine.get_suggestions_for_failed_command(&text, &history, &settings.shortcuts);
-                                if !suggestions.is_empty() {
-                                    println!("\n💡 Suggestions:");
-                                    for suggestion in &suggestions {
-                                        println!("   {}", suggestion);
-                                    }
-                                    
-                                    // Also show time-based suggestions
-                                    let time_suggestions = suggestions_engine.get_time_based_suggestions(&history, 3);
-                                    if !time_suggestions.is_empty() {
-                                        println!("   Commands you often use at this time: {}", time_suggestions.join(", "));
-                                    }
-                                }
-                                
-                                // Announce no match
-                                if settings.voice_feedback.announce_errors {
-                                    voice.speak("No matching command found");
-                                }
-                            }
-                            
-                            // Record in history
-                            let duration_ms = start_time.elapsed().as_millis() as u64;
-                            let entry = CommandEntry {
-                                timestamp: Local::now(),
-                                transcription: text,
-                                command_matched: matched_phrase,
-                                command_executed: executed_command,
-                                success: command_executed,
-                                duration_ms,
-                            };
-                            history.add_entry(entry);
-                            
-                            // Save history
-                            if let Err(e) = history.save(history_path) {
-                                eprintln!("Failed to save history: {}", e);
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("Transcription error: {}", e);
-                            // Announce transcription error
-                            if settings.voice_feedback.announce_errors {
-                                voice.speak("Transcription failed");
-                            }
-                            
-                            // Record failed transcription in history
-                            let duration_ms = start_time.elapsed().as_millis() as u64;
-                            let entry = CommandEntry {
-                                timestamp: Local::now(),
-                                transcription: "[Transcription failed]".to_string(),
-                                command_matched: None,
-                                command_executed: None,
-                                success: false,
-                                duration_ms,
-                            };
-                            history.add_entry(entry);
-                        }
+                } else if settings.recording_mode == RecordingMode::Hold && !woke {
+                    // Push-to-talk ends with the release
+                    if recorder.limit_reached() {
+                        announce_recording_limit(&settings, &voice);
                     }
-                    
-                    // Clean up temp file
-                    let _ = fs::remove_file(temp_audio);
+                    is_recording = false;
+                    preview = None;
+                    finish_recording(&recorder, capture_thread.take(), &queue, &settings, &voice, recording_dictation);
+                    if let Some(listener) = &wake_listener {
+                        listener.resume();
+                    }
+                    while hotkeys.is_held(&device_state.get_keys()) && !shutdown::requested() {
+                        thread::sleep(Duration::from_millis(50));
+                    }
+                }
+                hotkeys.reset();
+            } else {
+                // Stop recording and hand the audio to the worker
+                if toggled.is_none() && recorder.limit_reached() {
+                    announce_recording_limit(&settings, &voice);
+                } else if toggled.is_none() {
+                    info!("🤫 Silence detected, stopping.");
+                }
+                is_recording = false;
+                preview = None;
+                finish_recording(&recorder, capture_thread.take(), &queue, &settings, &voice, recording_dictation);
+                if let Some(listener) = &wake_listener {
+                    listener.resume();
                 }
                 
                 // Wait for key release
-                while device_state.get_keys().contains(&target_key) {
+                while hotkeys.is_held(&device_state.get_keys()) && !shutdown::requested() {
                     thread::sleep(Duration::from_millis(50));
                 }
+                hotkeys.reset();
             }
         }
         
         thread::sleep(Duration::from_millis(50));
     }
+    
+    info!("Shutting down...");
+    // A recording in progress is dropped; ones already queued are still handled
+    drop(preview);
+    if is_recording {
+        recorder.stop_recording();
+        if let Some(handle) = capture_thread.take() {
+            let _ = handle.join();
+        }
+    }
+    if let Some(listener) = &wake_listener {
+        listener.pause();
+    }
+    drop(queue);
+    
+    if let Err(e) = history.lock().unwrap().save(HISTORY_PATH) {
+        error!("Failed to save history: {}", e);
+    }
+    
+    voice.clear_queue();
+    voice.speak("Shutting down");
+    voice.finish(SHUTDOWN_SPEECH_MAX_WAIT);
+    voice.stop();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_command_is_rejected() {
+        let mut settings = Settings::default();
+        assert!(settings.validate().is_ok());
+        
+        settings.shortcuts.insert("do nothing".to_string(), "   ".into());
+        assert!(settings.validate().is_err());
+        
+        assert!(execute_command("", &CommandGuard::default()).is_err());
+        assert!(execute_command("  ", &CommandGuard::default()).is_err());
+    }
+    
+    #[test]
+    fn test_long_transcription_does_not_match_contained_shortcut() {
+        let mut settings = Settings::default();
+        settings.shortcuts.insert("open terminal".to_string(), "xterm".into());
+        let sentence = "so then I told him to open terminal and run the tests again";
+        assert!(find_shortcut_match(sentence, &settings).is_some());
+        
+        settings.max_words_for_command_match = Some(6);
+        assert!(find_shortcut_match(sentence, &settings).is_none());
+        assert_eq!(find_shortcut_match("Open terminal.", &settings).unwrap().0, "open terminal");
+    }
+    
+    #[test]
+    fn test_chained_commands_need_two_shortcuts() {
+        let mut settings = Settings { allow_command_chaining: true, max_chained_commands: 2, ..Settings::default() };
+        settings.shortcuts.insert("open terminal".to_string(), "xterm".into());
+        settings.shortcuts.insert("open browser".to_string(), "firefox".into());
+        settings.shortcuts.insert("search for {query}".to_string(), "xdg-open https://duckduckgo.com/?q={query}".into());
+        
+        assert_eq!(chained_commands("Open terminal and then open browser.", &settings, false), vec!["Open terminal", "open browser."]);
+        // Only one piece is a shortcut, so the template gets all of it
+        assert_eq!(chained_commands("search for salt and pepper", &settings, false), vec!["search for salt and pepper"]);
+        assert_eq!(chained_commands("open terminal and open browser and open terminal", &settings, false).len(), 2);
+        assert_eq!(chained_commands("open terminal and open browser", &settings, true).len(), 1);
+        settings.allow_command_chaining = false;
+        assert_eq!(chained_commands("open terminal and open browser", &settings, false).len(), 1);
+    }
+    
+    #[test]
+    fn test_assistant_name_gate() {
+        let mut settings = Settings::default();
+        assert_eq!(addressed_text("open terminal", &settings).as_deref(), Some("open terminal"));
+        
+        settings.assistant_name = Some("computer".to_string());
+        assert_eq!(addressed_text("Computer, open terminal", &settings).as_deref(), Some("open terminal"));
+        assert_eq!(addressed_text("open terminal", &settings).as_deref(), Some("open terminal"));
+        
+        settings.require_assistant_name = true;
+        assert_eq!(addressed_text("open terminal", &settings), None);
+        assert_eq!(addressed_text("open terminal, computer", &settings).as_deref(), Some("open terminal"));
+    }
+    
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_run_captured_reports_output_and_exit_status() {
+        assert_eq!(run_captured("echo hello", &CommandGuard::default()).unwrap(), "hello");
+        
+        let err = run_captured("echo partial; echo 'no such branch' >&2; exit 3", &CommandGuard::default()).unwrap_err();
+        match err.downcast_ref::<AssistantError>() {
+            Some(AssistantError::CommandFailed { status, output, .. }) => {
+                assert_eq!(*status, Some(3));
+                assert_eq!(output, "no such branch");
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(err.to_string().contains("exited with status 3: no such branch"));
+    }
+    
+    #[test]
+    fn test_initial_prompt_from_vocabulary_and_override() {
+        let mut shortcuts = HashMap::new();
+        shortcuts.insert("take screenshot".to_string(), Shortcut::from("screencapture -i shot.png"));
+        shortcuts.insert("open {app}".to_string(), Shortcut::from("open -a {app}"));
+        let settings = Settings {
+            shortcuts,
+            decoding_profile: DecodingProfile::Command,
+            vocabulary: vec!["Kubernetes".to_string(), " ".to_string()],
+            ..Settings::default()
+        };
+        assert_eq!(settings.transcribe_options().initial_prompt.as_deref(), Some("Kubernetes, take screenshot"));
+        
+        // Profiles without the shortcut prompt send none unless one is given explicitly
+        let dictation = Settings { decoding_profile: DecodingProfile::Dictation, ..settings.clone() };
+        assert_eq!(dictation.transcribe_options().initial_prompt, None);
+        let explicit = Settings { initial_prompt: Some("Meeting notes about Kubernetes.".to_string()), ..dictation };
+        assert_eq!(explicit.transcribe_options().initial_prompt.as_deref(), Some("Meeting notes about Kubernetes."));
+        let disabled = Settings { initial_prompt: Some(String::new()), ..settings };
+        assert_eq!(disabled.transcribe_options().initial_prompt, None);
+    }
+    
+    #[test]
+    fn test_dry_run_is_an_alias_for_preview_mode() {
+        let settings: Settings = serde_json::from_str(r#"{"dry_run": true, "shortcuts": {}, "whisper_model_path": "m.bin"}"#).unwrap();
+        assert!(settings.preview_mode);
+        
+        // An override from --dry-run survives reloading a file that doesn't set it
+        let current = Settings { dry_run: true, ..Settings::default() };
+        assert!(apply_reloaded_settings(&current, Settings::default()).previewing());
+    }
+    
+    #[test]
+    fn test_dry_run_is_never_saved() {
+        // As when "disable open terminal" saves the settings during a --dry-run session
+        let settings = Settings { dry_run: true, ..Settings::default() };
+        assert!(settings.previewing());
+        let saved: Settings = serde_json::from_str(&settings_json(&settings).unwrap()).unwrap();
+        assert!(!saved.preview_mode);
+        assert!(!saved.previewing());
+    }
+    
+    #[test]
+    fn test_preview_mode_never_executes() {
+        let voice = VoiceFeedback::new(false, None);
+        let mut settings = Settings { preview_mode: true, ..Settings::default() };
+        
+        let mut ran = false;
+        let outcome = dispatch("open terminal", "gnome-terminal", &settings, &voice, || {
+            ran = true;
+            Ok(())
+        });
+        assert_eq!(outcome, CommandOutcome::Suppressed);
+        assert!(!ran);
+        
+        settings.preview_mode = false;
+        assert_eq!(dispatch("open terminal", "gnome-terminal", &settings, &voice, || Ok(())), CommandOutcome::Executed);
+    }
+    
+    #[test]
+    fn test_blocked_commands_never_spawn() {
+        let voice = VoiceFeedback::new(false, None);
+        let settings = Settings { denylist: vec![r"\brm\s+-rf\b".to_string()], ..Settings::default() };
+        let guard = settings.command_guard().unwrap();
+        
+        let outcome = dispatch("clean up", "rm -rf /tmp/scratch", &settings, &voice, || execute_command("rm -rf /tmp/scratch", &guard));
+        assert_eq!(outcome, CommandOutcome::Blocked);
+        
+        // Each step of a macro is checked before any of them runs
+        let shortcut: Shortcut = serde_json::from_str(r#"{"steps": [{"command": "true"}, {"command": "rm -rf ~/scratch"}]}"#).unwrap();
+        let err = run_shortcut(&shortcut, &voice, &guard).unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(AssistantError::CommandBlocked { .. })));
+        
+        let invalid = Settings { allowlist: vec!["(".to_string()], allowlist_only: true, ..Settings::default() };
+        assert!(invalid.validate().is_err());
+    }
+    
+    #[test]
+    fn test_failed_macro_step_fails_the_command() {
+        let voice = VoiceFeedback::new(false, None);
+        let settings = Settings::default();
+        let guard = settings.command_guard().unwrap();
+        
+        // The outcome is known before the history entry is written
+        let shortcut: Shortcut = serde_json::from_str(r#"{"steps": [{"command": "true", "post_delay_ms": 1}, {"command": " "}]}"#).unwrap();
+        let outcome = dispatch("set up", &shortcut.display_command(), &settings, &voice, || run_shortcut(&shortcut, &voice, &guard));
+        assert_eq!(outcome, CommandOutcome::Failed);
+    }
+    
+    #[test]
+    fn test_confirmed_shortcut_starts_its_cooldown() {
+        let voice = VoiceFeedback::new(false, None);
+        let settings = Settings { cooldown_ms: 1000, ..Settings::default() };
+        let guard = settings.command_guard().unwrap();
+        let shortcut = Shortcut { confirm: true, ..Shortcut::from("true") };
+        let mut cooldowns = Cooldowns::default();
+        
+        assert!(!cooling_down(&cooldowns, "take screenshot", &shortcut, &settings, &voice));
+        run_confirmed_shortcut("take screenshot", &shortcut, &settings, &voice, &guard, &mut cooldowns);
+        // So saying it again straight away isn't asked about, let alone run
+        assert!(cooling_down(&cooldowns, "take screenshot", &shortcut, &settings, &voice));
+    }
+    
+    #[test]
+    fn test_nbest_selection_uses_the_dispatch_matcher() {
+        let contents = serde_json::json!({
+            "whisper_model_path": "m.bin",
+            "shortcuts": { "re:set volume to (\\d+)": "pactl set-sink-volume @DEFAULT_SINK@ $1%", "close window": "wmctrl -c :ACTIVE:" },
+            "homophones": [["close", "clothes"]],
+            "nbest": { "enabled": true, "count": 3, "confidence_weight": 1.0, "selection": "FirstMatch" },
+        });
+        let settings = parse_settings(&contents.to_string()).unwrap();
+        // Strict enough that only a real match counts
+        let suggestions = SmartSuggestions::with_thresholds(0.95, 0.7).with_homophones(Homophones::new(&settings.homophones));
+        let hypothesis = |text: &str, confidence: f32| Hypothesis { text: text.to_string(), confidence, segments: Vec::new() };
+        
+        // Only the runner-up fits the pattern, which a substring test on the key never sees
+        let volume = vec![hypothesis("set volume to thirty", 0.9), hypothesis("Set volume to 30.", 0.6)];
+        assert_eq!(pick_hypothesis(volume, &suggestions, &settings), "Set volume to 30.");
+        // "clothes" is a declared homophone of "close", so the runner-up dispatches
+        let close = vec![hypothesis("those windows", 0.9), hypothesis("clothes window", 0.5)];
+        assert_eq!(pick_hypothesis(close, &suggestions, &settings), "clothes window");
+    }
+    
+    #[test]
+    fn test_confidence_thresholds_must_be_fractions() {
+        let settings: Settings = serde_json::from_str(r#"{"whisper_model_path": "m.bin", "shortcuts": {}, "match_confidence": 0.95}"#).unwrap();
+        assert_eq!(settings.match_min_confidence, 0.95);
+        assert!(settings.validate().is_ok());
+        
+        // A percentage instead of a fraction would never match anything
+        let percent = Settings { match_min_confidence: 85.0, ..Settings::default() };
+        assert!(percent.validate().unwrap_err().to_string().contains("match_min_confidence"));
+        assert!(Settings { suggestion_min_confidence: -0.1, ..Settings::default() }.validate().is_err());
+    }
+    
+    #[test]
+    fn test_settle_delay_only_after_a_command() {
+        let mut settings = Settings::default();
+        assert_eq!(settle_delay(&settings, true), None);
+        
+        settings.post_command_settle_ms = 250;
+        assert_eq!(settle_delay(&settings, true), Some(Duration::from_millis(250)));
+        assert_eq!(settle_delay(&settings, false), None);
+    }
+    
+    #[test]
+    fn test_once_processes_a_single_recording() {
+        let recording = |samples: Vec<f32>| Recording { samples, sample_rate: 16000, channels: 1 };
+        let mut processed = Vec::new();
+        assert!(run_once(recording(vec![0.1, 0.2, 0.3]), |r| processed.push(r)));
+        assert_eq!(processed, vec![recording(vec![0.1, 0.2, 0.3])]);
+        
+        // An empty buffer never reaches transcription
+        assert!(!run_once(recording(Vec::new()), |r| processed.push(r)));
+        assert_eq!(processed.len(), 1);
+    }
+    
+    #[test]
+    fn test_recording_duration_from_sample_count() {
+        let mono = Recording { samples: vec![0.0; 8000], sample_rate: 16000, channels: 1 };
+        assert_eq!(mono.duration_ms(), 500);
+        
+        // Interleaved stereo at 48 kHz: 96000 samples are one second
+        let stereo = Recording { samples: vec![0.0; 96000], sample_rate: 48000, channels: 2 };
+        assert_eq!(stereo.duration_ms(), 1000);
+    }
+    
+    #[test]
+    fn test_recording_mode_setting() {
+        assert_eq!(Settings::default().recording_mode, RecordingMode::Toggle);
+        let settings: Settings = serde_json::from_str(r#"{"keybind": "F8", "shortcuts": {}, "whisper_model_path": "m.bin", "recording_mode": "Hold"}"#).unwrap();
+        assert_eq!(settings.recording_mode, RecordingMode::Hold);
+    }
+    
+    #[test]
+    fn test_parse_keybind_combos() {
+        assert_eq!(parse_keybind("F8").unwrap(), vec![Keycode::F8]);
+        assert_eq!(
+            parse_keybind("LCtrl + LShift+F8").unwrap(),
+            vec![Keycode::LControl, Keycode::LShift, Keycode::F8]
+        );
+        assert_eq!(parse_keybind("ctrl+shift+space").unwrap(), vec![Keycode::LControl, Keycode::LShift, Keycode::Space]);
+        
+        assert!(parse_keybind("LCtrl+Hyper").unwrap_err().contains("unknown key 'Hyper'"));
+        assert!(parse_keybind("LCtrl+").unwrap_err().contains("empty key"));
+        assert!(parse_keybind("F8+f8").unwrap_err().contains("twice"));
+    }
+    
+    #[test]
+    fn test_confirm_shortcuts_ask_unless_previewing() {
+        let shortcut = Shortcut { confirm: true, ..Shortcut::from("rm -rf ~/tmp/scratch") };
+        assert!(needs_confirmation(&shortcut, &Settings::default()));
+        assert!(!needs_confirmation(&Shortcut::from("firefox"), &Settings::default()));
+        assert!(!needs_confirmation(&shortcut, &Settings { preview_mode: true, ..Settings::default() }));
+    }
+    
+    #[test]
+    fn test_reload_applies_only_live_settings() {
+        let current = Settings::default();
+        let mut reloaded = Settings {
+            keybind: Some("LCtrl+F9".to_string()),
+            whisper_model_path: "other-model.bin".to_string(),
+            ..Settings::default()
+        };
+        reloaded.shortcuts.insert("open notes".to_string(), "notes".into());
+        reloaded.voice_feedback.confirm_commands = !current.voice_feedback.confirm_commands;
+        
+        let applied = apply_reloaded_settings(&current, reloaded);
+        assert_eq!(applied.keybind.as_deref(), Some("LCtrl+F9"));
+        assert!(applied.shortcuts.contains_key("open notes"));
+        assert_ne!(applied.voice_feedback.confirm_commands, current.voice_feedback.confirm_commands);
+        assert_eq!(applied.whisper_model_path, current.whisper_model_path);
+        assert!(!same_settings(&applied, &current));
+        assert!(same_settings(&current, &Settings::default()));
+    }
+    
+    #[test]
+    fn test_included_toggle_survives_its_own_reload() {
+        let mut reloaded = Settings::default();
+        reloaded.shortcuts.insert("open mail".to_string(), "thunderbird".into());
+        reloaded.shortcuts_file = Some(ShortcutFiles::One("shortcuts.toml".to_string()));
+        reloaded.own_shortcuts = Settings::default().shortcuts;
+        
+        // "disable open mail", then the reload its save_settings sets off
+        let mut current = apply_reloaded_settings(&Settings::default(), reloaded.clone());
+        current.shortcuts.get_mut("open mail").unwrap().enabled = false;
+        current.included_toggles.insert("open mail".to_string(), false);
+        let applied = apply_reloaded_settings(&current, reloaded.clone());
+        assert!(!applied.shortcuts["open mail"].enabled);
+        assert!(same_settings(&applied, &current));
+        
+        // A shortcut the settings file now defines itself isn't overridden
+        reloaded.own_shortcuts.insert("open mail".to_string(), "thunderbird".into());
+        assert!(apply_reloaded_settings(&current, reloaded).shortcuts["open mail"].enabled);
+    }
+    
+    #[test]
+    fn test_reload_with_voice_feedback_off_stops_speech() {
+        let voice = VoiceFeedback::new(false, None);
+        voice.set_enabled(true, None);
+        assert!(!voice.is_silent());
+        
+        let current = Settings::default();
+        let mut reloaded = Settings::default();
+        reloaded.voice_feedback.enabled = false;
+        apply_voice_settings(&voice, &current.voice_feedback, &reloaded.voice_feedback);
+        assert!(voice.is_silent());
+        voice.speak("Executed open terminal");
+        assert!(voice.finish(Duration::ZERO));
+    }
+    
+    #[test]
+    fn test_reload_keeps_keybind_when_new_one_is_invalid() {
+        let current = Settings::default();
+        let reloaded = Settings { keybind: Some("LCtrl+Hyper".to_string()), ..Settings::default() };
+        assert_eq!(apply_reloaded_settings(&current, reloaded).keybind, current.keybind);
+    }
+    
+    #[test]
+    fn test_shortcuts_file_is_merged_and_kept_apart() {
+        let dir = std::env::temp_dir();
+        let json = dir.join(format!("stt_whisper_shortcuts_{}.json", std::process::id()));
+        let toml = dir.join(format!("stt_whisper_shortcuts_{}.toml", std::process::id()));
+        fs::write(&json, r#"{"open browser": "chromium", "open mail": {"command": "thunderbird", "enabled": false}}"#).unwrap();
+        fs::write(&toml, "\"open browser\" = \"firefox\"\n").unwrap();
+        
+        let contents = serde_json::json!({
+            "whisper_model_path": "m.bin",
+            "shortcuts": { "open terminal": "xterm", "open browser": "safari" },
+            "shortcuts_file": [json, toml],
+        });
+        let settings = parse_settings(&contents.to_string()).unwrap();
+        assert_eq!(settings.shortcuts.len(), 3);
+        assert_eq!(settings.shortcuts["open browser"].command, "firefox");
+        assert!(!settings.shortcuts["open mail"].enabled);
+        // Only the settings file's own shortcuts go back into it
+        assert_eq!(settings.own_shortcuts.len(), 2);
+        assert_eq!(settings.own_shortcuts["open browser"].command, "safari");
+        
+        fs::remove_file(&toml).unwrap();
+        assert!(parse_settings(&contents.to_string()).is_err());
+        let _ = fs::remove_file(&json);
+    }
+    
+    #[test]
+    fn test_single_keybind_migrates_to_command_binding() {
+        let old: Settings = serde_json::from_str(r#"{"keybind": "F12", "shortcuts": {}, "whisper_model_path": "m.bin"}"#).unwrap();
+        assert_eq!(old.keybinds(), vec![Keybind { keys: "F12".to_string(), action: KeybindAction::Command }]);
+        let bare: Settings = serde_json::from_str(r#"{"shortcuts": {}, "whisper_model_path": "m.bin"}"#).unwrap();
+        assert_eq!(bare.keybinds()[0].keys, DEFAULT_KEYBIND);
+        
+        let new: Settings = serde_json::from_str(
+            r#"{"keybinds": [{"keys": "F8", "action": "Command"}, {"keys": "F9", "action": "Dictation"}], "shortcuts": {}, "whisper_model_path": "m.bin"}"#,
+        )
+        .unwrap();
+        assert_eq!(new.keybinds().len(), 2);
+        assert!(new.validate().is_ok());
+        
+        let mut hotkeys = build_hotkeys(parse_keybinds(&new.keybinds()).unwrap(), Duration::from_millis(150));
+        assert_eq!(hotkeys.update(&[Keycode::F9], Instant::now()), Some(HotkeyAction::ToggleRecording { dictation: true }));
+    }
+    
+    #[test]
+    fn test_keybinds_need_a_recording_binding_and_distinct_keys() {
+        let bind = |keys: &str, action| Keybind { keys: keys.to_string(), action };
+        assert!(parse_keybinds(&[bind("F5", KeybindAction::Reload)]).unwrap_err().contains("no keybind starts recording"));
+        assert!(parse_keybinds(&[bind("LCtrl+F8", KeybindAction::Command), bind("F8+LCtrl", KeybindAction::Dictation)])
+            .unwrap_err()
+            .contains("more than one action"));
+        
+        // Binding F1 overrides the built-in statistics key
+        let bindings = parse_keybinds(&[bind("F8", KeybindAction::Command), bind("F1", KeybindAction::Reload)]).unwrap();
+        let mut hotkeys = build_hotkeys(bindings, Duration::from_millis(150));
+        assert_eq!(hotkeys.update(&[Keycode::F1], Instant::now()), Some(HotkeyAction::Reload));
+    }
+    
+    #[test]
+    fn test_cancel_key() {
+        let mut settings = Settings::default();
+        assert_eq!(settings.cancel_keys(), Ok(Some(vec![Keycode::Escape])));
+        assert!(settings.validate().is_ok());
+        
+        settings.cancel_key = " ".to_string();
+        assert_eq!(settings.cancel_keys(), Ok(None));
+        settings.cancel_key = "Esc+Tab".to_string();
+        assert!(settings.validate().is_err());
+        // It can't also start a recording
+        settings.cancel_key = "F8".to_string();
+        assert!(settings.validate().is_err());
+    }
+    
+    #[test]
+    fn test_recording_is_cut_at_the_limit() {
+        let recorder = AudioRecorder::new(None).with_max_duration(Duration::from_secs(1));
+        *recorder.format.lock().unwrap() = (10, 2);
+        recorder.arm();
+        recorder.push_samples(&[0.1; 19]);
+        assert!(!recorder.check_limit());
+        assert!(!recorder.should_stop());
+        
+        recorder.push_samples(&[0.1; 6]);
+        assert!(recorder.check_limit());
+        assert!(recorder.should_stop());
+        assert!(!recorder.is_recording());
+        assert_eq!(recorder.stop_recording().samples.len(), 10);
+        
+        assert!(!AudioRecorder::new(None).check_limit());
+    }
+    
+    #[test]
+    fn test_file_recording_matches_live_preparation() {
+        // Half a second of silence around a second of tone, as a 44.1 kHz stereo file
+        let path = std::env::temp_dir().join(format!("stt_whisper_file_{}.wav", std::process::id()));
+        let spec = hound::WavSpec { channels: 2, sample_rate: 44100, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for i in 0..2 * 44100 {
+            let sample = if (22050..66150).contains(&i) { 8000 } else { 0 };
+            writer.write_sample(sample as i16).unwrap();
+            writer.write_sample(sample as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        let path_str = path.to_str().unwrap();
+        
+        let recording = file_recording(path_str, &Settings { trim_silence: false, ..Settings::default() }).unwrap();
+        assert_eq!((recording.sample_rate, recording.channels), (16000, 1));
+        assert!(recording.duration_ms().abs_diff(2000) <= 1);
+        
+        // Trimmed down to the tone and the recorder's padding
+        let trimmed = file_recording(path_str, &Settings { trim_silence: true, silence_threshold: 0.01, ..Settings::default() }).unwrap();
+        assert!(trimmed.duration_ms() < 2000 && trimmed.duration_ms() >= 1000, "{} ms", trimmed.duration_ms());
+        
+        let _ = fs::remove_file(&path);
+        assert!(file_recording(path_str, &Settings::default()).is_err());
+    }
+    
+    #[test]
+    fn test_rapid_stops_never_hang_the_capture_loop() {
+        let recorder = AudioRecorder::new(None);
+        let (done, finished) = std::sync::mpsc::channel();
+        let stress = thread::spawn(move || {
+            for round in 0..100 {
+                recorder.arm();
+                let feeder = recorder.clone();
+                let capture = thread::spawn(move || {
+                    // Stands in for the audio callback and the stream thread
+                    feeder.push_samples(&[0.1; 64]);
+                    feeder.hold_open();
+                    feeder.push_samples(&[0.1; 64]);
+                });
+                // Every other round stops before the capture thread has even started
+                if round % 2 == 1 {
+                    thread::yield_now();
+                }
+                let recording = recorder.stop_recording();
+                capture.join().unwrap();
+                assert!(recording.samples.len() <= 64);
+                assert!(!recorder.is_recording());
+            }
+            done.send(()).unwrap();
+        });
+        finished.recv_timeout(Duration::from_secs(10)).expect("capture loop did not stop");
+        stress.join().unwrap();
+    }
+    
+    #[test]
+    fn test_stop_recording_downmixes_stereo() {
+        let recorder = AudioRecorder::new(None);
+        *recorder.format.lock().unwrap() = (48000, 2);
+        recorder.samples.lock().unwrap().extend_from_slice(&[1.0, 0.0, 0.5, -0.5, -1.0, -0.25]);
+        
+        let recording = recorder.stop_recording();
+        assert_eq!(recording.samples, vec![0.5, 0.0, -0.625]);
+        assert_eq!(recording.channels, 1);
+        assert_eq!(recording.sample_rate, 48000);
+    }
+    
+    #[test]
+    fn test_peek_since_copies_new_frames() {
+        let recorder = AudioRecorder::new(None);
+        *recorder.format.lock().unwrap() = (48000, 2);
+        recorder.arm();
+        recorder.push_samples(&[1.0, 0.0, 0.5, -0.5, -1.0]);
+        
+        // The half frame waits for its other channel
+        assert_eq!(recorder.peek_since(0).samples, vec![0.5, 0.0]);
+        recorder.push_samples(&[-0.25]);
+        assert_eq!(recorder.peek_since(2).samples, vec![-0.625]);
+        assert!(recorder.peek_since(3).samples.is_empty());
+        // Nothing was taken from the recording
+        assert_eq!(recorder.stop_recording().samples.len(), 3);
+        assert!(recorder.peek_since(3).samples.is_empty());
+    }
+    
+    #[test]
+    fn test_stop_recording_trims_silence_when_enabled() {
+        let recorder = AudioRecorder::new(None).with_trim_silence(0.01);
+        *recorder.format.lock().unwrap() = (16000, 1);
+        recorder.arm();
+        recorder.push_samples(&[0.0; 16000]);
+        recorder.push_samples(&[0.5; 3200]);
+        recorder.push_samples(&[0.0; 16000]);
+        
+        // 200 ms of speech plus 200 ms of padding on each side
+        let recording = recorder.stop_recording();
+        assert_eq!(recording.duration_ms(), 600);
+        assert!(recording.samples[3200..6400].iter().all(|&s| s == 0.5));
+    }
+    
+    #[test]
+    fn test_resample_48k_sine_to_16k() {
+        let sine: Vec<f32> = (0..48000)
+            .map(|i| (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 48000.0).sin())
+            .collect();
+        assert_eq!(resample_to_16k(&sine, 48000).len(), sine.len() / 3);
+        
+        // Already at 16 kHz it passes through unchanged
+        assert_eq!(resample_to_16k(&sine[..1600], 16000), sine[..1600].to_vec());
+    }
+    
+    #[test]
+    fn test_default_config_round_trips() {
+        let printed = serde_json::to_string_pretty(&Settings::default()).unwrap();
+        let parsed: Settings = serde_json::from_str(&printed).unwrap();
+        assert!(parsed.validate().is_ok());
+        // Compare as values since shortcut order isn't stable
+        assert_eq!(
+            serde_json::to_value(&parsed).unwrap(),
+            serde_json::to_value(Settings::default()).unwrap()
+        );
+    }
 }
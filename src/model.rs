@@ -0,0 +1,330 @@
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Seek, SeekFrom};
+#[cfg(not(target_os = "linux"))]
+use std::process::Command;
+
+use log::warn;
+
+use crate::error::AssistantError;
+
+pub const MB: u64 = 1024 * 1024;
+
+// whisper.cpp needs the weights plus compute/KV buffers, which grow roughly with model size
+const MEMORY_OVERHEAD_FACTOR: f64 = 1.25;
+const MEMORY_BASE_OVERHEAD: u64 = 200 * MB;
+// Below this much headroom we still load, but warn that the system may start swapping
+const MEMORY_WARN_FACTOR: f64 = 1.2;
+
+// "ggml" read as a little-endian u32
+const GGML_MAGIC: u32 = 0x6767_6d6c;
+// n_vocab, audio ctx/state/head/layer, text ctx/state/head/layer, n_mels, ftype
+const GGML_HPARAMS_BYTES: u64 = 11 * 4;
+
+#[derive(Debug, PartialEq)]
+pub enum MemoryVerdict {
+    Ok,
+    Tight,
+    Insufficient,
+}
+
+pub fn estimate_required_memory(model_bytes: u64) -> u64 {
+    (model_bytes as f64 * MEMORY_OVERHEAD_FACTOR) as u64 + MEMORY_BASE_OVERHEAD
+}
+
+pub fn evaluate_memory(required_bytes: u64, available_bytes: u64) -> MemoryVerdict {
+    if available_bytes < required_bytes {
+        MemoryVerdict::Insufficient
+    } else if (available_bytes as f64) < required_bytes as f64 * MEMORY_WARN_FACTOR {
+        MemoryVerdict::Tight
+    } else {
+        MemoryVerdict::Ok
+    }
+}
+
+pub fn check_memory(model_path: &str) -> Result<(), AssistantError> {
+    let model_bytes = match fs::metadata(model_path) {
+        Ok(meta) => meta.len(),
+        Err(_) => return Ok(()),
+    };
+    let available_bytes = match available_memory() {
+        Some(bytes) => bytes,
+        None => {
+            warn!("Could not determine available memory, skipping model memory check.");
+            return Ok(());
+        }
+    };
+
+    let required_bytes = estimate_required_memory(model_bytes);
+    match evaluate_memory(required_bytes, available_bytes) {
+        MemoryVerdict::Ok => Ok(()),
+        MemoryVerdict::Tight => {
+            warn!(
+                "Warning: model needs about {} MB and only {} MB is available. Loading may be slow or fail.",
+                required_bytes / MB,
+                available_bytes / MB
+            );
+            Ok(())
+        }
+        MemoryVerdict::Insufficient => Err(AssistantError::InsufficientMemory {
+            model_path: model_path.to_string(),
+            required_bytes,
+            available_bytes,
+        }),
+    }
+}
+
+// A partly downloaded model only fails deep inside whisper.cpp with an unhelpful error,
+// so walk the ggml layout first and make sure every tensor is actually in the file
+pub fn check_model_file(model_path: &str) -> Result<(), AssistantError> {
+    let incomplete = |reason: String| AssistantError::IncompleteModel {
+        model_path: model_path.to_string(),
+        reason,
+    };
+    let file = File::open(model_path).map_err(|e| incomplete(e.to_string()))?;
+    let file_len = file.metadata().map_err(|e| incomplete(e.to_string()))?.len();
+    validate_ggml(&mut BufReader::new(file), file_len).map_err(incomplete)
+}
+
+fn validate_ggml<R: Read + Seek>(reader: &mut R, file_len: u64) -> Result<(), String> {
+    if read_i32(reader).map_err(|_| "file is empty".to_string())? as u32 != GGML_MAGIC {
+        return Err("not a ggml model file".to_string());
+    }
+    skip(reader, file_len, GGML_HPARAMS_BYTES)?;
+
+    // Mel filterbank
+    let n_mel = read_count(reader)?;
+    let n_fft = read_count(reader)?;
+    let mel_bytes = n_mel.checked_mul(n_fft).and_then(|n| n.checked_mul(4));
+    skip(reader, file_len, mel_bytes.ok_or("mel filterbank has a corrupt header")?)?;
+
+    // Vocabulary: length-prefixed tokens
+    for _ in 0..read_count(reader)? {
+        let len = read_count(reader)?;
+        skip(reader, file_len, len)?;
+    }
+
+    let mut tensors = 0;
+    while reader.stream_position().map_err(|e| e.to_string())? < file_len {
+        let n_dims = read_count(reader)?;
+        let name_len = read_count(reader)?;
+        let ttype = read_i32(reader).map_err(|_| truncated())?;
+        if !(1..=4).contains(&n_dims) || name_len > 1024 {
+            return Err(format!("tensor {} has a corrupt header", tensors));
+        }
+
+        let corrupt = || format!("tensor {} has a corrupt header", tensors);
+        let mut elements: u64 = 1;
+        for _ in 0..n_dims {
+            elements = elements.checked_mul(read_count(reader)?).ok_or_else(corrupt)?;
+        }
+        let mut name = vec![0; name_len as usize];
+        reader.read_exact(&mut name).map_err(|_| truncated())?;
+
+        // Types this check doesn't know about can't be measured; trust the rest of the file
+        let Some((block, block_bytes)) = block_size(ttype) else {
+            return Ok(());
+        };
+        let bytes = (elements / block).checked_mul(block_bytes).ok_or_else(corrupt)?;
+        skip(reader, file_len, bytes)
+            .map_err(|_| format!("file ends inside tensor '{}'", String::from_utf8_lossy(&name)))?;
+        tensors += 1;
+    }
+
+    if tensors == 0 {
+        return Err(truncated());
+    }
+    Ok(())
+}
+
+// (elements per block, bytes per block) for each ggml tensor type
+fn block_size(ttype: i32) -> Option<(u64, u64)> {
+    let size = match ttype {
+        0 => (1, 4),   // f32
+        1 => (1, 2),   // f16
+        2 => (32, 18), // q4_0
+        3 => (32, 20), // q4_1
+        6 => (32, 22), // q5_0
+        7 => (32, 24), // q5_1
+        8 => (32, 34), // q8_0
+        _ => return None,
+    };
+    Some(size)
+}
+
+fn truncated() -> String {
+    "file ends early".to_string()
+}
+
+fn read_i32<R: Read>(reader: &mut R) -> std::io::Result<i32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(i32::from_le_bytes(bytes))
+}
+
+fn read_count<R: Read>(reader: &mut R) -> Result<u64, String> {
+    let value = read_i32(reader).map_err(|_| truncated())?;
+    u64::try_from(value).map_err(|_| format!("negative size {} in header", value))
+}
+
+fn skip<R: Seek>(reader: &mut R, file_len: u64, bytes: u64) -> Result<(), String> {
+    let position = reader.stream_position().map_err(|e| e.to_string())?;
+    if position.checked_add(bytes).is_none_or(|end| end > file_len) {
+        return Err(truncated());
+    }
+    reader.seek(SeekFrom::Current(bytes as i64)).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn available_memory() -> Option<u64> {
+    let contents = fs::read_to_string("/proc/meminfo").ok()?;
+    parse_meminfo_available(&contents)
+}
+
+#[cfg(target_os = "macos")]
+fn available_memory() -> Option<u64> {
+    let output = Command::new("vm_stat").output().ok()?;
+    parse_vm_stat_available(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(target_os = "windows")]
+fn available_memory() -> Option<u64> {
+    let output = Command::new("powershell")
+        .args(["-NoProfile", "-Command", "(Get-CimInstance Win32_OperatingSystem).FreePhysicalMemory"])
+        .output()
+        .ok()?;
+    let kb: u64 = String::from_utf8_lossy(&output.stdout).trim().parse().ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn available_memory() -> Option<u64> {
+    None
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_meminfo_available(contents: &str) -> Option<u64> {
+    contents
+        .lines()
+        .find(|line| line.starts_with("MemAvailable:"))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|kb| kb.parse::<u64>().ok())
+        .map(|kb| kb * 1024)
+}
+
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_vm_stat_available(contents: &str) -> Option<u64> {
+    let page_size = contents
+        .lines()
+        .next()
+        .and_then(|line| line.split("page size of ").nth(1))
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|size| size.parse::<u64>().ok())
+        .unwrap_or(4096);
+
+    let mut pages = 0;
+    for line in contents.lines() {
+        if line.starts_with("Pages free:") || line.starts_with("Pages inactive:") || line.starts_with("Pages speculative:") {
+            let count = line.split(':').nth(1)?.trim().trim_end_matches('.');
+            pages += count.parse::<u64>().ok()?;
+        }
+    }
+
+    if pages == 0 {
+        None
+    } else {
+        Some(pages * page_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_required_memory() {
+        // base model (~142 MB) should fit comfortably under half a gigabyte
+        let base = estimate_required_memory(142 * MB);
+        assert!(base > 142 * MB && base < 512 * MB);
+
+        // large model (~2.9 GB) needs more than its file size
+        assert!(estimate_required_memory(2900 * MB) > 3500 * MB);
+    }
+
+    #[test]
+    fn test_evaluate_memory_thresholds() {
+        let required = estimate_required_memory(2900 * MB);
+
+        assert_eq!(evaluate_memory(required, 16 * 1024 * MB), MemoryVerdict::Ok);
+        assert_eq!(evaluate_memory(required, required + required / 10), MemoryVerdict::Tight);
+        assert_eq!(evaluate_memory(required, 2 * 1024 * MB), MemoryVerdict::Insufficient);
+    }
+
+    // Header, a 2x3 mel filterbank, a two-token vocabulary and one 4x4 f32 tensor
+    fn tiny_ggml_model() -> Vec<u8> {
+        let mut header = vec![GGML_MAGIC as i32];
+        header.extend([1; 11]);
+        header.extend([2, 3, 0, 0, 0, 0, 0, 0]);
+        let mut bytes: Vec<u8> = header.iter().flat_map(|v| v.to_le_bytes()).collect();
+
+        bytes.extend(2i32.to_le_bytes());
+        for token in ["hi", "there"] {
+            bytes.extend((token.len() as i32).to_le_bytes());
+            bytes.extend(token.as_bytes());
+        }
+
+        let name = b"encoder.conv1.weight";
+        for value in [2, name.len() as i32, 0, 4, 4] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend(name);
+        bytes.extend([0; 16 * 4]);
+        bytes
+    }
+
+    fn validate(bytes: &[u8]) -> Result<(), String> {
+        validate_ggml(&mut std::io::Cursor::new(bytes), bytes.len() as u64)
+    }
+
+    #[test]
+    fn test_truncated_model_is_rejected() {
+        let model = tiny_ggml_model();
+        assert_eq!(validate(&model), Ok(()));
+
+        // Interrupted download: the last tensor is cut short
+        let error = validate(&model[..model.len() - 10]).unwrap_err();
+        assert!(error.contains("encoder.conv1.weight"), "{}", error);
+
+        // Cut off before any tensor, or not a model at all
+        assert!(validate(&model[..40]).is_err());
+        assert_eq!(validate(b"<html>404 Not Found</html>"), Err("not a ggml model file".to_string()));
+    }
+
+    #[test]
+    fn test_huge_dimensions_are_corrupt() {
+        let model = tiny_ggml_model();
+        let set = |bytes: &mut Vec<u8>, offset: usize, value: i32| bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+
+        // n_mel and n_fft follow the magic and the 11 hparams; two i32s just fit in a u64
+        let mut mel = model.clone();
+        set(&mut mel, 48, i32::MAX);
+        set(&mut mel, 52, i32::MAX);
+        assert_eq!(validate(&mel), Err(truncated()));
+
+        // A 4-D tensor whose element count doesn't fit in a u64
+        let tensor = model.len() - 16 * 4 - 20 - 5 * 4;
+        let mut huge = model[..tensor].to_vec();
+        for value in [4, 1, 0, i32::MAX, i32::MAX, i32::MAX, i32::MAX] {
+            huge.extend(value.to_le_bytes());
+        }
+        huge.extend(b"x");
+        assert_eq!(validate(&huge), Err("tensor 0 has a corrupt header".to_string()));
+    }
+
+    #[test]
+    fn test_parse_meminfo_available() {
+        let meminfo = "MemTotal:       16314372 kB\nMemFree:         1234567 kB\nMemAvailable:    8000000 kB\n";
+        assert_eq!(parse_meminfo_available(meminfo), Some(8000000 * 1024));
+        assert_eq!(parse_meminfo_available("MemTotal: 1 kB\n"), None);
+    }
+}
@@ -0,0 +1,104 @@
+use std::process::Command;
+
+use crate::shortcuts::Shortcut;
+
+#[derive(Debug, PartialEq)]
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+    pub icon: Option<String>,
+}
+
+// A shortcut's own icon wins over the global default
+pub fn notification_for(phrase: &str, shortcut: &Shortcut, default_icon: Option<&str>) -> Notification {
+    Notification {
+        title: "Voice Assistant".to_string(),
+        body: format!("Executed {}", phrase),
+        icon: shortcut.icon.as_deref().or(default_icon).map(|icon| icon.to_string()),
+    }
+}
+
+pub fn cue_for<'a>(shortcut: &'a Shortcut, default_cue: Option<&'a str>) -> Option<&'a str> {
+    shortcut.cue.as_deref().or(default_cue)
+}
+
+// Both helpers shell out to the platform's own tools and never block the caller
+pub fn show(notification: &Notification) -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(target_os = "linux")]
+    {
+        let mut command = Command::new("notify-send");
+        if let Some(icon) = &notification.icon {
+            command.args(["-i", icon]);
+        }
+        command.arg(&notification.title).arg(&notification.body).spawn()?;
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        // Notification Center always uses the app's icon
+        let script = format!(
+            "display notification {:?} with title {:?}",
+            notification.body, notification.title
+        );
+        Command::new("osascript").args(["-e", &script]).spawn()?;
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        let script = format!(
+            "[void][System.Reflection.Assembly]::LoadWithPartialName('System.Windows.Forms'); \
+             $n = New-Object System.Windows.Forms.NotifyIcon; $n.Icon = [System.Drawing.SystemIcons]::Information; \
+             $n.Visible = $true; $n.ShowBalloonTip(3000, '{}', '{}', 'Info'); Start-Sleep 4; $n.Dispose()",
+            notification.title.replace('\'', "''"),
+            notification.body.replace('\'', "''")
+        );
+        Command::new("powershell").args(["-NoProfile", "-Command", &script]).spawn()?;
+    }
+
+    Ok(())
+}
+
+pub fn play_cue(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(target_os = "linux")]
+    {
+        if Command::new("paplay").arg(path).spawn().is_err() {
+            Command::new("aplay").args(["-q", path]).spawn()?;
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        Command::new("afplay").arg(path).spawn()?;
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        let script = format!("(New-Object Media.SoundPlayer '{}').PlaySync()", path.replace('\'', "''"));
+        Command::new("powershell").args(["-NoProfile", "-Command", &script]).spawn()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_per_command_icon_and_cue_override_defaults() {
+        let mut screenshot = Shortcut::from("gnome-screenshot");
+        screenshot.icon = Some("camera-photo".to_string());
+        screenshot.cue = Some("sounds/shutter.wav".to_string());
+        let terminal = Shortcut::from("gnome-terminal");
+
+        let notification = notification_for("take screenshot", &screenshot, Some("dialog-information"));
+        assert_eq!(notification.icon.as_deref(), Some("camera-photo"));
+        assert_eq!(notification.body, "Executed take screenshot");
+        assert_eq!(cue_for(&screenshot, Some("sounds/done.wav")), Some("sounds/shutter.wav"));
+
+        let notification = notification_for("open terminal", &terminal, Some("dialog-information"));
+        assert_eq!(notification.icon.as_deref(), Some("dialog-information"));
+        assert_eq!(cue_for(&terminal, Some("sounds/done.wav")), Some("sounds/done.wav"));
+        assert_eq!(cue_for(&terminal, None), None);
+    }
+}
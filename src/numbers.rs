@@ -0,0 +1,71 @@
+// Spoken numbers as Whisper writes them: "3", "Three.", "twenty-one"
+
+use crate::builtins::normalize;
+
+const UNITS: &[&str] = &[
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten",
+    "eleven", "twelve", "thirteen", "fourteen", "fifteen", "sixteen", "seventeen", "eighteen", "nineteen",
+];
+const TENS: &[&str] = &["", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety"];
+
+// The whole transcription must be a number; "open three files" is not
+pub fn parse_spoken_number(text: &str) -> Option<u64> {
+    let normalized = normalize(text);
+    if let Ok(n) = normalized.replace(' ', "").parse::<u64>() {
+        return Some(n);
+    }
+
+    let mut total = 0;
+    let mut current = 0;
+    let mut seen = false;
+    for word in normalized.split_whitespace() {
+        if let Some(n) = UNITS.iter().position(|w| *w == word) {
+            current += n as u64;
+        } else if let Some(n) = TENS.iter().position(|w| !w.is_empty() && *w == word) {
+            current += n as u64 * 10;
+        } else if word == "hundred" {
+            current = current.max(1) * 100;
+        } else if word == "thousand" {
+            total += current.max(1) * 1000;
+            current = 0;
+        } else if word == "and" && seen {
+            continue;
+        } else {
+            return None;
+        }
+        seen = true;
+    }
+
+    if seen {
+        Some(total + current)
+    } else {
+        None
+    }
+}
+
+// Fills `{n}` in the numeric command template when the transcription is a bare number
+pub fn numeric_command(template: &str, text: &str) -> Option<String> {
+    parse_spoken_number(text).map(|n| template.replace("{n}", &n.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_spoken_number() {
+        assert_eq!(parse_spoken_number("Three."), Some(3));
+        assert_eq!(parse_spoken_number("12"), Some(12));
+        assert_eq!(parse_spoken_number("twenty-one"), Some(21));
+        assert_eq!(parse_spoken_number("one hundred and five"), Some(105));
+        assert_eq!(parse_spoken_number(""), None);
+        assert_eq!(parse_spoken_number("open three files"), None);
+    }
+
+    #[test]
+    fn test_numeric_command_template() {
+        let template = "wmctrl -s {n}";
+        assert_eq!(numeric_command(template, "three"), Some("wmctrl -s 3".to_string()));
+        assert_eq!(numeric_command(template, "open terminal"), None);
+    }
+}
@@ -0,0 +1,62 @@
+// Watches the settings file, and any shortcut files it includes, so edits apply
+// without restarting the assistant
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+pub struct SettingsWatcher {
+    // Watching stops when this is dropped
+    watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    dirs: Vec<PathBuf>,
+    file_names: Vec<OsString>,
+}
+
+impl SettingsWatcher {
+    pub fn new(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = SettingsWatcher {
+            watcher: notify::recommended_watcher(sender)?,
+            events,
+            dirs: Vec::new(),
+            file_names: Vec::new(),
+        };
+        watcher.watch_file(path)?;
+        Ok(watcher)
+    }
+
+    // Adds another file; watching one twice is harmless
+    pub fn watch_file(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let path = Path::new(path);
+        let file_name = path.file_name().ok_or_else(|| format!("{} is not a file", path.display()))?.to_os_string();
+        // Many editors save by writing a new file and renaming it over the old one,
+        // which a watch on the file itself would miss
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+
+        if !self.dirs.iter().any(|watched| watched == dir) {
+            self.watcher.watch(dir, RecursiveMode::NonRecursive)?;
+            self.dirs.push(dir.to_path_buf());
+        }
+        if !self.file_names.contains(&file_name) {
+            self.file_names.push(file_name);
+        }
+        Ok(())
+    }
+
+    // Whether a watched file was written since the last call; never blocks
+    pub fn changed(&self) -> bool {
+        let mut changed = false;
+        for event in self.events.try_iter().flatten() {
+            changed |= self.file_names.iter().any(|file_name| is_write_to(&event, file_name));
+        }
+        changed
+    }
+}
+
+fn is_write_to(event: &Event, file_name: &OsString) -> bool {
+    (event.kind.is_modify() || event.kind.is_create())
+        && event.paths.iter().any(|path| path.file_name() == Some(file_name.as_os_str()))
+}
@@ -0,0 +1,317 @@
+// A small HTTP/1.1 service for other programs on this machine, built with the
+// `http-server` feature. One request per connection, one connection at a time:
+//   POST /transcribe          an audio file (WAV, FLAC, MP3 or OGG) as the body -> {"text": "..."}
+//   GET  /history?count=N     the last N history entries, newest first (20 by default)
+// There is no authentication, which is why it listens on localhost unless told otherwise.
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::{debug, warn};
+use serde_json::{json, Value};
+use whisper_rs::WhisperContext;
+
+use crate::history::CommandHistory;
+use crate::transcribe::{self, TranscribeOptions};
+
+const MAX_BODY_BYTES: usize = 64 * 1024 * 1024;
+const MAX_HEADER_LINES: usize = 100;
+const DEFAULT_HISTORY_COUNT: usize = 20;
+// A client that stops sending mustn't hold up everyone else
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl Response {
+    fn error(status: u16, message: &str) -> Self {
+        Response { status, body: json!({ "error": message }) }
+    }
+}
+
+// Only what the two routes need: the request line, Content-Length and the body.
+// Chunked bodies aren't supported, so clients have to send Content-Length.
+pub fn read_request<R: BufRead>(reader: &mut R) -> Result<Request, String> {
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|e| e.to_string())?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err("malformed request line".to_string());
+    };
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, Some(query.to_string())),
+        None => (target, None),
+    };
+
+    let mut content_length = 0;
+    let mut header_lines = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).map_err(|e| e.to_string())? == 0 {
+            return Err("connection closed before the end of the headers".to_string());
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        header_lines += 1;
+        if header_lines > MAX_HEADER_LINES {
+            return Err("too many headers".to_string());
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().map_err(|_| format!("invalid Content-Length '{}'", value.trim()))?;
+            }
+        }
+    }
+
+    if content_length > MAX_BODY_BYTES {
+        return Err(format!("body is larger than {} MB", MAX_BODY_BYTES / (1024 * 1024)));
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).map_err(|e| format!("incomplete body: {}", e))?;
+
+    Ok(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query,
+        body,
+    })
+}
+
+pub fn handle<T>(request: &Request, transcribe: T, history: &Mutex<CommandHistory>) -> Response
+where
+    T: FnOnce(&[u8]) -> Result<String, Box<dyn std::error::Error>>,
+{
+    match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/transcribe") => {
+            if request.body.is_empty() {
+                return Response::error(400, "send the audio file as the request body");
+            }
+            match transcribe(&request.body) {
+                Ok(text) => Response { status: 200, body: json!({ "text": text }) },
+                Err(e) => Response::error(400, &format!("couldn't transcribe the audio: {}", e)),
+            }
+        }
+        ("GET", "/history") => {
+            let count = match history_count(request.query.as_deref()) {
+                Ok(count) => count,
+                Err(e) => return Response::error(400, &e),
+            };
+            let history = history.lock().unwrap();
+            match serde_json::to_value(history.get_recent_entries(count)) {
+                Ok(entries) => Response { status: 200, body: entries },
+                Err(e) => Response::error(500, &e.to_string()),
+            }
+        }
+        (_, "/transcribe") | (_, "/history") => Response::error(405, "method not allowed"),
+        _ => Response::error(404, "not found"),
+    }
+}
+
+fn history_count(query: Option<&str>) -> Result<usize, String> {
+    let count = query
+        .into_iter()
+        .flat_map(|query| query.split('&'))
+        .find_map(|pair| pair.strip_prefix("count="));
+    match count {
+        Some(count) => count.parse().map_err(|_| format!("count must be a number, not '{}'", count)),
+        None => Ok(DEFAULT_HISTORY_COUNT),
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    }
+}
+
+fn write_response<W: Write>(writer: &mut W, response: &Response) -> io::Result<()> {
+    let body = response.body.to_string();
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason(response.status),
+        body.len(),
+        body
+    )?;
+    writer.flush()
+}
+
+// Returns the bound address, so port 0 picks a free port
+pub fn spawn(
+    address: &str,
+    whisper: Arc<WhisperContext>,
+    options: TranscribeOptions,
+    history: Arc<Mutex<CommandHistory>>,
+    temp_dir: PathBuf,
+) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(address)?;
+    let bound = listener.local_addr()?;
+    if !bound.ip().is_loopback() {
+        warn!("The HTTP server on {} accepts connections from other machines and has no authentication.", bound);
+    }
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = serve(stream, &whisper, &options, &history, &temp_dir) {
+                        debug!("HTTP connection failed: {}", e);
+                    }
+                }
+                Err(e) => warn!("HTTP connection failed: {}", e),
+            }
+        }
+    });
+    Ok(bound)
+}
+
+fn serve(
+    stream: TcpStream,
+    whisper: &WhisperContext,
+    options: &TranscribeOptions,
+    history: &Mutex<CommandHistory>,
+    temp_dir: &Path,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let response = match read_request(&mut reader) {
+        Ok(request) => {
+            debug!("HTTP {} {}", request.method, request.path);
+            handle(&request, |audio| transcribe_body(whisper, audio, options, temp_dir), history)
+        }
+        Err(e) => Response::error(400, &e),
+    };
+    write_response(&mut &stream, &response)
+}
+
+// The decoders read files, so the body goes through a temporary one. No extension, so
+// the format is recognised from the contents.
+fn transcribe_body(whisper: &WhisperContext, audio: &[u8], options: &TranscribeOptions, temp_dir: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let file = TempFile::create(temp_dir, audio)?;
+    match file.path.to_str() {
+        Some(path) => transcribe::transcribe_audio(whisper, path, options),
+        None => Err("temporary path isn't valid UTF-8".into()),
+    }
+}
+
+// Removed when dropped, whichever way the request ends
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    // Named after the process and the time, so two assistants sharing a directory don't collide
+    fn create(dir: &Path, contents: &[u8]) -> io::Result<Self> {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|t| t.as_nanos()).unwrap_or_default();
+        let file = TempFile {
+            path: dir.join(format!("stt_whisper_http_{}_{}", std::process::id(), nanos)),
+        };
+        fs::write(&file.path, contents)?;
+        Ok(file)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::CommandEntry;
+    use chrono::Local;
+
+    fn request(raw: &str) -> Result<Request, String> {
+        read_request(&mut raw.as_bytes())
+    }
+
+    #[test]
+    fn test_read_request() {
+        let post = request("POST /transcribe HTTP/1.1\r\nHost: localhost\r\ncontent-length: 4\r\n\r\nRIFF").unwrap();
+        assert_eq!(post.method, "POST");
+        assert_eq!(post.path, "/transcribe");
+        assert_eq!(post.body, b"RIFF");
+
+        let get = request("GET /history?count=3 HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(get.query.as_deref(), Some("count=3"));
+        assert!(get.body.is_empty());
+
+        assert!(request("\r\n").is_err());
+        assert!(request("POST /transcribe HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort").is_err());
+        assert!(request("POST /transcribe HTTP/1.1\r\nContent-Length: lots\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn test_routes() {
+        let history = Mutex::new(CommandHistory::new());
+        for phrase in ["open terminal", "lock screen"] {
+            history.lock().unwrap().add_entry(CommandEntry {
+                timestamp: Local::now(),
+                transcription: phrase.to_string(),
+                command_matched: Some(phrase.to_string()),
+                command_executed: None,
+                success: true,
+                duration_ms: 100,
+                audio_duration_ms: 800,
+                outcome: None,
+            });
+        }
+        let no_audio = |_: &[u8]| -> Result<String, Box<dyn std::error::Error>> { panic!("not a transcription") };
+        let get = |target: &str| request(&format!("GET {} HTTP/1.1\r\n\r\n", target)).unwrap();
+
+        let response = handle(&get("/history?count=1"), no_audio, &history);
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body[0]["transcription"], "lock screen");
+        assert_eq!(response.body.as_array().unwrap().len(), 1);
+        assert_eq!(handle(&get("/history"), no_audio, &history).body.as_array().unwrap().len(), 2);
+        assert_eq!(handle(&get("/history?count=many"), no_audio, &history).status, 400);
+
+        let post = request("POST /transcribe HTTP/1.1\r\nContent-Length: 4\r\n\r\nRIFF").unwrap();
+        let response = handle(&post, |audio| Ok(format!("{} bytes", audio.len())), &history);
+        assert_eq!(response, Response { status: 200, body: json!({ "text": "4 bytes" }) });
+        let empty = request("POST /transcribe HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(handle(&empty, no_audio, &history).status, 400);
+
+        assert_eq!(handle(&get("/transcribe"), no_audio, &history).status, 405);
+        assert_eq!(handle(&get("/"), no_audio, &history).status, 404);
+    }
+
+    #[test]
+    fn test_temp_file_is_removed() {
+        let dir = std::env::temp_dir();
+        let first = TempFile::create(&dir, b"RIFF").unwrap();
+        let second = TempFile::create(&dir, b"fLaC").unwrap();
+        assert_ne!(first.path, second.path);
+        assert_eq!(fs::read(&first.path).unwrap(), b"RIFF");
+
+        let path = first.path.clone();
+        drop(first);
+        assert!(!path.exists());
+        assert!(TempFile::create(&dir.join("no_such_directory"), b"RIFF").is_err());
+    }
+}
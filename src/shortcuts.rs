@@ -0,0 +1,663 @@
+use std::collections::HashMap;
+use std::thread;
+use std::time::Duration;
+use log::warn;
+use regex::{Regex, RegexBuilder};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::homophones::Homophones;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct MacroStep {
+    pub command: String,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub post_delay_ms: u64,
+}
+
+// A shortcut is either a bare command string or an object with extra options,
+// so existing settings files keep working unchanged.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum ShortcutConfig {
+    Command(String),
+    Detailed {
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        command: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        steps: Vec<MacroStep>,
+        #[serde(default, skip_serializing_if = "is_zero")]
+        post_delay_ms: u64,
+        #[serde(default = "default_enabled", skip_serializing_if = "is_enabled")]
+        enabled: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        icon: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cue: Option<String>,
+        // Key sequence sent to the focused window instead of running a command
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        keys: Option<String>,
+        // Ask for a spoken "yes" before running
+        #[serde(default, skip_serializing_if = "is_false")]
+        confirm: bool,
+        // Wait for the command to exit and treat a nonzero status as a failure
+        #[serde(default, skip_serializing_if = "is_false")]
+        wait: bool,
+        // Read the command's output aloud once it exits; implies `wait`
+        #[serde(default, skip_serializing_if = "is_false")]
+        speak_output: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cooldown_ms: Option<u64>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "ShortcutConfig", into = "ShortcutConfig")]
+pub struct Shortcut {
+    pub command: String,
+    pub steps: Vec<MacroStep>,
+    pub post_delay_ms: u64,
+    // Disabled shortcuts stay in the config but are never matched
+    pub enabled: bool,
+    // Override the global notification icon and sound cue (a WAV file)
+    pub icon: Option<String>,
+    pub cue: Option<String>,
+    // "ctrl+c" etc., see keystrokes.rs
+    pub keys: Option<String>,
+    // Destructive commands shouldn't fire on a misheard or fuzzy match alone
+    pub confirm: bool,
+    // Most commands are started and left running; these are waited for so their
+    // output and exit status can be reported
+    pub wait: bool,
+    pub speak_output: bool,
+    // Overrides the global cooldown_ms; 0 lets this one repeat immediately
+    pub cooldown_ms: Option<u64>,
+}
+
+impl Shortcut {
+    // Macros (multiple steps or any delay) sleep while running, so callers
+    // should run them off the key-polling loop.
+    pub fn is_macro(&self) -> bool {
+        !self.steps.is_empty() || self.post_delay_ms > 0
+    }
+
+    pub fn waits(&self) -> bool {
+        self.wait || self.speak_output
+    }
+
+    pub fn steps(&self) -> Vec<MacroStep> {
+        let mut steps = if self.steps.is_empty() {
+            vec![MacroStep {
+                command: self.command.clone(),
+                post_delay_ms: 0,
+            }]
+        } else {
+            self.steps.clone()
+        };
+
+        if let Some(last) = steps.last_mut() {
+            last.post_delay_ms += self.post_delay_ms;
+        }
+        steps
+    }
+
+    // Fills `{name}` placeholders in the command and every step
+    pub fn with_captures(&self, captures: &[(String, String)]) -> Shortcut {
+        self.fill_commands(|command| {
+            captures.iter().fold(command.to_string(), |command, (name, value)| {
+                command.replace(&format!("{{{}}}", name), value)
+            })
+        })
+    }
+
+    // Fills `$1`, `$2`... from a regex phrase's groups. Highest first, so `$1` doesn't
+    // eat the start of `$10`; other `$` uses such as `$HOME` are left for the shell.
+    pub fn with_groups(&self, groups: &[String]) -> Shortcut {
+        self.fill_commands(|command| {
+            groups.iter().enumerate().rev().fold(command.to_string(), |command, (i, value)| {
+                command.replace(&format!("${}", i + 1), value)
+            })
+        })
+    }
+
+    fn fill_commands<F: Fn(&str) -> String>(&self, fill: F) -> Shortcut {
+        let mut shortcut = self.clone();
+        shortcut.command = fill(&self.command);
+        for step in &mut shortcut.steps {
+            step.command = fill(&step.command);
+        }
+        shortcut
+    }
+
+    pub fn display_command(&self) -> String {
+        if let Some(keys) = &self.keys {
+            format!("keys: {}", keys)
+        } else if self.steps.is_empty() {
+            self.command.clone()
+        } else {
+            self.steps.iter().map(|s| s.command.as_str()).collect::<Vec<_>>().join("; ")
+        }
+    }
+}
+
+// The schema describes the on-disk form rather than the flattened struct
+impl JsonSchema for Shortcut {
+    fn schema_name() -> String {
+        "Shortcut".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        ShortcutConfig::json_schema(gen)
+    }
+}
+
+impl From<&str> for Shortcut {
+    fn from(command: &str) -> Self {
+        Shortcut {
+            command: command.to_string(),
+            steps: Vec::new(),
+            post_delay_ms: 0,
+            enabled: true,
+            icon: None,
+            cue: None,
+            keys: None,
+            confirm: false,
+            wait: false,
+            speak_output: false,
+            cooldown_ms: None,
+        }
+    }
+}
+
+impl From<ShortcutConfig> for Shortcut {
+    fn from(config: ShortcutConfig) -> Self {
+        match config {
+            ShortcutConfig::Command(command) => Shortcut::from(command.as_str()),
+            ShortcutConfig::Detailed { command, steps, post_delay_ms, enabled, icon, cue, keys, confirm, wait, speak_output, cooldown_ms } => {
+                Shortcut {
+                    command,
+                    steps,
+                    post_delay_ms,
+                    enabled,
+                    icon,
+                    cue,
+                    keys,
+                    confirm,
+                    wait,
+                    speak_output,
+                    cooldown_ms,
+                }
+            }
+        }
+    }
+}
+
+impl From<Shortcut> for ShortcutConfig {
+    fn from(shortcut: Shortcut) -> Self {
+        let plain = shortcut.steps.is_empty()
+            && shortcut.post_delay_ms == 0
+            && shortcut.enabled
+            && shortcut.icon.is_none()
+            && shortcut.cue.is_none()
+            && shortcut.keys.is_none()
+            && !shortcut.confirm
+            && !shortcut.waits()
+            && shortcut.cooldown_ms.is_none();
+        if plain {
+            ShortcutConfig::Command(shortcut.command)
+        } else {
+            ShortcutConfig::Detailed {
+                command: shortcut.command,
+                steps: shortcut.steps,
+                post_delay_ms: shortcut.post_delay_ms,
+                enabled: shortcut.enabled,
+                icon: shortcut.icon,
+                cue: shortcut.cue,
+                keys: shortcut.keys,
+                confirm: shortcut.confirm,
+                wait: shortcut.wait,
+                speak_output: shortcut.speak_output,
+                cooldown_ms: shortcut.cooldown_ms,
+            }
+        }
+    }
+}
+
+// First enabled shortcut whose phrase appears in the transcription
+pub fn find_exact<'a>(text: &str, shortcuts: &'a HashMap<String, Shortcut>, homophones: &Homophones) -> Option<(&'a String, &'a Shortcut)> {
+    let text = homophones.canonicalize(text);
+    shortcuts.iter().find(|(phrase, shortcut)| {
+        shortcut.enabled && !is_regex_phrase(phrase) && text.contains(&homophones.canonicalize(phrase))
+    })
+}
+
+// Shortcut keys starting with this are regular expressions rather than phrases
+pub const REGEX_PREFIX: &str = "re:";
+
+pub fn is_regex_phrase(phrase: &str) -> bool {
+    phrase.starts_with(REGEX_PREFIX)
+}
+
+// The `re:` shortcuts, compiled when the settings are loaded
+#[derive(Debug, Clone, Default)]
+pub struct RegexShortcuts {
+    patterns: Vec<(String, Regex)>,
+}
+
+impl RegexShortcuts {
+    pub fn compile(shortcuts: &HashMap<String, Shortcut>) -> Result<Self, String> {
+        let mut patterns = Vec::new();
+        for phrase in shortcuts.keys().filter(|phrase| is_regex_phrase(phrase)) {
+            let regex = RegexBuilder::new(&phrase[REGEX_PREFIX.len()..])
+                .case_insensitive(true)
+                .build()
+                .map_err(|e| format!("shortcut '{}' is not a valid regex: {}", phrase, e))?;
+            patterns.push((phrase.clone(), regex));
+        }
+        // Tried in a fixed order, unlike the HashMap
+        patterns.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(RegexShortcuts { patterns })
+    }
+
+    // The first enabled pattern found anywhere in the transcription, ignoring case. The
+    // phrase returned is the text it matched, which reads better than the pattern.
+    pub fn find(&self, text: &str, shortcuts: &HashMap<String, Shortcut>, trim_words: &[String]) -> Option<(String, Shortcut)> {
+        self.patterns.iter().find_map(|(phrase, regex)| {
+            let shortcut = shortcuts.get(phrase).filter(|shortcut| shortcut.enabled)?;
+            let captures = regex.captures(text)?;
+            let groups: Vec<String> = captures
+                .iter()
+                .skip(1)
+                .map(|group| group.map_or(String::new(), |group| trim_capture(group.as_str(), trim_words)))
+                .collect();
+            if !groups.iter().all(|value| is_safe_argument(phrase, value)) {
+                return None;
+            }
+            let matched = captures[0].trim().trim_matches(CAPTURE_PUNCTUATION).to_string();
+            Some((matched, shortcut.with_groups(&groups)))
+        })
+    }
+}
+
+// Phrases like "search for {query}" capture the rest of the transcription into the
+// placeholder; the shortcut is returned with its command already filled in.
+pub fn find_template(text: &str, shortcuts: &HashMap<String, Shortcut>, trim_words: &[String]) -> Option<(String, Shortcut)> {
+    shortcuts
+        .iter()
+        .filter(|(phrase, shortcut)| shortcut.enabled && phrase.contains('{') && !is_regex_phrase(phrase))
+        .find_map(|(phrase, shortcut)| {
+            let captures = capture_placeholders(phrase, text, trim_words)?;
+            if !captures.iter().all(|(_, value)| is_safe_argument(phrase, value)) {
+                return None;
+            }
+            Some((phrase.clone(), shortcut.with_captures(&captures)))
+        })
+}
+
+// Commands containing `{args}` get whatever was said after the phrase, so "open
+// browser github.com" can run `xdg-open {args}`. Without trailing words it is empty.
+pub fn fill_args(phrase: &str, shortcut: &Shortcut, text: &str, trim_words: &[String]) -> Option<Shortcut> {
+    let uses_args = shortcut.command.contains(ARGS_PLACEHOLDER)
+        || shortcut.steps.iter().any(|step| step.command.contains(ARGS_PLACEHOLDER));
+    if !uses_args {
+        return Some(shortcut.clone());
+    }
+
+    // ASCII lowercasing keeps byte offsets, as in capture_placeholders
+    let args = match text.to_ascii_lowercase().find(&phrase.to_ascii_lowercase()) {
+        Some(index) => trim_capture(&text[index + phrase.len()..], trim_words),
+        None => String::new(),
+    };
+    if !is_safe_argument(phrase, &args) {
+        return None;
+    }
+    Some(shortcut.with_captures(&[("args".to_string(), args)]))
+}
+
+const ARGS_PLACEHOLDER: &str = "{args}";
+
+// Spoken values end up inside a shell command, so anything that could start another
+// command or redirect output is refused rather than escaped
+const SHELL_METACHARACTERS: &[char] = &[';', '|', '&', '`', '$', '<', '>', '(', ')', '\\', '"', '\'', '\n'];
+
+fn is_safe_argument(phrase: &str, value: &str) -> bool {
+    match value.chars().find(|c| SHELL_METACHARACTERS.contains(c)) {
+        Some(c) => {
+            warn!("Not running '{}': the spoken argument \"{}\" contains '{}'", phrase, value, c.escape_default());
+            false
+        }
+        None => true,
+    }
+}
+
+enum TemplatePart<'a> {
+    Literal(&'a str),
+    Placeholder(&'a str),
+}
+
+fn parse_template(phrase: &str) -> Option<Vec<TemplatePart<'_>>> {
+    let mut parts = Vec::new();
+    let mut rest = phrase;
+    while let Some(start) = rest.find('{') {
+        let end = start + rest[start..].find('}')?;
+        parts.push(TemplatePart::Literal(&rest[..start]));
+        parts.push(TemplatePart::Placeholder(&rest[start + 1..end]));
+        rest = &rest[end + 1..];
+    }
+    parts.push(TemplatePart::Literal(rest));
+    Some(parts)
+}
+
+fn capture_placeholders(phrase: &str, text: &str, trim_words: &[String]) -> Option<Vec<(String, String)>> {
+    // ASCII lowercasing keeps byte offsets, so captures can be sliced from the original text
+    let phrase = phrase.to_ascii_lowercase();
+    let lower_text = text.to_ascii_lowercase();
+
+    let mut captures = Vec::new();
+    let mut pending: Option<&str> = None;
+    let mut pos = 0;
+
+    let mut capture = |name: &str, raw: &str| {
+        let value = trim_capture(raw, trim_words);
+        if value.is_empty() {
+            return false;
+        }
+        captures.push((name.to_string(), value));
+        true
+    };
+
+    for part in parse_template(&phrase)? {
+        match part {
+            TemplatePart::Placeholder(name) => {
+                if pending.is_some() {
+                    return None;
+                }
+                pending = Some(name);
+            }
+            TemplatePart::Literal(literal) => {
+                let literal = literal.trim();
+                if literal.is_empty() {
+                    continue;
+                }
+                let index = lower_text[pos..].find(literal)?;
+                if let Some(name) = pending.take() {
+                    if !capture(name, &text[pos..pos + index]) {
+                        return None;
+                    }
+                }
+                pos += index + literal.len();
+            }
+        }
+    }
+
+    if let Some(name) = pending {
+        if !capture(name, &text[pos..]) {
+            return None;
+        }
+    }
+    Some(captures)
+}
+
+// Punctuation Whisper puts around words ("rust, please.")
+const CAPTURE_PUNCTUATION: &[char] = &['.', ',', '!', '?', ';', ':', '"', '\''];
+
+// Strips configured filler words and punctuation from both ends of a captured value
+pub fn trim_capture(value: &str, trim_words: &[String]) -> String {
+    let clean = |token: &str| token.trim_matches(CAPTURE_PUNCTUATION).to_lowercase();
+    let mut tokens: Vec<&str> = value.split_whitespace().collect();
+
+    loop {
+        let before = tokens.len();
+
+        while tokens.first().is_some_and(|t| clean(t).is_empty()) {
+            tokens.remove(0);
+        }
+        while tokens.last().is_some_and(|t| clean(t).is_empty()) {
+            tokens.pop();
+        }
+
+        for word in trim_words {
+            let word: Vec<String> = word.split_whitespace().map(|w| w.to_lowercase()).collect();
+            let n = word.len();
+            if n == 0 || n > tokens.len() {
+                continue;
+            }
+            if tokens[tokens.len() - n..].iter().map(|t| clean(t)).eq(word.iter().cloned()) {
+                tokens.truncate(tokens.len() - n);
+            } else if tokens[..n].iter().map(|t| clean(t)).eq(word.iter().cloned()) {
+                tokens.drain(..n);
+            }
+        }
+
+        if tokens.len() == before {
+            break;
+        }
+    }
+
+    tokens.join(" ").trim_matches(CAPTURE_PUNCTUATION).to_string()
+}
+
+// "Open terminal, and then open browser." -> ["Open terminal", "open browser."]. Splits at
+// whole words only, so "brand" doesn't split on "and"; several in a row count as one.
+pub fn split_chain(text: &str, chain_words: &[String]) -> Vec<String> {
+    let mut segments = vec![Vec::new()];
+    for token in text.split_whitespace() {
+        let word = token.trim_matches(CAPTURE_PUNCTUATION).to_lowercase();
+        if chain_words.iter().any(|chain_word| chain_word.eq_ignore_ascii_case(&word)) {
+            segments.push(Vec::new());
+        } else if let Some(segment) = segments.last_mut() {
+            segment.push(token);
+        }
+    }
+    segments
+        .into_iter()
+        .map(|segment| segment.join(" ").trim_end_matches([',', ';']).to_string())
+        .filter(|segment| !segment.is_empty())
+        .collect()
+}
+
+pub fn enabled_only(shortcuts: &HashMap<String, Shortcut>) -> HashMap<String, Shortcut> {
+    shortcuts
+        .iter()
+        .filter(|(_, shortcut)| shortcut.enabled)
+        .map(|(phrase, shortcut)| (phrase.clone(), shortcut.clone()))
+        .collect()
+}
+
+pub fn run_macro<F, E>(steps: &[MacroStep], mut execute: F) -> Result<(), E>
+where
+    F: FnMut(&str) -> Result<(), E>,
+{
+    for step in steps {
+        execute(&step.command)?;
+        if step.post_delay_ms > 0 {
+            thread::sleep(Duration::from_millis(step.post_delay_ms));
+        }
+    }
+    Ok(())
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn is_enabled(value: &bool) -> bool {
+    *value
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_bare_string_and_detailed_shortcuts() {
+        let bare: Shortcut = serde_json::from_str("\"gnome-terminal\"").unwrap();
+        assert_eq!(bare.command, "gnome-terminal");
+        assert!(!bare.is_macro());
+        assert_eq!(serde_json::to_string(&bare).unwrap(), "\"gnome-terminal\"");
+
+        let detailed: Shortcut = serde_json::from_str(
+            r#"{"steps": [{"command": "firefox", "post_delay_ms": 1500}, {"command": "xdotool key F11"}]}"#,
+        )
+        .unwrap();
+        assert!(detailed.is_macro());
+        assert_eq!(detailed.steps()[0].post_delay_ms, 1500);
+        assert_eq!(detailed.display_command(), "firefox; xdotool key F11");
+    }
+
+    #[test]
+    fn test_wait_flags_round_trip() {
+        let json = r#"{"command":"git pull","speak_output":true}"#;
+        let shortcut: Shortcut = serde_json::from_str(json).unwrap();
+        assert!(shortcut.waits() && !shortcut.wait);
+        assert_eq!(serde_json::to_string(&shortcut).unwrap(), json);
+        assert!(!Shortcut::from("python -m http.server").waits());
+    }
+
+    #[test]
+    fn test_confirm_flag_round_trips() {
+        let bare: Shortcut = serde_json::from_str("\"rm -rf ~/tmp/scratch\"").unwrap();
+        assert!(!bare.confirm);
+
+        let json = r#"{"command":"rm -rf ~/tmp/scratch","confirm":true}"#;
+        let confirmed: Shortcut = serde_json::from_str(json).unwrap();
+        assert!(confirmed.confirm);
+        assert_eq!(serde_json::to_string(&confirmed).unwrap(), json);
+    }
+
+    #[test]
+    fn test_declared_homophone_still_matches() {
+        let shortcuts: HashMap<String, Shortcut> = serde_json::from_str(r#"{"close window": "xdotool key alt+F4"}"#).unwrap();
+        assert!(find_exact("Clothes window.", &shortcuts, &Homophones::default()).is_none());
+
+        let homophones = Homophones::new(&[vec!["clothes".to_string(), "close".to_string()]]);
+        assert_eq!(find_exact("Clothes window.", &shortcuts, &homophones).unwrap().0, "close window");
+    }
+
+    #[test]
+    fn test_disabled_shortcut_is_loaded_but_never_matched() {
+        let shortcuts: HashMap<String, Shortcut> = serde_json::from_str(
+            r#"{"open terminal": {"command": "xterm", "enabled": false}, "open browser": "firefox"}"#,
+        )
+        .unwrap();
+        assert!(!shortcuts["open terminal"].enabled);
+        assert!(shortcuts["open browser"].enabled);
+
+        assert!(find_exact("Open terminal.", &shortcuts, &Homophones::default()).is_none());
+        assert_eq!(find_exact("open browser", &shortcuts, &Homophones::default()).unwrap().0, "open browser");
+        assert!(!enabled_only(&shortcuts).contains_key("open terminal"));
+
+        // The flag survives a round trip so the definition isn't lost
+        let json = serde_json::to_string(&shortcuts["open terminal"]).unwrap();
+        assert!(!serde_json::from_str::<Shortcut>(&json).unwrap().enabled);
+    }
+
+    #[test]
+    fn test_captured_argument_drops_trailing_filler() {
+        let mut shortcuts = HashMap::new();
+        shortcuts.insert(
+            "search for {query}".to_string(),
+            Shortcut::from("xdg-open https://google.com/search?q={query}"),
+        );
+        let trim_words = vec!["please".to_string(), "thank you".to_string()];
+
+        let (phrase, shortcut) = find_template("Search for rust please", &shortcuts, &trim_words).unwrap();
+        assert_eq!(phrase, "search for {query}");
+        assert_eq!(shortcut.command, "xdg-open https://google.com/search?q=rust");
+
+        assert_eq!(trim_capture(" Rust lifetimes, thank you.", &trim_words), "Rust lifetimes");
+        // Only the edges are trimmed
+        assert_eq!(trim_capture("rust please docs", &trim_words), "rust please docs");
+        assert!(find_template("search for please", &shortcuts, &trim_words).is_none());
+    }
+
+    #[test]
+    fn test_trailing_args_fill_command() {
+        let shortcut = Shortcut::from("xdg-open https://{args}");
+        let trim_words = vec!["please".to_string()];
+
+        let filled = fill_args("open browser", &shortcut, "Open browser github.com please.", &trim_words).unwrap();
+        assert_eq!(filled.command, "xdg-open https://github.com");
+
+        // Nothing after the phrase leaves the placeholder empty
+        assert_eq!(fill_args("open browser", &shortcut, "open browser", &trim_words).unwrap().command, "xdg-open https://");
+
+        // Commands without the placeholder are untouched
+        let plain = Shortcut::from("firefox");
+        assert_eq!(fill_args("open browser", &plain, "open browser x; rm -rf ~", &trim_words).unwrap().command, "firefox");
+    }
+
+    #[test]
+    fn test_unsafe_args_are_rejected() {
+        let shortcut = Shortcut::from("xdg-open {args}");
+        for text in ["open browser x; rm -rf ~", "open browser a | b", "open browser `id`", "open browser $(id)"] {
+            assert!(fill_args("open browser", &shortcut, text, &[]).is_none(), "{}", text);
+        }
+
+        let mut shortcuts = HashMap::new();
+        shortcuts.insert("search for {query}".to_string(), Shortcut::from("xdg-open https://google.com/search?q={query}"));
+        assert!(find_template("search for rust && reboot", &shortcuts, &[]).is_none());
+    }
+
+    #[test]
+    fn test_regex_shortcuts_fill_groups() {
+        let mut shortcuts = HashMap::new();
+        shortcuts.insert(r"re:set volume to (\d+)".to_string(), Shortcut::from("pactl set-sink-volume @DEFAULT_SINK@ $1% && echo $HOME"));
+        shortcuts.insert(r"re:move (\w+) to desktop (\d)".to_string(), Shortcut::from("wmctrl -r $1 -t $2"));
+        shortcuts.insert(r"re:run (.+)".to_string(), Shortcut::from("echo $1"));
+        let patterns = RegexShortcuts::compile(&shortcuts).unwrap();
+
+        let (phrase, shortcut) = patterns.find("Set volume to 40.", &shortcuts, &[]).unwrap();
+        assert_eq!(phrase, "Set volume to 40");
+        assert_eq!(shortcut.command, "pactl set-sink-volume @DEFAULT_SINK@ 40% && echo $HOME");
+        let (_, shortcut) = patterns.find("move firefox to desktop 2", &shortcuts, &[]).unwrap();
+        assert_eq!(shortcut.command, "wmctrl -r firefox -t 2");
+        assert!(patterns.find("set volume to loud", &shortcuts, &[]).is_none());
+
+        // Captures go through the same checks as spoken placeholders
+        assert!(patterns.find("run this; reboot", &shortcuts, &[]).is_none());
+        // Phrase matching never sees the pattern as text
+        assert!(find_exact(r"re:run (.+)", &shortcuts, &Homophones::default()).is_none());
+
+        shortcuts.insert("re:open (unclosed".to_string(), Shortcut::from("true"));
+        let error = RegexShortcuts::compile(&shortcuts).unwrap_err();
+        assert!(error.contains("'re:open (unclosed'"), "{}", error);
+    }
+
+    #[test]
+    fn test_split_chain() {
+        let words = vec!["and".to_string(), "then".to_string()];
+        assert_eq!(split_chain("Open terminal, and then open browser.", &words), vec!["Open terminal", "open browser."]);
+        assert_eq!(split_chain("open the brandy", &words), vec!["open the brandy"]);
+        assert_eq!(split_chain("and then", &words), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_macro_runner_honors_post_delay() {
+        let steps = vec![
+            MacroStep { command: "first".to_string(), post_delay_ms: 100 },
+            MacroStep { command: "second".to_string(), post_delay_ms: 0 },
+        ];
+
+        let mut executed = Vec::new();
+        let result: Result<(), String> = run_macro(&steps, |command| {
+            executed.push((command.to_string(), Instant::now()));
+            Ok(())
+        });
+
+        assert!(result.is_ok());
+        assert_eq!(executed.len(), 2);
+        assert!(executed[1].1.duration_since(executed[0].1) >= Duration::from_millis(100));
+    }
+}
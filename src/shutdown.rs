@@ -0,0 +1,49 @@
+// Ctrl-C and SIGTERM ask the main loop to stop rather than killing the process, so the
+// history is saved and speech is cut off cleanly. The handler only touches an atomic,
+// which is all that is safe inside a signal handler; a second Ctrl-C exits immediately.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::Acquire)
+}
+
+#[cfg(unix)]
+extern "C" fn handle_signal(_signal: libc::c_int) {
+    if REQUESTED.swap(true, Ordering::AcqRel) {
+        // The clean shutdown is stuck; _exit is async-signal-safe, unlike process::exit
+        unsafe { libc::_exit(130) };
+    }
+}
+
+#[cfg(unix)]
+pub fn install() -> io::Result<()> {
+    let handler = handle_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        if unsafe { libc::signal(signal, handler) } == libc::SIG_ERR {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn install() -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "clean shutdown is only supported on Unix"))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signal_requests_shutdown() {
+        install().unwrap();
+        assert!(!requested());
+        assert_eq!(unsafe { libc::raise(libc::SIGTERM) }, 0);
+        assert!(requested());
+    }
+}
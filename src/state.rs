@@ -0,0 +1,57 @@
+use std::fs;
+use std::path::Path;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+// Toggles changed by voice while running. They reset on restart unless
+// `persist_runtime_state` is on, in which case they live in their own small file
+// so the settings file is never rewritten behind the user's back.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuntimeState {
+    // Voice feedback silenced with "mute"
+    pub muted: bool,
+    // Shortcuts ignored until "resume listening"
+    pub paused: bool,
+}
+
+impl RuntimeState {
+    pub fn load(path: &str) -> Self {
+        if !Path::new(path).exists() {
+            return Self::default();
+        }
+
+        match fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|contents| {
+            serde_json::from_str(&contents).map_err(|e| e.to_string())
+        }) {
+            Ok(state) => state,
+            Err(e) => {
+                warn!("Ignoring unreadable runtime state in {}: {}", path, e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_muted_state_survives_reload() {
+        let path = std::env::temp_dir().join(format!("stt_whisper_state_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        assert_eq!(RuntimeState::load(path), RuntimeState::default());
+
+        let state = RuntimeState { muted: true, ..RuntimeState::default() };
+        state.save(path).unwrap();
+        assert_eq!(RuntimeState::load(path), state);
+
+        fs::remove_file(path).unwrap();
+    }
+}
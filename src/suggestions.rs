@@ -1,21 +1,72 @@
 use std::collections::HashMap;
 use chrono::{Local, Timelike, Datelike};
 use crate::history::CommandHistory;
+use crate::homophones::Homophones;
+use crate::shortcuts::{self, Shortcut};
+use crate::transcribe::Hypothesis;
 
+#[derive(Debug, PartialEq)]
+pub struct HypothesisMatch {
+    pub hypothesis: usize,
+    // The shortcut key, or for a `re:` pattern the text it matched
+    pub phrase: String,
+    pub score: f32,
+}
+
+// Jaro-Winkler forgives transpositions and rewards a shared prefix, which suits single
+// words; on longer phrases the shared "open " alone would lift every score. It is also
+// generous to anagrams ("open" and "nope"), so it only counts when the first letters agree.
+const JARO_WINKLER_MAX_CHARS: usize = 12;
+// Phrases whose words all sound alike ("write email", "right email") score at least this,
+// which is above the default bar for running a command
+const PHONETIC_MATCH_SCORE: f32 = 0.9;
+// Letters that are silent at the start of a word
+const SILENT_PREFIXES: &[(&str, &str)] = &[("wr", "r"), ("kn", "n"), ("gn", "n"), ("ps", "s"), ("wh", "w")];
+// How often one command has to follow another before it's suggested after it
+const MIN_SEQUENCE_COUNT: usize = 2;
+pub const DEFAULT_MATCH_MIN_CONFIDENCE: f32 = 0.85;
+pub const DEFAULT_SUGGESTION_MIN_CONFIDENCE: f32 = 0.7;
+
+// Two bars: a near-miss can be worth suggesting long before it is safe to run
 pub struct SmartSuggestions {
-    min_confidence: f32,
+    match_min_confidence: f32,
+    suggestion_min_confidence: f32,
+    homophones: Homophones,
+}
+
+impl Default for SmartSuggestions {
+    fn default() -> Self {
+        SmartSuggestions::with_confidence(DEFAULT_MATCH_MIN_CONFIDENCE)
+    }
 }
 
 impl SmartSuggestions {
-    pub fn new() -> Self {
+    // Sets only the bar for running a fuzzy match; suggestions keep the default bar
+    pub fn with_confidence(match_min_confidence: f32) -> Self {
+        SmartSuggestions::with_thresholds(match_min_confidence, DEFAULT_SUGGESTION_MIN_CONFIDENCE)
+    }
+
+    pub fn with_thresholds(match_min_confidence: f32, suggestion_min_confidence: f32) -> Self {
         SmartSuggestions {
-            min_confidence: 0.7,
+            match_min_confidence,
+            suggestion_min_confidence,
+            homophones: Homophones::default(),
         }
     }
 
+    pub fn with_homophones(mut self, homophones: Homophones) -> Self {
+        self.homophones = homophones;
+        self
+    }
+
+    // Whether a fuzzy score is high enough to run the command without asking
+    pub fn is_confident(&self, score: f32) -> bool {
+        score >= self.match_min_confidence
+    }
+
     pub fn fuzzy_match(&self, input: &str, target: &str) -> f32 {
-        let input_lower = input.to_lowercase();
-        let target_lower = target.to_lowercase();
+        let input_lower = self.homophones.canonicalize(input);
+        let target_lower = self.homophones.canonicalize(target);
         
         if input_lower == target_lower {
             return 1.0;
@@ -25,47 +76,107 @@ impl SmartSuggestions {
             return 0.8;
         }
         
-        self.levenshtein_similarity(&input_lower, &target_lower)
+        let levenshtein = self.levenshtein_similarity(&input_lower, &target_lower);
+        let short = input_lower.chars().count().max(target_lower.chars().count()) <= JARO_WINKLER_MAX_CHARS;
+        let score = if short && input_lower.chars().next() == target_lower.chars().next() {
+            (levenshtein + self.jaro_winkler_similarity(&input_lower, &target_lower)) / 2.0
+        } else {
+            levenshtein
+        };
+        
+        if self.phonetic_match(&input_lower, &target_lower) {
+            score.max(PHONETIC_MATCH_SCORE)
+        } else {
+            score
+        }
     }
 
-    fn levenshtein_similarity(&self, s1: &str, s2: &str) -> f32 {
-        let len1 = s1.len();
-        let len2 = s2.len();
-        let max_len = len1.max(len2);
-        
-        if max_len == 0 {
+    // Whether both phrases have the same number of words and each pair sounds alike
+    pub fn phonetic_match(&self, input: &str, target: &str) -> bool {
+        let codes = |text: &str| text.split_whitespace().map(phonetic_code).collect::<Option<Vec<_>>>();
+        match (codes(input), codes(target)) {
+            (Some(input), Some(target)) => !input.is_empty() && input == target,
+            _ => false,
+        }
+    }
+
+    fn jaro_winkler_similarity(&self, s1: &str, s2: &str) -> f32 {
+        let s1: Vec<char> = s1.chars().collect();
+        let s2: Vec<char> = s2.chars().collect();
+        if s1 == s2 {
             return 1.0;
         }
+        if s1.is_empty() || s2.is_empty() {
+            return 0.0;
+        }
         
-        let mut matrix = vec![vec![0; len2 + 1]; len1 + 1];
-        
-        for i in 0..=len1 {
-            matrix[i][0] = i;
+        // Characters match if they are equal and not too far apart
+        let window = (s1.len().max(s2.len()) / 2).saturating_sub(1);
+        let mut matched1 = vec![false; s1.len()];
+        let mut matched2 = vec![false; s2.len()];
+        let mut matches = 0;
+        for (i, c) in s1.iter().enumerate() {
+            let start = i.saturating_sub(window);
+            let end = (i + window + 1).min(s2.len());
+            for j in start..end {
+                if !matched2[j] && s2[j] == *c {
+                    matched1[i] = true;
+                    matched2[j] = true;
+                    matches += 1;
+                    break;
+                }
+            }
+        }
+        if matches == 0 {
+            return 0.0;
         }
         
-        for j in 0..=len2 {
-            matrix[0][j] = j;
+        // Matched characters that appear in a different order
+        let order1 = s1.iter().zip(&matched1).filter(|(_, m)| **m).map(|(c, _)| c);
+        let order2 = s2.iter().zip(&matched2).filter(|(_, m)| **m).map(|(c, _)| c);
+        let transpositions = order1.zip(order2).filter(|(a, b)| a != b).count() / 2;
+        
+        let m = matches as f32;
+        let jaro = (m / s1.len() as f32 + m / s2.len() as f32 + (m - transpositions as f32) / m) / 3.0;
+        
+        let prefix = s1.iter().zip(&s2).take(4).take_while(|(a, b)| a == b).count();
+        jaro + prefix as f32 * 0.1 * (1.0 - jaro)
+    }
+
+    fn levenshtein_similarity(&self, s1: &str, s2: &str) -> f32 {
+        let s1: Vec<char> = s1.chars().collect();
+        let s2: Vec<char> = s2.chars().collect();
+        let max_len = s1.len().max(s2.len());
+        
+        if max_len == 0 {
+            return 1.0;
         }
         
-        for i in 1..=len1 {
-            for j in 1..=len2 {
-                let cost = if s1.chars().nth(i - 1) == s2.chars().nth(j - 1) { 0 } else { 1 };
-                matrix[i][j] = (matrix[i - 1][j] + 1)
-                    .min(matrix[i][j - 1] + 1)
-                    .min(matrix[i - 1][j - 1] + cost);
+        // Only the previous row of the distance matrix is needed
+        let mut previous: Vec<usize> = (0..=s2.len()).collect();
+        let mut current = vec![0; s2.len() + 1];
+        for (i, c1) in s1.iter().enumerate() {
+            current[0] = i + 1;
+            for (j, c2) in s2.iter().enumerate() {
+                let cost = usize::from(c1 != c2);
+                current[j + 1] = (previous[j + 1] + 1)
+                    .min(current[j] + 1)
+                    .min(previous[j] + cost);
             }
+            std::mem::swap(&mut previous, &mut current);
         }
         
-        1.0 - (matrix[len1][len2] as f32 / max_len as f32)
+        1.0 - (previous[s2.len()] as f32 / max_len as f32)
     }
 
-    pub fn find_best_match<'a>(&self, input: &str, commands: &'a HashMap<String, String>) -> Option<(&'a str, f32)> {
+    pub fn find_best_match<'a, V>(&self, input: &str, commands: &'a HashMap<String, V>) -> Option<(&'a str, f32)> {
         let mut best_match = None;
         let mut best_score = 0.0;
         
-        for (phrase, _) in commands {
+        // A pattern isn't something anyone says
+        for phrase in commands.keys().filter(|phrase| !shortcuts::is_regex_phrase(phrase)) {
             let score = self.fuzzy_match(input, phrase);
-            if score > best_score && score >= self.min_confidence {
+            if score > best_score && score >= self.suggestion_min_confidence {
                 best_score = score;
                 best_match = Some(phrase.as_str());
             }
@@ -74,6 +185,53 @@ impl SmartSuggestions {
         best_match.map(|m| (m, best_score))
     }
 
+    // The shortcut a hypothesis names and how sure that is. `matches` is the dispatcher's
+    // own matcher (homophones, placeholders, `re:` patterns), so a hypothesis only scores
+    // 1.0 if it would really run that shortcut; otherwise the nearest phrase, fuzzily.
+    fn hypothesis_candidate<V, M>(&self, text: &str, commands: &HashMap<String, V>, matches: &M) -> Option<(String, f32)>
+    where
+        M: Fn(&str) -> Option<String>,
+    {
+        match matches(text) {
+            Some(phrase) => Some((phrase, 1.0)),
+            None => self.find_best_match(&text.to_lowercase(), commands).map(|(phrase, score)| (phrase.to_string(), score)),
+        }
+    }
+
+    // Scores every hypothesis against the shortcuts and keeps the pair with the best
+    // decode_confidence^weight * fuzzy_score, so a slightly less confident hypothesis
+    // that clearly names a command beats a confident one that names nothing.
+    pub fn best_hypothesis_match<V, M>(&self, hypotheses: &[Hypothesis], commands: &HashMap<String, V>, confidence_weight: f32, matches: M) -> Option<HypothesisMatch>
+    where
+        M: Fn(&str) -> Option<String>,
+    {
+        let mut best: Option<HypothesisMatch> = None;
+        
+        for (i, hypothesis) in hypotheses.iter().enumerate() {
+            if let Some((phrase, fuzzy_score)) = self.hypothesis_candidate(&hypothesis.text, commands, &matches) {
+                let score = hypothesis.confidence.powf(confidence_weight) * fuzzy_score;
+                if best.as_ref().is_none_or(|b| score > b.score) {
+                    best = Some(HypothesisMatch { hypothesis: i, phrase, score });
+                }
+            }
+        }
+        
+        best
+    }
+
+    // The most confident hypothesis whose best shortcut clears the bar for running it,
+    // ignoring how far ahead the others are
+    pub fn first_confident_match<V, M>(&self, hypotheses: &[Hypothesis], commands: &HashMap<String, V>, matches: M) -> Option<HypothesisMatch>
+    where
+        M: Fn(&str) -> Option<String>,
+    {
+        hypotheses.iter().enumerate().find_map(|(i, hypothesis)| {
+            self.hypothesis_candidate(&hypothesis.text, commands, &matches)
+                .filter(|(_, score)| self.is_confident(*score))
+                .map(|(phrase, score)| HypothesisMatch { hypothesis: i, phrase, score })
+        })
+    }
+
     pub fn get_time_based_suggestions(&self, history: &CommandHistory, limit: usize) -> Vec<String> {
         let now = Local::now();
         let current_hour = now.hour();
@@ -115,7 +273,7 @@ impl SmartSuggestions {
     pub fn get_frequency_suggestions(&self, history: &CommandHistory, limit: usize) -> Vec<String> {
         let stats = history.get_statistics();
         let mut usage_vec: Vec<_> = stats.command_usage.into_iter().collect();
-        usage_vec.sort_by(|a, b| b.1.cmp(&a.1));
+        usage_vec.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
         
         usage_vec
             .into_iter()
@@ -124,11 +282,64 @@ impl SmartSuggestions {
             .collect()
     }
 
-    pub fn get_suggestions_for_failed_command(&self, input: &str, history: &CommandHistory, commands: &HashMap<String, String>) -> Vec<String> {
+    // Commands most often run straight after `previous`, counting consecutive pairs of
+    // executed commands in the history. A pair seen only once is treated as chance.
+    pub fn get_sequence_suggestions(&self, history: &CommandHistory, previous: &str, limit: usize) -> Vec<String> {
+        let executed: Vec<&str> = history
+            .get_all_entries()
+            .iter()
+            .filter(|entry| entry.success)
+            .filter_map(|entry| entry.command_matched.as_deref())
+            .collect();
+        
+        // Count and position of the most recent occurrence, so ties go to the newer habit
+        let mut followers: HashMap<&str, (usize, usize)> = HashMap::new();
+        for (position, pair) in executed.windows(2).enumerate() {
+            if pair[0] == previous && pair[1] != previous {
+                let follower = followers.entry(pair[1]).or_default();
+                follower.0 += 1;
+                follower.1 = position;
+            }
+        }
+        
+        let mut suggestions: Vec<_> = followers.into_iter().filter(|(_, (count, _))| *count >= MIN_SEQUENCE_COUNT).collect();
+        suggestions.sort_by_key(|(_, (count, position))| std::cmp::Reverse((*count, *position)));
+        suggestions
+            .into_iter()
+            .take(limit)
+            .map(|(cmd, _)| cmd.to_string())
+            .collect()
+    }
+
+    // Short enough to say aloud after a miss; disabled shortcuts aren't offered
+    pub fn spoken_suggestion(&self, input: &str, commands: &HashMap<String, Shortcut>) -> Option<String> {
+        let (best_match, _) = self.find_best_match(input, commands)?;
+        commands[best_match].enabled.then(|| format!("Did you mean {}?", best_match))
+    }
+
+    pub fn get_suggestions_for_failed_command(&self, input: &str, history: &CommandHistory, commands: &HashMap<String, Shortcut>) -> Vec<String> {
         let mut suggestions = Vec::new();
         
         if let Some((best_match, score)) = self.find_best_match(input, commands) {
-            suggestions.push(format!("Did you mean: {} ({}% match)?", best_match, (score * 100.0) as i32));
+            let disabled = if commands[best_match].enabled { "" } else { " (disabled)" };
+            suggestions.push(format!("Did you mean: {}{} ({}% match)?", best_match, disabled, (score * 100.0) as i32));
+        }
+        
+        let previous = history
+            .get_all_entries()
+            .iter()
+            .rev()
+            .filter(|entry| entry.success)
+            .find_map(|entry| entry.command_matched.as_deref());
+        if let Some(previous) = previous {
+            let next: Vec<String> = self
+                .get_sequence_suggestions(history, previous, 2)
+                .into_iter()
+                .filter(|cmd| commands.get(cmd).is_some_and(|shortcut| shortcut.enabled))
+                .collect();
+            if !next.is_empty() {
+                suggestions.push(format!("After {} you usually run: {}", previous, next.join(" or ")));
+            }
         }
         
         let time_suggestions = self.get_time_based_suggestions(history, 2);
@@ -191,13 +402,64 @@ impl TimePattern {
     }
 }
 
+// A Soundex-style code, not standard Soundex: there is no cut to a letter and three digits,
+// so long words must sound alike all the way through ("terminal" and "terminate" differ).
+// Silent leading letters and a "gh" that isn't followed by a vowel are dropped first, so
+// "write" and "right" share a code.
+fn phonetic_code(word: &str) -> Option<String> {
+    let mut word: String = word.to_lowercase().chars().filter(|c| c.is_ascii_alphabetic()).collect();
+    if let Some((prefix, sound)) = SILENT_PREFIXES.iter().find(|(prefix, _)| word.starts_with(prefix)) {
+        word = format!("{}{}", sound, &word[prefix.len()..]);
+    }
+    let chars: Vec<char> = word.chars().collect();
+    let mut letters = Vec::with_capacity(chars.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let silent_gh = chars[i] == 'g'
+            && chars.get(i + 1) == Some(&'h')
+            && i > 0
+            && !chars.get(i + 2).is_some_and(|c| "aeiouy".contains(*c));
+        if silent_gh {
+            i += 2;
+            continue;
+        }
+        letters.push(chars[i]);
+        i += 1;
+    }
+    
+    let digit = |c: char| match c {
+        'b' | 'f' | 'p' | 'v' => Some('1'),
+        'c' | 'g' | 'j' | 'k' | 'q' | 's' | 'x' | 'z' => Some('2'),
+        'd' | 't' => Some('3'),
+        'l' => Some('4'),
+        'm' | 'n' => Some('5'),
+        'r' => Some('6'),
+        _ => None,
+    };
+    let (&first, rest) = letters.split_first()?;
+    let mut code = first.to_ascii_uppercase().to_string();
+    let mut previous = digit(first);
+    for &c in rest {
+        match digit(c) {
+            Some(d) if previous != Some(d) => code.push(d),
+            Some(_) => {}
+            // 'h' and 'w' don't separate letters with the same code, vowels do
+            None if c == 'h' || c == 'w' => continue,
+            None => {}
+        }
+        previous = digit(c);
+    }
+    Some(code)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::history::CommandEntry;
 
     #[test]
     fn test_fuzzy_match() {
-        let suggestions = SmartSuggestions::new();
+        let suggestions = SmartSuggestions::default();
         
         assert_eq!(suggestions.fuzzy_match("terminal", "terminal"), 1.0);
         assert!(suggestions.fuzzy_match("termnal", "terminal") > 0.7);
@@ -205,13 +467,203 @@ mod tests {
         assert!(suggestions.fuzzy_match("term", "terminal") > 0.5);
     }
 
+    #[test]
+    fn test_transposed_short_words_score_higher() {
+        let suggestions = SmartSuggestions::default();
+        
+        let score = suggestions.fuzzy_match("treminal", "terminal");
+        assert!(score > suggestions.levenshtein_similarity("treminal", "terminal"));
+        assert!(suggestions.is_confident(score), "{}", score);
+        assert!(suggestions.fuzzy_match("firefxo", "firefox") > suggestions.fuzzy_match("firefox", "thunderbird"));
+        
+        // Long phrases and different first letters keep the plain edit distance
+        assert_eq!(suggestions.fuzzy_match("open", "nope"), suggestions.levenshtein_similarity("open", "nope"));
+        assert_eq!(
+            suggestions.fuzzy_match("open termnl", "open terminal"),
+            suggestions.levenshtein_similarity("open termnl", "open terminal")
+        );
+    }
+
+    #[test]
+    fn test_phonetic_match_homophones() {
+        let suggestions = SmartSuggestions::default();
+        
+        assert!(suggestions.phonetic_match("too", "two"));
+        assert!(suggestions.phonetic_match("right", "write"));
+        assert!(suggestions.phonetic_match("knight", "night"));
+        assert!(suggestions.phonetic_match("Check male", "check mail"));
+        assert!(suggestions.phonetic_match("their", "there"));
+        assert!(!suggestions.phonetic_match("terminal", "terminate"));
+        assert!(!suggestions.phonetic_match("open", "nope"));
+        assert!(!suggestions.phonetic_match("write email", "write"));
+        assert!(!suggestions.phonetic_match("", ""));
+        
+        // Sounding alike makes a misspelled command confident enough to run
+        assert!(!suggestions.is_confident(suggestions.levenshtein_similarity("nite", "night")));
+        assert!(suggestions.is_confident(suggestions.fuzzy_match("nite", "night")));
+    }
+
+    #[test]
+    fn test_phonetic_codes() {
+        // Soundex coding, which gives these short names their usual codes
+        assert_eq!(phonetic_code("Robert").as_deref(), Some("R163"));
+        assert_eq!(phonetic_code("Rupert").as_deref(), Some("R163"));
+        assert_eq!(phonetic_code("Tymczak").as_deref(), Some("T522"));
+        // Standard Soundex stops at A261; the whole word is kept here
+        assert_eq!(phonetic_code("Ashcraft").as_deref(), Some("A2613"));
+        assert_ne!(phonetic_code("terminal"), phonetic_code("terminate"));
+        assert_eq!(phonetic_code("write"), phonetic_code("right"));
+        assert_eq!(phonetic_code("42"), None);
+    }
+
+    #[test]
+    fn test_jaro_winkler_similarity() {
+        let suggestions = SmartSuggestions::default();
+        
+        assert_eq!(suggestions.jaro_winkler_similarity("", ""), 1.0);
+        assert_eq!(suggestions.jaro_winkler_similarity("abc", ""), 0.0);
+        assert!((suggestions.jaro_winkler_similarity("martha", "marhta") - 0.961).abs() < 0.001);
+        assert!((suggestions.jaro_winkler_similarity("dixon", "dicksonx") - 0.813).abs() < 0.001);
+    }
+
+    // Plain substring matching, standing in for the dispatcher's matcher
+    fn contains_phrase(text: &str, commands: &HashMap<String, ()>) -> Option<String> {
+        let text = text.to_lowercase();
+        commands.keys().find(|phrase| text.contains(phrase.as_str())).cloned()
+    }
+
+    #[test]
+    fn test_best_hypothesis_match() {
+        let suggestions = SmartSuggestions::default();
+        let mut commands = HashMap::new();
+        commands.insert("open terminal".to_string(), ());
+        commands.insert("open browser".to_string(), ());
+        let matches = |text: &str| contains_phrase(text, &commands);
+        
+        let hypothesis = |text: &str, confidence: f32| Hypothesis { text: text.to_string(), confidence, segments: Vec::new() };
+        
+        // The most confident hypothesis matches nothing, the runner-up names a command
+        let hypotheses = vec![hypothesis("pen german", 0.9), hypothesis("open terminal", 0.7)];
+        let best = suggestions.best_hypothesis_match(&hypotheses, &commands, 1.0, matches).unwrap();
+        assert_eq!(best.hypothesis, 1);
+        assert_eq!(best.phrase, "open terminal");
+        
+        // Acoustic confidence outweighs a perfect but unlikely lexical match...
+        let hypotheses = vec![hypothesis("open terminl", 0.9), hypothesis("open terminal", 0.3)];
+        assert_eq!(suggestions.best_hypothesis_match(&hypotheses, &commands, 1.0, matches).unwrap().hypothesis, 0);
+        // ...unless the weighting ignores it
+        assert_eq!(suggestions.best_hypothesis_match(&hypotheses, &commands, 0.0, matches).unwrap().hypothesis, 1);
+        
+        assert!(suggestions.best_hypothesis_match(&[hypothesis("pen german", 0.9)], &commands, 1.0, matches).is_none());
+    }
+
+    #[test]
+    fn test_first_confident_match() {
+        let suggestions = SmartSuggestions::with_thresholds(0.9, 0.6);
+        let mut commands = HashMap::new();
+        commands.insert("open terminal".to_string(), ());
+        
+        let hypothesis = |text: &str, confidence: f32| Hypothesis { text: text.to_string(), confidence, segments: Vec::new() };
+        
+        // "open termnl" is close enough to suggest but not to run, so the runner-up wins
+        let hypotheses = vec![hypothesis("open termnl", 0.9), hypothesis("open terminal", 0.2), hypothesis("open terminal now", 0.1)];
+        let matches = |text: &str| contains_phrase(text, &commands);
+        let first = suggestions.first_confident_match(&hypotheses, &commands, matches).unwrap();
+        assert_eq!((first.hypothesis, first.phrase.as_str(), first.score), (1, "open terminal", 1.0));
+        
+        assert!(suggestions.first_confident_match(&hypotheses[..1], &commands, matches).is_none());
+    }
+
+    #[test]
+    fn test_suggestion_below_match_threshold() {
+        let suggestions = SmartSuggestions::with_thresholds(0.9, 0.6);
+        let mut commands = HashMap::new();
+        commands.insert("open terminal".to_string(), Shortcut::from("gnome-terminal"));
+        
+        // Close enough to suggest, not close enough to run
+        let (phrase, score) = suggestions.find_best_match("open termnl", &commands).unwrap();
+        assert_eq!(phrase, "open terminal");
+        assert!(!suggestions.is_confident(score));
+        // Raising only the bar for running keeps the default suggestion bar
+        let cautious = SmartSuggestions::with_confidence(0.99);
+        assert_eq!(cautious.find_best_match("open termnl", &commands), Some((phrase, score)));
+        assert!(!cautious.is_confident(score));
+        
+        let failed = suggestions.get_suggestions_for_failed_command("open termnl", &CommandHistory::new(), &commands);
+        assert!(failed[0].starts_with("Did you mean: open terminal"), "{:?}", failed);
+        assert_eq!(suggestions.spoken_suggestion("open termnl", &commands).as_deref(), Some("Did you mean open terminal?"));
+        
+        commands.get_mut("open terminal").unwrap().enabled = false;
+        assert_eq!(suggestions.spoken_suggestion("open termnl", &commands), None);
+        
+        // A stricter suggestion bar hides it again
+        let strict = SmartSuggestions::with_thresholds(0.9, 0.9);
+        assert!(strict.find_best_match("open termnl", &commands).is_none());
+    }
+
+    #[test]
+    fn test_sequence_suggestions() {
+        let mut history = CommandHistory::new();
+        let run = |history: &mut CommandHistory, phrase: &str, success: bool| {
+            history.add_entry(CommandEntry {
+                timestamp: Local::now(),
+                transcription: phrase.to_string(),
+                command_matched: Some(phrase.to_string()),
+                command_executed: None,
+                success,
+                duration_ms: 100,
+                audio_duration_ms: 800,
+                outcome: None,
+            });
+        };
+        for _ in 0..3 {
+            run(&mut history, "open terminal", true);
+            run(&mut history, "open browser", true);
+        }
+        run(&mut history, "open terminal", true);
+        run(&mut history, "open email", false);
+        run(&mut history, "open editor", true);
+        run(&mut history, "open terminal", true);
+        run(&mut history, "open editor", true);
+        // Seen once, so not a habit yet
+        run(&mut history, "lock screen", true);
+        run(&mut history, "play music", true);
+        
+        let suggestions = SmartSuggestions::default();
+        // The failed "open email" doesn't break the terminal -> editor pair
+        assert_eq!(suggestions.get_sequence_suggestions(&history, "open terminal", 5), ["open browser", "open editor"]);
+        assert_eq!(suggestions.get_sequence_suggestions(&history, "open terminal", 1), ["open browser"]);
+        assert!(suggestions.get_sequence_suggestions(&history, "lock screen", 5).is_empty());
+        assert_eq!(suggestions.get_sequence_suggestions(&history, "open browser", 5), ["open terminal"]);
+        
+        run(&mut history, "open terminal", true);
+        let commands: HashMap<String, Shortcut> = ["open terminal", "open browser", "open editor"]
+            .into_iter()
+            .map(|phrase| (phrase.to_string(), Shortcut::from("true")))
+            .collect();
+        let failed = suggestions.get_suggestions_for_failed_command("zzz", &history, &commands);
+        assert!(failed.contains(&"After open terminal you usually run: open browser or open editor".to_string()), "{:?}", failed);
+    }
+
     #[test]
     fn test_levenshtein_similarity() {
-        let suggestions = SmartSuggestions::new();
+        let suggestions = SmartSuggestions::default();
         
         assert_eq!(suggestions.levenshtein_similarity("", ""), 1.0);
         assert_eq!(suggestions.levenshtein_similarity("abc", "abc"), 1.0);
         assert!(suggestions.levenshtein_similarity("abc", "abd") > 0.6);
         assert!(suggestions.levenshtein_similarity("kitten", "sitting") < 0.6);
+        assert!((suggestions.levenshtein_similarity("kitten", "sitting") - (1.0 - 3.0 / 7.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_levenshtein_counts_characters_not_bytes() {
+        let suggestions = SmartSuggestions::default();
+        
+        // One substitution in four characters, though "é" is two bytes (five bytes in all)
+        assert!((suggestions.levenshtein_similarity("café", "cafe") - 0.75).abs() < 1e-6);
+        assert!((suggestions.levenshtein_similarity("ouvrir le navigateur", "ouvrir le navigàteur") - 0.95).abs() < 1e-6);
+        assert_eq!(suggestions.levenshtein_similarity("ñandú", "ñandú"), 1.0);
+        assert_eq!(suggestions.levenshtein_similarity("", "über"), 0.0);
     }
 }
\ No newline at end of file
@@ -0,0 +1,571 @@
+use std::time::Instant;
+
+use log::{debug, info, warn};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContext};
+
+pub struct TranscribeOptions {
+    // Whisper language code, or "auto" to detect it per recording
+    pub language: String,
+    pub default_language: String,
+    pub min_language_confidence: f32,
+    pub decoding: DecodingParams,
+    // Text Whisper treats as already said, biasing it towards these words
+    pub initial_prompt: Option<String>,
+    pub task: Task,
+}
+
+// Translate turns speech in any language into English text, so English shortcut phrases
+// still match. `language` keeps naming what is spoken.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, JsonSchema)]
+pub enum Task {
+    #[default]
+    #[serde(alias = "transcribe")]
+    Transcribe,
+    #[serde(alias = "translate")]
+    Translate,
+}
+
+#[derive(Debug, PartialEq)]
+struct TaskParams<'a> {
+    language: &'a str,
+    translate: bool,
+}
+
+// English-only models have no translate task, and English needs no translating
+fn task_params(task: Task, language: &str, multilingual: bool) -> TaskParams<'_> {
+    TaskParams {
+        language,
+        translate: task == Task::Translate && multilingual && language != "en",
+    }
+}
+
+// Presets for the Whisper knobs, so users pick what they say rather than tune parameters
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, JsonSchema)]
+pub enum DecodingProfile {
+    // Short phrases: one segment, no carried-over context, primed with the shortcut phrases
+    Command,
+    // Free speech, decoded the way this assistant always has
+    #[default]
+    Dictation,
+    // Beam search for the best transcription at a few times the cost
+    Accurate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecodingParams {
+    // 1 decodes greedily
+    pub beam_size: u32,
+    // Greedy candidates sampled per segment at non-zero temperature
+    pub best_of: u32,
+    pub single_segment: bool,
+    pub suppress_non_speech: bool,
+    pub no_context: bool,
+    pub prompt_with_shortcuts: bool,
+}
+
+impl DecodingProfile {
+    pub fn params(self) -> DecodingParams {
+        match self {
+            DecodingProfile::Command => DecodingParams {
+                beam_size: 1,
+                best_of: 1,
+                single_segment: true,
+                suppress_non_speech: true,
+                no_context: true,
+                prompt_with_shortcuts: true,
+            },
+            DecodingProfile::Dictation => DecodingParams {
+                beam_size: 1,
+                best_of: 1,
+                single_segment: false,
+                suppress_non_speech: false,
+                no_context: false,
+                prompt_with_shortcuts: false,
+            },
+            DecodingProfile::Accurate => DecodingParams {
+                beam_size: 5,
+                best_of: 1,
+                single_segment: false,
+                suppress_non_speech: true,
+                no_context: false,
+                prompt_with_shortcuts: false,
+            },
+        }
+    }
+}
+
+// Individual settings that win over the chosen profile
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct DecodingOverrides {
+    pub beam_size: Option<u32>,
+    pub single_segment: Option<bool>,
+    pub suppress_non_speech: Option<bool>,
+    pub no_context: Option<bool>,
+    pub prompt_with_shortcuts: Option<bool>,
+}
+
+impl DecodingOverrides {
+    pub fn apply(&self, mut params: DecodingParams) -> DecodingParams {
+        params.beam_size = self.beam_size.unwrap_or(params.beam_size).max(1);
+        params.single_segment = self.single_segment.unwrap_or(params.single_segment);
+        params.suppress_non_speech = self.suppress_non_speech.unwrap_or(params.suppress_non_speech);
+        params.no_context = self.no_context.unwrap_or(params.no_context);
+        params.prompt_with_shortcuts = self.prompt_with_shortcuts.unwrap_or(params.prompt_with_shortcuts);
+        params
+    }
+}
+
+// Explicit choice of Whisper's search strategy, e.g. {"strategy": "BeamSearch", "beam_size": 5}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "strategy")]
+pub enum SamplingSettings {
+    Greedy {
+        #[serde(default = "default_best_of")]
+        best_of: u32,
+    },
+    BeamSearch {
+        #[serde(default = "default_beam_size")]
+        beam_size: u32,
+    },
+}
+
+fn default_best_of() -> u32 {
+    1
+}
+
+fn default_beam_size() -> u32 {
+    5
+}
+
+impl SamplingSettings {
+    pub fn apply(&self, mut params: DecodingParams) -> DecodingParams {
+        match *self {
+            SamplingSettings::Greedy { best_of } => {
+                params.beam_size = 1;
+                params.best_of = best_of.max(1);
+            }
+            SamplingSettings::BeamSearch { beam_size } => params.beam_size = beam_size.max(1),
+        }
+        params
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hypothesis {
+    pub text: String,
+    // Mean probability of the decoded text tokens
+    pub confidence: f32,
+    pub segments: Vec<TranscriptSegment>,
+}
+
+// One of Whisper's segments, with its position in the audio
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptSegment {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub text: String,
+    // Mean probability of the segment's text tokens
+    pub confidence: f32,
+}
+
+impl TranscriptSegment {
+    // "[00:01.200 --> 00:02.500] text (93%)", like whisper.cpp's own output
+    pub fn timeline_line(&self) -> String {
+        format!(
+            "[{} --> {}] {} ({:.0}%)",
+            format_timestamp(self.start_ms),
+            format_timestamp(self.end_ms),
+            self.text,
+            self.confidence * 100.0
+        )
+    }
+}
+
+fn format_timestamp(ms: u64) -> String {
+    format!("{:02}:{:02}.{:03}", ms / 60_000, ms / 1000 % 60, ms % 1000)
+}
+
+// The plain transcription, for callers that don't care about timing
+pub fn segments_text(segments: &[TranscriptSegment]) -> String {
+    segments.iter().map(|s| s.text.as_str()).collect::<Vec<_>>().join(" ")
+}
+
+// Nothing but Whisper's annotations for non-speech, such as "[BLANK_AUDIO]", "(silence)"
+// or "*coughs*", which is what a keypress with nobody talking usually transcribes to
+pub fn is_blank(text: &str) -> bool {
+    let mut closing = None;
+    for c in text.chars() {
+        match (closing, c) {
+            (None, '[') => closing = Some(']'),
+            (None, '(') => closing = Some(')'),
+            (None, '*') => closing = Some('*'),
+            (Some(end), c) if c == end => closing = None,
+            (None, c) if c.is_alphanumeric() => return false,
+            _ => {}
+        }
+    }
+    true
+}
+
+// Language codes Whisper knows, in whisper.cpp's order
+const LANGUAGES: &[&str] = &[
+    "en", "zh", "de", "es", "ru", "ko", "fr", "ja", "pt", "tr", "pl", "ca", "nl", "ar",
+    "sv", "it", "id", "hi", "fi", "vi", "he", "uk", "el", "ms", "cs", "ro", "da", "hu",
+    "ta", "no", "th", "ur", "hr", "bg", "lt", "la", "mi", "ml", "cy", "sk", "te", "fa",
+    "lv", "bn", "sr", "az", "sl", "kn", "et", "mk", "br", "eu", "is", "hy", "ne", "mn",
+    "bs", "kk", "sq", "sw", "gl", "mr", "pa", "si", "km", "sn", "yo", "so", "af", "oc",
+    "ka", "be", "tg", "sd", "gu", "am", "yi", "lo", "uz", "fo", "ht", "ps", "tk", "nn",
+    "mt", "sa", "lb", "my", "bo", "tl", "mg", "as", "tt", "haw", "ln", "ha", "ba", "jw",
+    "su", "yue",
+];
+
+// "auto" is only meaningful for `language`, so callers decide whether to allow it
+pub fn check_language(code: &str) -> Result<(), String> {
+    if LANGUAGES.contains(&code) {
+        return Ok(());
+    }
+    let hint = match LANGUAGES.iter().find(|c| c.eq_ignore_ascii_case(code)) {
+        Some(c) => format!("codes are lowercase, use '{}'", c),
+        None => "use a code like 'en', 'es' or 'fr'".to_string(),
+    };
+    Err(format!("unknown language '{}' ({})", code, hint))
+}
+
+// Temperatures used to sample alternative decodings for the N-best list
+const NBEST_TEMPERATURES: &[f32] = &[0.0, 0.2, 0.4, 0.6, 0.8, 1.0];
+
+// The GPU backend whisper.cpp was built with (cargo features `cuda` or `metal`), if any
+const GPU_BACKEND: Option<&str> = if cfg!(feature = "cuda") {
+    Some("CUDA")
+} else if cfg!(feature = "metal") {
+    Some("Metal")
+} else {
+    None
+};
+
+fn gpu_backend(use_gpu: bool, built_with: Option<&'static str>) -> Option<&'static str> {
+    built_with.filter(|_| use_gpu)
+}
+
+// Loading the model dominates the cost of a short transcription, so it is loaded once
+// and every recording only creates a fresh state on it
+pub fn load_model(whisper_path: &str, use_gpu: bool) -> Result<WhisperContext, Box<dyn std::error::Error>> {
+    let started = Instant::now();
+    let ctx = match gpu_backend(use_gpu, GPU_BACKEND) {
+        Some(backend) => match new_context(whisper_path, true) {
+            Ok(ctx) => {
+                info!("Whisper backend: GPU ({})", backend);
+                ctx
+            }
+            Err(e) => {
+                warn!("Whisper couldn't start on the GPU ({}), falling back to the CPU.", e);
+                let ctx = new_context(whisper_path, false)?;
+                info!("Whisper backend: CPU");
+                ctx
+            }
+        },
+        None => {
+            let ctx = new_context(whisper_path, false)?;
+            if use_gpu {
+                info!("Whisper backend: CPU (built without GPU support)");
+            } else {
+                info!("Whisper backend: CPU");
+            }
+            ctx
+        }
+    };
+    debug!("Whisper model loaded in {} ms", started.elapsed().as_millis());
+    Ok(ctx)
+}
+
+fn new_context(whisper_path: &str, use_gpu: bool) -> Result<WhisperContext, whisper_rs::WhisperError> {
+    let mut params = whisper_rs::WhisperContextParameters::default();
+    params.use_gpu(use_gpu);
+    WhisperContext::new_with_params(whisper_path, params)
+}
+
+pub fn transcribe_audio(ctx: &WhisperContext, audio_path: &str, options: &TranscribeOptions) -> Result<String, Box<dyn std::error::Error>> {
+    // Load and process audio
+    let samples = crate::audio::load_audio_file(audio_path)?;
+    Ok(segments_text(&transcribe_segments(ctx, &samples, options)?))
+}
+
+pub fn transcribe_segments(ctx: &WhisperContext, samples: &[f32], options: &TranscribeOptions) -> Result<Vec<TranscriptSegment>, Box<dyn std::error::Error>> {
+    let mut hypotheses = transcribe_nbest(ctx, samples, options, 1)?;
+    Ok(hypotheses.remove(0).segments)
+}
+
+// For audio that is already in memory as 16 kHz mono, such as a finished recording or
+// the wake listener's window
+pub fn transcribe_samples(ctx: &WhisperContext, samples: &[f32], options: &TranscribeOptions) -> Result<String, Box<dyn std::error::Error>> {
+    let mut hypotheses = transcribe_nbest(ctx, samples, options, 1)?;
+    Ok(hypotheses.remove(0).text)
+}
+
+// Decodes the audio several times at increasing temperatures and returns the distinct
+// results, most confident first. Each extra hypothesis costs a full decode.
+pub fn transcribe_nbest(ctx: &WhisperContext, samples: &[f32], options: &TranscribeOptions, count: usize) -> Result<Vec<Hypothesis>, Box<dyn std::error::Error>> {
+    let started = Instant::now();
+    
+    // Create a state for processing
+    let mut state = ctx.create_state()?;
+    
+    let language = if options.language == "auto" {
+        if ctx.is_multilingual() {
+            detect_language(&mut state, samples, options)?
+        } else {
+            // English-only models can't detect anything else
+            "en".to_string()
+        }
+    } else {
+        options.language.clone()
+    };
+    
+    let mut hypotheses: Vec<Hypothesis> = Vec::new();
+    for &temperature in NBEST_TEMPERATURES.iter().take(count.max(1)) {
+        let hypothesis = decode(ctx, &mut state, samples, &language, temperature, options)?;
+        match hypotheses.iter_mut().find(|h| h.text.eq_ignore_ascii_case(&hypothesis.text)) {
+            Some(existing) => existing.confidence = existing.confidence.max(hypothesis.confidence),
+            None => hypotheses.push(hypothesis),
+        }
+    }
+    
+    hypotheses.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    debug!("Transcribed in {} ms", started.elapsed().as_millis());
+    Ok(hypotheses)
+}
+
+fn decode(ctx: &WhisperContext, state: &mut whisper_rs::WhisperState, samples: &[f32], language: &str, temperature: f32, options: &TranscribeOptions) -> Result<Hypothesis, Box<dyn std::error::Error>> {
+    // Create parameters
+    let decoding = &options.decoding;
+    let strategy = if decoding.beam_size > 1 {
+        SamplingStrategy::BeamSearch { beam_size: decoding.beam_size as i32, patience: -1.0 }
+    } else {
+        SamplingStrategy::Greedy { best_of: decoding.best_of as i32 }
+    };
+    let mut params = FullParams::new(strategy);
+    params.set_print_special(false);
+    params.set_print_progress(false);
+    params.set_print_timestamps(false);
+    let task = task_params(options.task, language, ctx.is_multilingual());
+    params.set_language(Some(task.language));
+    params.set_translate(task.translate);
+    params.set_temperature(temperature);
+    params.set_single_segment(decoding.single_segment);
+    params.set_suppress_non_speech_tokens(decoding.suppress_non_speech);
+    params.set_no_context(decoding.no_context);
+    if let Some(prompt) = &options.initial_prompt {
+        params.set_initial_prompt(prompt);
+    }
+    
+    // Run whisper
+    state.full(params, samples)?;
+    
+    // Get transcription
+    let num_segments = state.full_n_segments()?;
+    let mut segments = Vec::new();
+    let mut probability_sum = 0.0;
+    let mut token_count = 0;
+    
+    for i in 0..num_segments {
+        let mut segment_sum = 0.0;
+        let mut segment_count = 0;
+        for t in 0..state.full_n_tokens(i)? {
+            // Special tokens (timestamps, end of text, ...) come after the text vocabulary
+            if state.full_get_token_id(i, t)? >= ctx.token_eot() {
+                continue;
+            }
+            segment_sum += state.full_get_token_prob(i, t)?;
+            segment_count += 1;
+        }
+        probability_sum += segment_sum;
+        token_count += segment_count;
+        
+        let text = state.full_get_segment_text(i)?;
+        let text = text.trim();
+        if !text.is_empty() {
+            // Whisper counts time in centiseconds
+            segments.push(TranscriptSegment {
+                start_ms: state.full_get_segment_t0(i)?.max(0) as u64 * 10,
+                end_ms: state.full_get_segment_t1(i)?.max(0) as u64 * 10,
+                text: text.to_string(),
+                confidence: if segment_count > 0 { segment_sum / segment_count as f32 } else { 0.0 },
+            });
+        }
+    }
+    
+    Ok(Hypothesis {
+        text: segments_text(&segments),
+        confidence: if token_count > 0 { probability_sum / token_count as f32 } else { 0.0 },
+        segments,
+    })
+}
+
+fn detect_language(state: &mut whisper_rs::WhisperState, samples: &[f32], options: &TranscribeOptions) -> Result<String, Box<dyn std::error::Error>> {
+    let threads = std::thread::available_parallelism().map(|n| n.get().min(4)).unwrap_or(1);
+    state.pcm_to_mel(samples, threads)?;
+    let probabilities = state.lang_detect(0, threads)?;
+    
+    let (best_id, probability) = probabilities
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(id, p)| (id as i32, *p))
+        .ok_or("Language detection returned no results")?;
+    let detected = whisper_rs::get_lang_str(best_id).unwrap_or(&options.default_language);
+    
+    let language = resolve_language(detected, probability, options.min_language_confidence, &options.default_language);
+    if language == detected {
+        info!("Detected language: {} ({:.0}% confidence)", detected, probability * 100.0);
+    } else {
+        info!(
+            "Detected language: {} ({:.0}% confidence, below threshold), using {}",
+            detected, probability * 100.0, language
+        );
+    }
+    
+    Ok(language.to_string())
+}
+
+// Short commands often give unreliable detections, so only trust confident ones
+pub fn resolve_language<'a>(detected: &'a str, probability: f32, min_confidence: f32, default_language: &'a str) -> &'a str {
+    if probability >= min_confidence {
+        detected
+    } else {
+        default_language
+    }
+}
+
+// Word-level edit distance over the reference length, ignoring case and punctuation
+pub fn word_error_rate(reference: &str, hypothesis: &str) -> f32 {
+    let reference = crate::builtins::normalize(reference);
+    let hypothesis = crate::builtins::normalize(hypothesis);
+    let reference: Vec<&str> = reference.split_whitespace().collect();
+    let hypothesis: Vec<&str> = hypothesis.split_whitespace().collect();
+    if reference.is_empty() {
+        return if hypothesis.is_empty() { 0.0 } else { 1.0 };
+    }
+
+    let mut previous: Vec<usize> = (0..=hypothesis.len()).collect();
+    for (i, ref_word) in reference.iter().enumerate() {
+        let mut current = vec![i + 1; hypothesis.len() + 1];
+        for (j, hyp_word) in hypothesis.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ref_word != hyp_word);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[hypothesis.len()] as f32 / reference.len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decoding_profile_params() {
+        let command = DecodingProfile::Command.params();
+        assert_eq!(command.beam_size, 1);
+        assert!(command.single_segment && command.suppress_non_speech && command.no_context && command.prompt_with_shortcuts);
+
+        let dictation = DecodingProfile::Dictation.params();
+        assert_eq!(dictation.beam_size, 1);
+        assert!(!dictation.single_segment && !dictation.no_context && !dictation.prompt_with_shortcuts);
+
+        let accurate = DecodingProfile::Accurate.params();
+        assert_eq!(accurate.beam_size, 5);
+        assert!(!accurate.single_segment && !accurate.no_context);
+    }
+
+    #[test]
+    fn test_decoding_overrides_win_over_profile() {
+        let overrides = DecodingOverrides { beam_size: Some(3), prompt_with_shortcuts: Some(false), ..DecodingOverrides::default() };
+        let params = overrides.apply(DecodingProfile::Command.params());
+        assert_eq!(params.beam_size, 3);
+        assert!(!params.prompt_with_shortcuts);
+        assert!(params.single_segment);
+
+        assert_eq!(DecodingOverrides::default().apply(DecodingProfile::Accurate.params()), DecodingProfile::Accurate.params());
+    }
+
+    #[test]
+    fn test_sampling_settings_choose_strategy() {
+        let beam: SamplingSettings = serde_json::from_str(r#"{"strategy": "BeamSearch", "beam_size": 8}"#).unwrap();
+        assert_eq!(beam.apply(DecodingProfile::Command.params()).beam_size, 8);
+
+        let greedy: SamplingSettings = serde_json::from_str(r#"{"strategy": "Greedy", "best_of": 3}"#).unwrap();
+        let params = greedy.apply(DecodingProfile::Accurate.params());
+        assert_eq!((params.beam_size, params.best_of), (1, 3));
+
+        // Omitted sizes fall back to the usual values
+        let beam: SamplingSettings = serde_json::from_str(r#"{"strategy": "BeamSearch"}"#).unwrap();
+        assert_eq!(beam, SamplingSettings::BeamSearch { beam_size: 5 });
+    }
+
+    #[test]
+    fn test_translate_keeps_the_spoken_language() {
+        assert_eq!(task_params(Task::Translate, "es", true), TaskParams { language: "es", translate: true });
+        assert_eq!(task_params(Task::Transcribe, "es", true), TaskParams { language: "es", translate: false });
+        assert_eq!(task_params(Task::Translate, "en", true), TaskParams { language: "en", translate: false });
+        // A .en model transcribes instead
+        assert_eq!(task_params(Task::Translate, "en", false), TaskParams { language: "en", translate: false });
+        assert_eq!(serde_json::from_str::<Task>("\"translate\"").unwrap(), Task::Translate);
+    }
+
+    #[test]
+    fn test_gpu_only_when_built_and_enabled() {
+        assert_eq!(gpu_backend(true, Some("CUDA")), Some("CUDA"));
+        assert_eq!(gpu_backend(false, Some("Metal")), None);
+        assert_eq!(gpu_backend(true, None), None);
+    }
+
+    #[test]
+    fn test_check_language() {
+        assert!(check_language("es").is_ok());
+        assert!(check_language("haw").is_ok());
+        assert!(check_language("EN").unwrap_err().contains("use 'en'"));
+        assert!(check_language("english").unwrap_err().contains("unknown language 'english'"));
+    }
+
+    #[test]
+    fn test_blank_transcriptions() {
+        assert!(is_blank(""));
+        assert!(is_blank("  \n"));
+        assert!(is_blank(" [BLANK_AUDIO]"));
+        assert!(is_blank("(silence) ..."));
+        assert!(is_blank("[Music] *coughs*"));
+        assert!(!is_blank("Open terminal."));
+        assert!(!is_blank("[BLANK_AUDIO] open terminal"));
+    }
+
+    #[test]
+    fn test_word_error_rate_tolerance() {
+        let expected = "The quick brown fox jumps over the lazy dog";
+        assert_eq!(word_error_rate(expected, "the quick brown fox jumps over the lazy dog."), 0.0);
+        // One misheard word out of nine is well within a 30% tolerance
+        assert!(word_error_rate(expected, "The quick brown box jumps over the lazy dog") <= 0.3);
+        // Output from the wrong language model is nowhere near
+        assert!(word_error_rate(expected, "Der schnelle braune Fuchs") > 0.3);
+    }
+
+    #[test]
+    fn test_low_confidence_detection_falls_back() {
+        assert_eq!(resolve_language("de", 0.92, 0.5, "en"), "de");
+        assert_eq!(resolve_language("nl", 0.31, 0.5, "en"), "en");
+        assert_eq!(resolve_language("es", 0.5, 0.5, "en"), "es");
+    }
+
+    #[test]
+    fn test_segments_flatten_and_timeline() {
+        let segments = vec![
+            TranscriptSegment { start_ms: 0, end_ms: 1200, text: "Open the".to_string(), confidence: 0.8 },
+            TranscriptSegment { start_ms: 1200, end_ms: 62_050, text: "terminal.".to_string(), confidence: 0.934 },
+        ];
+        assert_eq!(segments_text(&segments), "Open the terminal.");
+        assert_eq!(segments_text(&[]), "");
+        assert_eq!(segments[1].timeline_line(), "[00:01.200 --> 01:02.050] terminal. (93%)");
+    }
+}
@@ -1,72 +1,419 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use tts::Tts;
+use std::time::{Duration, Instant};
+use chrono::{Local, NaiveTime};
+use log::{debug, error, warn};
+use tts::{Backends, Tts};
 
 pub struct VoiceFeedback {
     tts: Arc<Mutex<Option<Tts>>>,
-    enabled: bool,
+    // One worker speaks queued utterances in order, each after the previous one ends
+    queue: Sender<Utterance>,
+    // Bumped to drop everything queued so far
+    generation: Arc<AtomicUsize>,
+    // Utterances sent to the worker and not yet spoken or dropped
+    pending: Arc<AtomicUsize>,
+    // Shared between clones, like `muted`, so a settings reload reaches every thread
+    enabled: Arc<AtomicBool>,
+    quiet_hours: Arc<Mutex<Option<(NaiveTime, NaiveTime)>>>,
+    natural_readback: Arc<AtomicBool>,
+    // Shared between clones so "mute" silences every thread at once
+    muted: Arc<AtomicBool>,
 }
 
-impl VoiceFeedback {
-    pub fn new(enabled: bool) -> Self {
-        let tts = if enabled {
-            match Tts::default() {
-                Ok(mut tts_instance) => {
-                    // Set voice properties
-                    let _ = tts_instance.set_rate(1.2); // Slightly faster speech
-                    let _ = tts_instance.set_pitch(1.0); // Normal pitch
-                    let _ = tts_instance.set_volume(0.9); // Slightly lower volume
-                    Some(tts_instance)
-                }
-                Err(e) => {
-                    eprintln!("Failed to initialize TTS: {}. Voice feedback disabled.", e);
-                    None
+// The range is [start, end); a start after the end wraps past midnight (22:00-07:00)
+pub fn in_quiet_hours(quiet_hours: (NaiveTime, NaiveTime), time: NaiveTime) -> bool {
+    let (start, end) = quiet_hours;
+    if start <= end {
+        time >= start && time < end
+    } else {
+        time >= start || time < end
+    }
+}
+
+// Backends the tts crate can build on this platform, under the names used in settings
+#[cfg(target_os = "linux")]
+const ENGINES: &[(&str, Backends)] = &[("speech-dispatcher", Backends::SpeechDispatcher)];
+#[cfg(target_os = "macos")]
+const ENGINES: &[(&str, Backends)] = &[("avfoundation", Backends::AvFoundation), ("appkit", Backends::AppKit)];
+#[cfg(windows)]
+const ENGINES: &[(&str, Backends)] = &[("winrt", Backends::WinRt)];
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+const ENGINES: &[(&str, Backends)] = &[];
+
+// "SpeechDispatcher", "speech_dispatcher" and "speech-dispatcher" all name the same engine
+fn find_engine<'a, T: Copy>(requested: &str, engines: &'a [(&'a str, T)]) -> Option<(&'a str, T)> {
+    let simplify = |name: &str| name.to_lowercase().replace(['-', '_', ' '], "");
+    engines
+        .iter()
+        .find(|(name, _)| simplify(name) == simplify(requested))
+        .copied()
+}
+
+// The requested engine if it exists and starts, otherwise the platform default
+fn create_tts(engine: Option<&str>) -> Result<Tts, tts::Error> {
+    if let Some(requested) = engine {
+        match find_engine(requested, ENGINES) {
+            Some((name, backend)) => match Tts::new(backend) {
+                Ok(tts) => {
+                    debug!("🔊 TTS engine: {}", name);
+                    return Ok(tts);
                 }
+                Err(e) => warn!("TTS engine '{}' failed to start ({}), using the default.", name, e),
+            },
+            None => {
+                let names: Vec<&str> = ENGINES.iter().map(|(name, _)| *name).collect();
+                warn!(
+                    "TTS engine '{}' isn't available here (choose from: {}), using the default.",
+                    requested,
+                    names.join(", ")
+                );
             }
-        } else {
+        }
+    }
+
+    let tts = Tts::default()?;
+    debug!("🔊 TTS engine: platform default");
+    Ok(tts)
+}
+
+// Lines for --list-voices: each voice's name and language, from the configured engine
+pub fn describe_voices(engine: Option<&str>) -> Result<Vec<String>, tts::Error> {
+    let voices = create_tts(engine)?.voices()?;
+    Ok(voices
+        .iter()
+        .map(|voice| format!("{}  ({})", voice.name(), voice.language()))
+        .collect())
+}
+
+// Names are matched ignoring case; an engine's exact voice id works too
+fn find_voice<'a, V>(requested: &str, voices: &'a [V], name_and_id: impl Fn(&V) -> (String, String)) -> Option<&'a V> {
+    voices.iter().find(|voice| {
+        let (name, id) = name_and_id(voice);
+        name.eq_ignore_ascii_case(requested) || id == requested
+    })
+}
+
+const SPEAKING_POLL: Duration = Duration::from_millis(20);
+// An engine that never reports the end of an utterance mustn't stall the queue
+const MAX_UTTERANCE: Duration = Duration::from_secs(30);
+
+struct Utterance {
+    text: String,
+    generation: usize,
+}
+
+fn speech_worker<F: FnMut(&str)>(utterances: Receiver<Utterance>, generation: Arc<AtomicUsize>, pending: Arc<AtomicUsize>, mut speak: F) {
+    for utterance in utterances {
+        // Skipped if it was queued before the last clear
+        if utterance.generation >= generation.load(Ordering::Relaxed) {
+            speak(&utterance.text);
+        }
+        pending.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+fn spawn_speech_worker(tts: Arc<Mutex<Option<Tts>>>, generation: Arc<AtomicUsize>, pending: Arc<AtomicUsize>) -> Sender<Utterance> {
+    let (sender, utterances) = mpsc::channel();
+    thread::spawn(move || {
+        speech_worker(utterances, generation, pending, |text| {
+            if let Some(tts) = tts.lock().unwrap().as_mut() {
+                let _ = tts.speak(text, false);
+            }
+            // The lock is released between polls so stop() can still get in
+            let is_speaking = || tts.lock().unwrap().as_ref().is_some_and(|tts| tts.is_speaking().unwrap_or(false));
+            wait_until_quiet(is_speaking, MAX_UTTERANCE);
+        });
+    });
+    sender
+}
+
+// Polls until `is_speaking` goes false; false if it was still speaking after `max_wait`
+pub fn wait_until_quiet<F: FnMut() -> bool>(mut is_speaking: F, max_wait: Duration) -> bool {
+    let started = Instant::now();
+    while is_speaking() {
+        if started.elapsed() >= max_wait {
+            return false;
+        }
+        thread::sleep(SPEAKING_POLL);
+    }
+    true
+}
+
+// Spells out symbols TTS engines read badly ("42%" -> "42 percent", "3ms" -> "3 milliseconds").
+// Only for what gets spoken; logs and matching keep the raw text.
+pub fn spoken_form(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| {
+            let stripped = word.trim_end_matches(|c: char| ",.;:!?)".contains(c));
+            let trailing = &word[stripped.len()..];
+            let spoken = match stripped {
+                ">" => "greater than".to_string(),
+                "<" => "less than".to_string(),
+                ">=" => "at least".to_string(),
+                "<=" => "at most".to_string(),
+                "=" => "equals".to_string(),
+                "&" => "and".to_string(),
+                _ => spell_unit(stripped).unwrap_or_else(|| stripped.to_string()),
+            };
+            format!("{}{}", spoken, trailing)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Command output can be pages long; only the start is worth hearing
+pub fn truncate_for_speech(text: &str, max_chars: usize) -> String {
+    let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.chars().count() <= max_chars {
+        return collapsed;
+    }
+    let cut: String = collapsed.chars().take(max_chars).collect();
+    let cut = cut.rsplit_once(' ').map_or(cut.as_str(), |(words, _)| words);
+    format!("{}...", cut)
+}
+
+// Rate, pitch and volume in the engine's own units; the defaults are slightly fast and quiet
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Prosody {
+    pub rate: f32,
+    pub pitch: f32,
+    pub volume: f32,
+}
+
+impl Default for Prosody {
+    fn default() -> Self {
+        Prosody { rate: 1.2, pitch: 1.0, volume: 0.9 }
+    }
+}
+
+// Values outside what the engine reports are pulled to the nearest end of its range
+fn clamp_to_range(property: &str, value: f32, min: f32, max: f32) -> f32 {
+    if min > max || (min..=max).contains(&value) {
+        return value;
+    }
+    let clamped = value.clamp(min, max);
+    warn!("TTS {} {} is outside this engine's range {} to {}, using {}.", property, value, min, max, clamped);
+    clamped
+}
+
+fn apply_prosody(tts: &mut Tts, prosody: &Prosody) {
+    let features = tts.supported_features();
+    if features.rate {
+        let rate = clamp_to_range("rate", prosody.rate, tts.min_rate(), tts.max_rate());
+        if let Err(e) = tts.set_rate(rate) {
+            warn!("Couldn't set TTS rate ({}), keeping the default.", e);
+        }
+    } else {
+        debug!("TTS engine doesn't support changing the rate");
+    }
+    if features.pitch {
+        let pitch = clamp_to_range("pitch", prosody.pitch, tts.min_pitch(), tts.max_pitch());
+        if let Err(e) = tts.set_pitch(pitch) {
+            warn!("Couldn't set TTS pitch ({}), keeping the default.", e);
+        }
+    } else {
+        debug!("TTS engine doesn't support changing the pitch");
+    }
+    if features.volume {
+        let volume = clamp_to_range("volume", prosody.volume, tts.min_volume(), tts.max_volume());
+        if let Err(e) = tts.set_volume(volume) {
+            warn!("Couldn't set TTS volume ({}), keeping the default.", e);
+        }
+    } else {
+        debug!("TTS engine doesn't support changing the volume");
+    }
+}
+
+fn select_voice(tts: &mut Tts, requested: &str) {
+    let voices = match tts.voices() {
+        Ok(voices) => voices,
+        Err(e) => {
+            warn!("Can't list TTS voices ({}), using the default voice.", e);
+            return;
+        }
+    };
+    match find_voice(requested, &voices, |voice| (voice.name(), voice.id())) {
+        Some(voice) => match tts.set_voice(voice) {
+            Ok(()) => debug!("🔊 TTS voice: {}", voice.name()),
+            Err(e) => warn!("TTS voice '{}' couldn't be selected ({}), using the default voice.", requested, e),
+        },
+        None => warn!(
+            "No TTS voice named '{}', using the default voice. Run with --list-voices to see them.",
+            requested
+        ),
+    }
+}
+
+fn spell_unit(word: &str) -> Option<String> {
+    for (suffix, unit) in [("%", "percent"), ("ms", "milliseconds"), ("MB", "megabytes"), ("Hz", "hertz")] {
+        if let Some(number) = word.strip_suffix(suffix) {
+            let is_number = !number.is_empty() && number.chars().all(|c| c.is_ascii_digit() || c == '.' || c == ',');
+            if is_number {
+                return Some(format!("{} {}", number, unit));
+            }
+        }
+    }
+    None
+}
+
+fn start_engine(engine: Option<&str>) -> Option<Tts> {
+    match create_tts(engine) {
+        Ok(mut tts) => {
+            apply_prosody(&mut tts, &Prosody::default());
+            Some(tts)
+        }
+        Err(e) => {
+            error!("Failed to initialize TTS: {}. Voice feedback disabled.", e);
             None
-        };
+        }
+    }
+}
+
+impl VoiceFeedback {
+    pub fn new(enabled: bool, engine: Option<&str>) -> Self {
+        let tts = if enabled { start_engine(engine) } else { None };
+
+        let generation = Arc::new(AtomicUsize::new(0));
+        let pending = Arc::new(AtomicUsize::new(0));
+        let tts = Arc::new(Mutex::new(tts));
 
         VoiceFeedback {
-            tts: Arc::new(Mutex::new(tts)),
-            enabled,
+            queue: spawn_speech_worker(Arc::clone(&tts), Arc::clone(&generation), Arc::clone(&pending)),
+            tts,
+            generation,
+            pending,
+            enabled: Arc::new(AtomicBool::new(enabled)),
+            quiet_hours: Arc::new(Mutex::new(None)),
+            natural_readback: Arc::new(AtomicBool::new(false)),
+            muted: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    pub fn with_quiet_hours(self, quiet_hours: Option<(NaiveTime, NaiveTime)>) -> Self {
+        self.set_quiet_hours(quiet_hours);
+        self
+    }
+
+    pub fn with_voice(self, name: Option<&str>) -> Self {
+        self.set_voice(name);
+        self
+    }
+
+    pub fn with_prosody(self, prosody: &Prosody) -> Self {
+        self.set_prosody(prosody);
+        self
+    }
+
+    pub fn with_natural_readback(self, natural_readback: bool) -> Self {
+        self.set_natural_readback(natural_readback);
+        self
+    }
+
+    // Turning feedback on starts the engine if it isn't running yet; turning it off drops
+    // whatever is still queued
+    pub fn set_enabled(&self, enabled: bool, engine: Option<&str>) {
+        if enabled {
+            let mut tts = self.tts.lock().unwrap();
+            if tts.is_none() {
+                *tts = start_engine(engine);
+            }
+        } else {
+            self.clear_queue();
+        }
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn set_quiet_hours(&self, quiet_hours: Option<(NaiveTime, NaiveTime)>) {
+        *self.quiet_hours.lock().unwrap() = quiet_hours;
+    }
+
+    // Keeps the current voice, with a warning, if there's no such voice
+    pub fn set_voice(&self, name: Option<&str>) {
+        if let Some(requested) = name {
+            if let Some(tts) = self.tts.lock().unwrap().as_mut() {
+                select_voice(tts, requested);
+            }
+        }
+    }
+
+    pub fn set_prosody(&self, prosody: &Prosody) {
+        if let Some(tts) = self.tts.lock().unwrap().as_mut() {
+            apply_prosody(tts, prosody);
+        }
+    }
+
+    pub fn set_natural_readback(&self, natural_readback: bool) {
+        self.natural_readback.store(natural_readback, Ordering::Relaxed);
+    }
+
+    fn prepare(&self, text: &str) -> String {
+        if self.natural_readback.load(Ordering::Relaxed) {
+            spoken_form(text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    pub fn set_muted(&self, muted: bool) {
+        self.muted.store(muted, Ordering::Relaxed);
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    // Disabled, muted or in quiet hours
+    pub fn is_silent(&self) -> bool {
+        !self.is_enabled()
+            || self.muted.load(Ordering::Relaxed)
+            || self.quiet_hours.lock().unwrap()
+            .is_some_and(|range| in_quiet_hours(range, Local::now().time()))
+    }
+
     pub fn speak(&self, text: &str) {
-        if !self.enabled {
+        if self.is_silent() {
             return;
         }
 
-        let tts_clone = Arc::clone(&self.tts);
-        let text = text.to_string();
-        
-        // Spawn a thread to avoid blocking
-        thread::spawn(move || {
-            if let Ok(mut tts_guard) = tts_clone.lock() {
-                if let Some(ref mut tts) = *tts_guard {
-                    let _ = tts.speak(&text, false);
-                }
-            }
+        self.pending.fetch_add(1, Ordering::AcqRel);
+        let sent = self.queue.send(Utterance {
+            text: self.prepare(text),
+            generation: self.generation.load(Ordering::Relaxed),
         });
+        if sent.is_err() {
+            self.pending.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+
+    // Waits for everything queued so far to be spoken, e.g. before the process exits;
+    // false if it was still talking after `max_wait`
+    pub fn finish(&self, max_wait: Duration) -> bool {
+        wait_until_quiet(|| self.pending.load(Ordering::Acquire) > 0, max_wait)
+    }
+
+    // Drops everything still waiting to be spoken and cuts off the current utterance
+    pub fn clear_queue(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        self.stop();
     }
 
-    #[allow(dead_code)]
     pub fn speak_blocking(&self, text: &str) {
-        if !self.enabled {
+        if self.is_silent() {
             return;
         }
 
         if let Ok(mut tts_guard) = self.tts.lock() {
             if let Some(ref mut tts) = *tts_guard {
-                let _ = tts.speak(text, true);
+                let _ = tts.speak(self.prepare(text), true);
             }
         }
     }
 
-    #[allow(dead_code)]
     pub fn is_speaking(&self) -> bool {
-        if !self.enabled {
+        if !self.is_enabled() {
             return false;
         }
 
@@ -78,25 +425,138 @@ impl VoiceFeedback {
         false
     }
 
-    #[allow(dead_code)]
     pub fn stop(&self) {
-        if !self.enabled {
-            return;
-        }
-
         if let Ok(mut tts_guard) = self.tts.lock() {
             if let Some(ref mut tts) = *tts_guard {
                 let _ = tts.stop();
             }
         }
     }
+
+    // Keeps the microphone from picking up our own voice: wait for speech to end,
+    // and cut it off if it runs past `max_wait`
+    pub fn silence_before_capture(&self, max_wait: Duration) {
+        // Nothing queued earlier may start once capture is running
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        if !wait_until_quiet(|| self.is_speaking(), max_wait) {
+            self.stop();
+        }
+    }
 }
 
 impl Clone for VoiceFeedback {
     fn clone(&self) -> Self {
         VoiceFeedback {
             tts: Arc::clone(&self.tts),
-            enabled: self.enabled,
+            queue: self.queue.clone(),
+            generation: Arc::clone(&self.generation),
+            pending: Arc::clone(&self.pending),
+            enabled: Arc::clone(&self.enabled),
+            quiet_hours: Arc::clone(&self.quiet_hours),
+            natural_readback: Arc::clone(&self.natural_readback),
+            muted: Arc::clone(&self.muted),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(text: &str) -> NaiveTime {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn test_quiet_hours_window() {
+        let afternoon = (time("13:00"), time("15:00"));
+        assert!(in_quiet_hours(afternoon, time("14:30")));
+        assert!(!in_quiet_hours(afternoon, time("15:00")));
+        assert!(!in_quiet_hours(afternoon, time("09:00")));
+
+        // Crossing midnight
+        let night = (time("22:00"), time("07:00"));
+        assert!(in_quiet_hours(night, time("23:15")));
+        assert!(in_quiet_hours(night, time("03:00")));
+        assert!(!in_quiet_hours(night, time("07:00")));
+        assert!(!in_quiet_hours(night, time("12:00")));
+    }
+
+    #[test]
+    fn test_find_engine_falls_back() {
+        let engines = [("speech-dispatcher", 1), ("winrt", 2)];
+        assert_eq!(find_engine("SpeechDispatcher", &engines), Some(("speech-dispatcher", 1)));
+        assert_eq!(find_engine("WinRT", &engines), Some(("winrt", 2)));
+        // Unknown engines leave the choice to the platform default
+        assert_eq!(find_engine("espeak", &engines), None);
+    }
+
+    #[test]
+    fn test_clamp_to_range() {
+        assert_eq!(clamp_to_range("rate", 1.2, 0.1, 2.0), 1.2);
+        assert_eq!(clamp_to_range("rate", 5.0, 0.1, 2.0), 2.0);
+        assert_eq!(clamp_to_range("volume", -1.0, 0.0, 1.0), 0.0);
+        // A backend reporting a nonsense range doesn't get to change the value
+        assert_eq!(clamp_to_range("pitch", 1.0, 2.0, 0.0), 1.0);
+    }
+
+    #[test]
+    fn test_find_voice_by_name_or_id() {
+        let voices = [("Samantha", "com.apple.voice.Samantha"), ("Daniel", "com.apple.voice.Daniel")];
+        let name_and_id = |voice: &(&str, &str)| (voice.0.to_string(), voice.1.to_string());
+        assert_eq!(find_voice("daniel", &voices, name_and_id), Some(&voices[1]));
+        assert_eq!(find_voice("com.apple.voice.Samantha", &voices, name_and_id), Some(&voices[0]));
+        // No partial matches: a typo keeps the default voice
+        assert_eq!(find_voice("Dan", &voices, name_and_id), None);
+    }
+
+    #[test]
+    fn test_wait_until_quiet_gate() {
+        // Capture is held back until speech ends
+        let mut polls = 0;
+        assert!(wait_until_quiet(|| { polls += 1; polls < 4 }, Duration::from_secs(1)));
+        assert_eq!(polls, 4);
+
+        // Speech that never ends is given up on after the bound
+        let started = Instant::now();
+        assert!(!wait_until_quiet(|| true, Duration::from_millis(60)));
+        assert!(started.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_speech_queue_keeps_order_and_clears() {
+        let (sender, utterances) = mpsc::channel();
+        let generation = Arc::new(AtomicUsize::new(0));
+        let say = |text: &str, generation: usize| sender.send(Utterance { text: text.to_string(), generation }).unwrap();
+
+        say("Recording", 0);
+        say("Processing", 0);
+        // Cleared before it got its turn
+        generation.store(1, Ordering::Relaxed);
+        say("Executed open terminal", 1);
+        drop(sender);
+
+        let mut spoken = Vec::new();
+        // What VoiceFeedback::speak would have counted for the three above
+        let pending = Arc::new(AtomicUsize::new(3));
+        speech_worker(utterances, Arc::clone(&generation), Arc::clone(&pending), |text| spoken.push(text.to_string()));
+        assert_eq!(spoken, vec!["Executed open terminal"]);
+        // Dropped utterances count as done too, so finish() doesn't wait for them
+        assert_eq!(pending.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_truncate_for_speech() {
+        assert_eq!(truncate_for_speech("  Already up\nto date.\n", 40), "Already up to date.");
+        assert_eq!(truncate_for_speech("error: could not compile crate", 20), "error: could not...");
+        assert_eq!(truncate_for_speech("ééééé", 3), "ééé...");
+    }
+
+    #[test]
+    fn test_spoken_form_expands_symbols() {
+        assert_eq!(spoken_form("Success rate: 42%"), "Success rate: 42 percent");
+        assert_eq!(spoken_form("Average time 3ms, > 10 commands."), "Average time 3 milliseconds, greater than 10 commands.");
+        // Words that merely end like a unit are left alone
+        assert_eq!(spoken_form("open items"), "open items");
+    }
+}
@@ -0,0 +1,162 @@
+// Hands-free mode. A second recorder keeps the last few seconds of audio, and every so
+// often, if anything loud enough was said, that window is transcribed. Hearing the wake
+// word hands over to the normal record-and-command flow until the main loop resumes it.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+
+use crate::builtins::normalize;
+
+// 20 ms at 16 kHz, the same windows the silence detector measures
+const LOUDNESS_WINDOW: usize = 320;
+
+// The most recent `capacity` samples
+pub struct RollingBuffer {
+    samples: VecDeque<f32>,
+    capacity: usize,
+}
+
+impl RollingBuffer {
+    pub fn new(capacity: usize) -> Self {
+        RollingBuffer {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, samples: &[f32]) {
+        self.samples.extend(samples);
+        let excess = self.samples.len().saturating_sub(self.capacity);
+        self.samples.drain(..excess);
+    }
+
+    pub fn to_vec(&self) -> Vec<f32> {
+        self.samples.iter().copied().collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    pub fn has_speech(&self, threshold: f32) -> bool {
+        has_speech(&self.to_vec(), threshold)
+    }
+}
+
+// Whether any short stretch is above `threshold` RMS; quiet windows are never transcribed,
+// which keeps the listener cheap while nobody is talking
+pub fn has_speech(samples: &[f32], threshold: f32) -> bool {
+    samples.chunks(LOUDNESS_WINDOW).any(|window| {
+        let sum_squares: f32 = window.iter().map(|s| s * s).sum();
+        (sum_squares / window.len() as f32).sqrt() >= threshold
+    })
+}
+
+// Whole words only, so "hey assistant" is heard in "Hey, assistant." but not in "they assistants"
+pub fn heard_wake_word(transcription: &str, wake_word: &str) -> bool {
+    let heard = normalize(transcription);
+    let wake_word = normalize(wake_word);
+    if wake_word.is_empty() {
+        return false;
+    }
+    let heard: Vec<&str> = heard.split_whitespace().collect();
+    let wake_word: Vec<&str> = wake_word.split_whitespace().collect();
+    heard.windows(wake_word.len()).any(|words| words == wake_word.as_slice())
+}
+
+// The main loop's end: pauses the listener while it records and picks up detections
+pub struct WakeListener {
+    active: Arc<AtomicBool>,
+    heard: Receiver<()>,
+}
+
+// The listening thread's end
+pub struct WakeSignal {
+    active: Arc<AtomicBool>,
+    heard: Sender<()>,
+}
+
+pub fn channel() -> (WakeListener, WakeSignal) {
+    let active = Arc::new(AtomicBool::new(true));
+    let (sender, receiver) = mpsc::channel();
+    (
+        WakeListener { active: Arc::clone(&active), heard: receiver },
+        WakeSignal { active, heard: sender },
+    )
+}
+
+impl WakeListener {
+    pub fn pause(&self) {
+        self.active.store(false, Ordering::Release);
+    }
+
+    pub fn resume(&self) {
+        self.active.store(true, Ordering::Release);
+    }
+
+    pub fn heard(&self) -> bool {
+        self.heard.try_iter().count() > 0
+    }
+}
+
+impl WakeSignal {
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Acquire)
+    }
+
+    // Pauses itself until resumed; false once the main loop is gone
+    pub fn notify(&self) -> bool {
+        self.active.store(false, Ordering::Release);
+        self.heard.send(()).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heard_wake_word() {
+        assert!(heard_wake_word("Hey, assistant.", "hey assistant"));
+        assert!(heard_wake_word("okay so hey assistant open the browser", "Hey Assistant"));
+        assert!(!heard_wake_word("they assistants", "hey assistant"));
+        assert!(!heard_wake_word("assistant hey", "hey assistant"));
+        assert!(!heard_wake_word("hey assistant", ""));
+    }
+
+    #[test]
+    fn test_rolling_buffer_keeps_the_latest_window() {
+        let mut buffer = RollingBuffer::new(4);
+        buffer.push(&[0.0, 0.0, 0.0]);
+        assert!(!buffer.has_speech(0.01));
+        buffer.push(&[0.5, 0.5, 0.5]);
+        assert_eq!(buffer.to_vec(), vec![0.0, 0.5, 0.5, 0.5]);
+        assert!(buffer.has_speech(0.01));
+
+        // A single loud stretch counts even when the rest of the window is quiet
+        let mut samples = vec![0.0; LOUDNESS_WINDOW * 50];
+        samples[LOUDNESS_WINDOW * 10..LOUDNESS_WINDOW * 11].fill(0.2);
+        assert!(has_speech(&samples, 0.1));
+        assert!(!has_speech(&samples, 0.3));
+    }
+
+    #[test]
+    fn test_signal_pauses_until_resumed() {
+        let (listener, signal) = channel();
+        assert!(signal.is_active() && !listener.heard());
+
+        assert!(signal.notify());
+        assert!(!signal.is_active());
+        assert!(listener.heard());
+        assert!(!listener.heard());
+
+        listener.resume();
+        assert!(signal.is_active());
+        listener.pause();
+        assert!(!signal.is_active());
+        drop(listener);
+        assert!(!signal.notify());
+    }
+}
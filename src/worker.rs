@@ -0,0 +1,82 @@
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+
+// Runs jobs one at a time on a background thread, in the order they were submitted,
+// so slow transcriptions don't block the key-polling loop.
+pub struct TranscriptionQueue<J> {
+    sender: Option<SyncSender<J>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl<J: Send + 'static> TranscriptionQueue<J> {
+    pub fn new<F>(capacity: usize, mut handler: F) -> Self
+    where
+        F: FnMut(J) + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel::<J>(capacity);
+        let handle = thread::spawn(move || {
+            for job in receiver {
+                handler(job);
+            }
+        });
+
+        TranscriptionQueue {
+            sender: Some(sender),
+            handle: Some(handle),
+        }
+    }
+
+    // Hands the job back if the queue is full or the worker has stopped
+    pub fn submit(&self, job: J) -> Result<(), J> {
+        match &self.sender {
+            Some(sender) => sender.try_send(job).map_err(|e| match e {
+                TrySendError::Full(job) | TrySendError::Disconnected(job) => job,
+            }),
+            None => Err(job),
+        }
+    }
+}
+
+impl<J> Drop for TranscriptionQueue<J> {
+    // Let queued jobs finish before the queue goes away
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_second_recording_queues_while_first_is_processing() {
+        let (started_tx, started_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let processed = Arc::new(Mutex::new(Vec::new()));
+        let processed_clone = Arc::clone(&processed);
+
+        let queue = TranscriptionQueue::new(2, move |job: u32| {
+            started_tx.send(job).unwrap();
+            if job == 1 {
+                // Simulate a slow transcription
+                release_rx.recv().unwrap();
+            }
+            processed_clone.lock().unwrap().push(job);
+        });
+
+        assert!(queue.submit(1).is_ok());
+        assert_eq!(started_rx.recv().unwrap(), 1);
+
+        // The worker is still busy with the first job, but the second is accepted
+        assert!(queue.submit(2).is_ok());
+        assert!(processed.lock().unwrap().is_empty());
+
+        release_tx.send(()).unwrap();
+        drop(queue);
+        assert_eq!(*processed.lock().unwrap(), vec![1, 2]);
+    }
+}